@@ -247,12 +247,24 @@ def fn_at_mu():
 def fn_rtio_log():
     return types.TBuiltinFunction("rtio_log")
 
+def fn_publish_result():
+    return types.TBuiltinFunction("publish_result")
+
 def fn_subkernel_await():
     return types.TBuiltinFunction("subkernel_await")
 
 def fn_subkernel_preload():
     return types.TBuiltinFunction("subkernel_preload")
 
+def fn_subkernel_preload_group():
+    return types.TBuiltinFunction("subkernel_preload_group")
+
+def fn_subkernel_cache_results():
+    return types.TBuiltinFunction("subkernel_cache_results")
+
+def fn_subkernel_cache_clear():
+    return types.TBuiltinFunction("subkernel_cache_clear")
+
 def fn_subkernel_send():
     return types.TBuiltinFunction("subkernel_send")
 
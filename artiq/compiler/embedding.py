@@ -115,7 +115,12 @@ class EmbeddingMap:
             "0:ZeroDivisionError",
             "0:LinAlgError",
             "UnwrapNoneError",
-            "CXPError"
+            "CXPError",
+            "WatchdogTimeout",
+            "RPCError",
+            "CXPTimeout",
+            "0:RecursionError",
+            "RTIOError"
         ])
 
     def preallocate_runtime_exception_names(self, names):
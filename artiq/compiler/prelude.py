@@ -55,10 +55,14 @@ def globals():
         # ARTIQ utility functions
         "rtio_log":             builtins.fn_rtio_log(),
         "core_log":             builtins.fn_print(),
+        "publish_result":       builtins.fn_publish_result(),
 
         # ARTIQ subkernel utility functions
         "subkernel_await":     builtins.fn_subkernel_await(),
         "subkernel_preload":   builtins.fn_subkernel_preload(),
+        "subkernel_preload_group": builtins.fn_subkernel_preload_group(),
+        "subkernel_cache_results": builtins.fn_subkernel_cache_results(),
+        "subkernel_cache_clear": builtins.fn_subkernel_cache_clear(),
         "subkernel_send":      builtins.fn_subkernel_send(),
         "subkernel_recv":      builtins.fn_subkernel_recv(), 
     }
@@ -2501,6 +2501,13 @@ class ARTIQIRGenerator(algorithm.Visitor):
             self.polymorphic_print([self.visit(arg) for arg in args],
                                    separator=" ", suffix="\x1D", as_rtio=True)
             return ir.Constant(None, builtins.TNone())
+        elif types.is_builtin(typ, "publish_result"):
+            if len(node.args) == 2 and len(node.keywords) == 0:
+                channel = self.visit(node.args[0])
+                value = self.visit(node.args[1])
+            else:
+                assert False
+            return self.append(ir.Builtin("publish_result", [channel, value], builtins.TNone()))
         elif types.is_builtin(typ, "delay"):
             if len(node.args) == 1 and len(node.keywords) == 0:
                 arg = self.visit(node.args[0])
@@ -2553,6 +2560,21 @@ class ARTIQIRGenerator(algorithm.Visitor):
             sid = ir.Constant(fn.sid, builtins.TInt32())
             dest = ir.Constant(fn.destination, builtins.TInt32())
             return self.append(ir.Builtin("subkernel_preload", [sid, dest], builtins.TNone()))
+        elif types.is_builtin(typ, "subkernel_preload_group"):
+            fns = [types.get_method_function(arg.type) if types.is_method(arg.type) else arg.type
+                   for arg in node.args]
+            # the group is preloaded to the destination of its first subkernel
+            dest = ir.Constant(fns[0].destination, builtins.TInt32())
+            sids = [ir.Constant(fn.sid, builtins.TInt32()) for fn in fns]
+            return self.append(ir.Builtin("subkernel_preload_group", [dest] + sids, builtins.TNone()))
+        elif types.is_builtin(typ, "subkernel_cache_results") or \
+                types.is_builtin(typ, "subkernel_cache_clear"):
+            fn = node.args[0].type
+            if types.is_method(fn):
+                fn = types.get_method_function(fn)
+            sid = ir.Constant(fn.sid, builtins.TInt32())
+            args = [sid] + [self.visit(arg) for arg in node.args[1:]]
+            return self.append(ir.Builtin(typ.name, args, builtins.TNone()))
         elif types.is_builtin(typ, "subkernel_send"):
             if len(node.args) == 3 and len(node.keywords) == 0:
                 dest = self.visit(node.args[0])
@@ -1268,6 +1268,21 @@ class Inferencer(algorithm.Visitor):
                             arg.loc, None)
             else:
                 diagnose(valid_forms())
+        elif types.is_builtin(typ, "publish_result"):
+            valid_forms = lambda: [
+                valid_form("publish_result(channel: numpy.int32, value: V) -> None"),
+            ]
+
+            self._unify(node.type, builtins.TNone(),
+                        node.loc, None)
+
+            if len(node.args) == 2 and len(node.keywords) == 0:
+                arg = node.args[0]
+
+                self._unify(arg.type, builtins.TInt32(),
+                            arg.loc, None)
+            else:
+                diagnose(valid_forms())
         elif types.is_builtin(typ, "now"):
             simple_form("now() -> float",
                         [], builtins.TFloat())
@@ -1343,6 +1358,47 @@ class Inferencer(algorithm.Visitor):
                                 node.loc, None)
             else:
                 diagnose(valid_forms())
+        elif types.is_builtin(typ, "subkernel_preload_group"):
+            valid_forms = lambda: [
+                valid_form("subkernel_preload_group(f: subkernel, *deps: subkernel) -> None")
+            ]
+            if len(node.args) >= 1 and len(node.keywords) == 0:
+                for arg in node.args:
+                    if types.is_var(arg.type):
+                        pass  # undetermined yet
+                    elif not (types.is_method(arg.type) or types.is_function(arg.type) or
+                              types.is_subkernel(arg.type)):
+                        diagnose(valid_forms())
+                self._unify(node.type, builtins.TNone(),
+                            node.loc, None)
+            else:
+                diagnose(valid_forms())
+        elif types.is_builtin(typ, "subkernel_cache_results") or \
+                types.is_builtin(typ, "subkernel_cache_clear"):
+            if types.is_builtin(typ, "subkernel_cache_results"):
+                valid_forms = lambda: [
+                    valid_form("subkernel_cache_results(f: subkernel, enable: bool) -> None")
+                ]
+                arg_count = 2
+            else:
+                valid_forms = lambda: [
+                    valid_form("subkernel_cache_clear(f: subkernel) -> None")
+                ]
+                arg_count = 1
+            if len(node.args) == arg_count and len(node.keywords) == 0:
+                arg0 = node.args[0].type
+                if types.is_var(arg0):
+                    pass  # undetermined yet
+                elif not (types.is_method(arg0) or types.is_function(arg0) or
+                          types.is_subkernel(arg0)):
+                    diagnose(valid_forms())
+                if arg_count == 2:
+                    self._unify(node.args[1].type, builtins.TBool(),
+                                node.args[1].loc, None)
+                self._unify(node.type, builtins.TNone(),
+                            node.loc, None)
+            else:
+                diagnose(valid_forms())
         elif types.is_builtin(typ, "subkernel_send"):
             valid_forms = lambda: [
                 valid_form("subkernel_send(dest: numpy.int?, name: str, value: V) -> None"),
@@ -397,6 +397,8 @@ class LLVMIRGenerator:
             llty = ll.FunctionType(llvoid, [lli32, llsliceptr, llptrptr])
         elif name == "rpc_recv":
             llty = ll.FunctionType(lli32, [llptr])
+        elif name == "publish_result":
+            llty = ll.FunctionType(llvoid, [lli32, llptrptr, llsliceptr])
 
         elif name == "subkernel_send_message":
             llty = ll.FunctionType(llvoid, [lli32, lli1, lli8, lli8, llsliceptr, llptrptr])
@@ -404,6 +406,12 @@ class LLVMIRGenerator:
             llty = ll.FunctionType(llvoid, [lli32, lli8, lli1])
         elif name == "subkernel_await_finish":
             llty = ll.FunctionType(llvoid, [lli32, lli64])
+        elif name == "subkernel_load_group":
+            llty = ll.FunctionType(llvoid, [llsliceptr, lli8])
+        elif name == "subkernel_cache_results":
+            llty = ll.FunctionType(llvoid, [lli32, lli1])
+        elif name == "subkernel_cache_clear":
+            llty = ll.FunctionType(llvoid, [lli32])
         elif name == "subkernel_await_message":
             llty = ll.FunctionType(lli8, [lli32, lli64, llsliceptr, lli8, lli8])
 
@@ -426,7 +434,7 @@ class LLVMIRGenerator:
             llglobal = ll.Function(self.llmodule, llty, name)
             if name in ("__artiq_raise", "__artiq_resume", "llvm.trap"):
                 llglobal.attributes.add("noreturn")
-            if name in ("rtio_log", "rpc_send", "rpc_send_async",
+            if name in ("rtio_log", "rpc_send", "rpc_send_async", "publish_result",
                         self.target.print_function):
                 llglobal.attributes.add("nounwind")
             if name.find("__py_") == 0:
@@ -1420,10 +1428,28 @@ class LLVMIRGenerator:
             lldest = ll.Constant(lli8, insn.operands[1].value)
             return self.llbuilder.call(self.llbuiltin("subkernel_load_run"), [llsid, lldest, ll.Constant(lli1, 0)], 
                                 name="subkernel.preload")
+        elif insn.op == "subkernel_preload_group":
+            lldest = ll.Constant(lli8, insn.operands[0].value)
+            sids = [operand.value for operand in insn.operands[1:]]
+            llsidsty = ll.ArrayType(lli32, len(sids))
+            llsids = self.llbuilder.alloca(llsidsty, name="subkernel.group.ids")
+            self.llbuilder.store(ll.Constant(llsidsty, sids), llsids)
+            llids = ll.Constant(llslice, ll.Undefined)
+            llids = self.llbuilder.insert_value(llids, self.llbuilder.bitcast(llsids, llptr), 0)
+            llids = self.llbuilder.insert_value(llids, ll.Constant(lli32, len(sids)), 1)
+            llidsptr = self.llbuilder.alloca(llslice, name="subkernel.group")
+            self.llbuilder.store(llids, llidsptr)
+            return self.llbuilder.call(self.llbuiltin("subkernel_load_group"), [llidsptr, lldest],
+                                       name="subkernel.preload.group")
+        elif insn.op in ("subkernel_cache_results", "subkernel_cache_clear"):
+            return self.llbuilder.call(self.llbuiltin(insn.op), [self.map(op) for op in insn.operands])
         elif insn.op == "subkernel_send":
             llmsgid = self.map(insn.operands[0])
             lldest = self.map(insn.operands[1])
             return self._build_subkernel_message(llmsgid, lldest, [insn.operands[2]])
+        elif insn.op == "publish_result":
+            llchannel = self.map(insn.operands[0])
+            return self._build_published_result(llchannel, insn.operands[1])
         elif insn.op == "subkernel_recv":
             llmsgid = self.map(insn.operands[0])
             lltimeout = self.map(insn.operands[1])
@@ -1753,6 +1779,32 @@ class LLVMIRGenerator:
                             [llid, llisreturn, lldest, llargcount, lltagptr, llargs])
         return self.llbuilder.call(self.llbuiltin("llvm.stackrestore"), [llstackptr])
 
+    def _build_published_result(self, llchannel, value):
+        # the value is sent like the argument of an async RPC, returning None
+        tag = self._build_arg_tag([value], call_type="published result")
+        tag += b"n"
+
+        llstackptr = self.llbuilder.call(self.llbuiltin("llvm.stacksave"), [],
+                                         name="publish.stack")
+        lltag = self.llconst_of_const(ir.Constant(tag, builtins.TStr()))
+        lltagptr = self.llbuilder.alloca(lltag.type)
+        self.llbuilder.store(lltag, lltagptr)
+
+        llargs = self.llbuilder.alloca(llptr, ll.Constant(lli32, 1),
+                                       name="publish.args")
+        if builtins.is_none(value.type):
+            llargslot = self.llbuilder.alloca(llunit, name="publish.arg")
+        else:
+            llvalue = self.map(value)
+            llargslot = self.llbuilder.alloca(llvalue.type, name="publish.arg")
+            self.llbuilder.store(llvalue, llargslot)
+        llargslot = self.llbuilder.bitcast(llargslot, llptr)
+        self.llbuilder.store(llargslot, llargs)
+
+        self.llbuilder.call(self.llbuiltin("publish_result"),
+                            [llchannel, llargs, lltagptr])
+        return self.llbuilder.call(self.llbuiltin("llvm.stackrestore"), [llstackptr])
+
     def _build_subkernel_return(self, insn):
         # builds a remote return.
         # unlike args, return only sends one thing.
@@ -10,6 +10,22 @@ def cache_get(key: TStr) -> TList(TInt32):
 def cache_put(key: TStr, value: TList(TInt32)) -> TNone:
     raise NotImplementedError("syscall not simulated")
 
+@syscall(flags={"nounwind"})
+def cache_try_put(key: TStr, value: TList(TInt32)) -> TBool:
+    raise NotImplementedError("syscall not simulated")
+
+@syscall
+def cache_cas(key: TStr, expected: TInt32, new: TInt32) -> TBool:
+    raise NotImplementedError("syscall not simulated")
+
+@syscall(flags={"nounwind"})
+def cache_get_bytes(key: TStr) -> TBytes:
+    raise NotImplementedError("syscall not simulated")
+
+@syscall
+def cache_put_bytes(key: TStr, value: TBytes) -> TNone:
+    raise NotImplementedError("syscall not simulated")
+
 
 class CoreCache:
     """Core device cache access"""
@@ -43,3 +59,66 @@ class CoreCache:
         :param list value: a list of 32-bit integers
         """
         cache_put(key, value)
+
+    @kernel
+    def try_put(self, key, value):
+        """Same as :meth:`put`, but return ``False`` instead of raising
+        :class:`~artiq.coredevice.exceptions.CacheError` if the value has
+        been extracted with :meth:`get` by the running kernel, in which case
+        the cache is left unchanged.
+
+        :param str key: cache key
+        :param list value: a list of 32-bit integers
+        :return: whether the value was put into the cache
+        """
+        return cache_try_put(key, value)
+
+    @kernel
+    def compare_and_swap(self, key, expected, new):
+        """Replace the first element of a value in the core device cache
+        with `new` if it is equal to `expected`, in a single step.
+
+        The rest of the value is left unchanged. A missing or empty value
+        counts as a first element of 0, and is replaced with ``[new]``.
+        This allows simple locks shared by kernels, e.g. taken by swapping
+        0 for 1 and released by swapping 1 for 0.
+
+        Like :meth:`put`, this raises :class:`~artiq.coredevice.exceptions.CacheError`
+        if the value has been extracted with :meth:`get` by the running kernel.
+
+        :param str key: cache key
+        :param int expected: expected first element
+        :param int new: replacement first element
+        :return: whether the element was replaced
+        """
+        return cache_cas(key, expected, new)
+
+    @kernel
+    def get_bytes(self, key):
+        """Extract a byte string from the core device cache.
+
+        Byte strings are stored separately from the lists of integers handled
+        by :meth:`get` and :meth:`put`, so the same key may hold one of each.
+        The same rule applies: once extracted, the value cannot be replaced
+        using :meth:`put_bytes` until all kernel functions finish executing.
+
+        If the cache does not contain any byte string associated with `key`,
+        an empty byte string is returned.
+
+        :param str key: cache key
+        :return: a byte string
+        """
+        return cache_get_bytes(key)
+
+    @kernel
+    def put_bytes(self, key, value):
+        """Put a byte string into the core device cache, e.g. a serialized
+        structure of parameters. The value will persist until reboot.
+
+        To remove a value from the cache, call :meth:`put_bytes` with an
+        empty byte string.
+
+        :param str key: cache key
+        :param bytes value: a byte string
+        """
+        cache_put_bytes(key, value)
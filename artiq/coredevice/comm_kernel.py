@@ -1,4 +1,5 @@
 import struct
+import zlib
 import logging
 import traceback
 import numpy
@@ -25,6 +26,7 @@ class Request(Enum):
     RPCException = 8
 
     SubkernelUpload = 9
+    LoadKernelChecked = 10
 
 
 class Reply(Enum):
@@ -38,8 +40,16 @@ class Reply(Enum):
     KernelException = 9
 
     RPCRequest = 10
+    # RPC request with the arguments in the debug format (see set_rpc_format)
+    RPCRequestDebug = 19
 
     ClockFailure = 15
+    KernelPanic = 16
+    DMADump = 17
+    PartialResult = 18
+    PublishResult = 20
+    # frame sizes of the backtrace of the KernelException that follows
+    BacktraceFrameSizes = 21
 
 
 class UnsupportedDevice(Exception):
@@ -158,7 +168,7 @@ receivers = {
 
 class CommKernelDummy:
     def __init__(self):
-        pass
+        self.result_subscribers = dict()
 
     def load(self, kernel_library):
         pass
@@ -191,8 +201,18 @@ class CommKernel:
         self.host = host
         self.port = port
         self.read_buffer = bytearray()
+        # set while decoding an RPC argument of known size from read_buffer
+        self.read_limited = False
         self.write_buffer = bytearray()
-
+        # raw DMA traces sent by the kernel with dma_dump, by name
+        self.dma_dumps = dict()
+        # data registered with set_partial_result by the last kernel that
+        # terminated with an exception
+        self.partial_result = None
+        # frame sizes sent for the backtrace of the next exception, if any
+        self.frame_sizes = None
+        # callbacks for the results published by kernels, by channel
+        self.result_subscribers = dict()
 
     def open(self):
         if hasattr(self, "socket"):
@@ -230,6 +250,8 @@ class CommKernel:
     def _read(self, length):
         # cache the reads to avoid frequent call to recv
         while len(self.read_buffer) < length:
+            if self.read_limited:
+                raise EOFError
             # the number is just the maximum amount
             # when there is not much data, it would return earlier
             diff = length - len(self.read_buffer)
@@ -376,8 +398,11 @@ class CommKernel:
                                     .format(runtime_id))
 
     def load(self, kernel_library):
-        self._write_header(Request.LoadKernel)
+        # The core device checks the CRC before linking the kernel, so that
+        # a transfer error fails the load instead of the kernel.
+        self._write_header(Request.LoadKernelChecked)
         self._write_bytes(kernel_library)
+        self._write(struct.pack(self.endian + "L", zlib.crc32(kernel_library)))
         self._flush()
 
         self._read_header()
@@ -400,6 +425,8 @@ class CommKernel:
             self._read_expect(Reply.LoadCompleted)
 
     def run(self):
+        self.partial_result = None
+        self.frame_sizes = None
         self._write_empty(Request.RunKernel)
         self._flush()
         logger.debug("running kernel")
@@ -425,6 +452,41 @@ class CommKernel:
             else:
                 args.append(value)
 
+    def _receive_rpc_args_debug(self, embedding_map, service_id):
+        # A mismatch is returned rather than raised, after reading the
+        # remaining arguments, so that the kernel can still be replied to.
+        args, kwargs, error = [], {}, None
+        while True:
+            size = self._read_int32()
+            if size == 0:
+                return args, kwargs, error
+            # Decode each argument from exactly the bytes the core device
+            # sent for it, so that a mismatch points at the argument.
+            data = self._read(size)
+            remaining = self.read_buffer
+            self.read_buffer = bytearray(data)
+            self.read_limited = True
+            try:
+                value = self._receive_rpc_value(embedding_map)
+                decoded = size - len(self.read_buffer)
+            except EOFError:
+                decoded = None
+            finally:
+                self.read_buffer = remaining
+                self.read_limited = False
+            index = len(args) + len(kwargs)
+            if decoded != size:
+                if error is None:
+                    error = IOError("RPC argument {} of service {} with tag {}: "
+                                    "{} bytes sent, {} decoded".format(
+                                        index, service_id, repr(chr(data[0])), size,
+                                        "more than that" if decoded is None else decoded))
+                continue
+            if isinstance(value, RPCKeyword):
+                kwargs[value.name] = value.value
+            else:
+                args.append(value)
+
     def _skip_rpc_value(self, tags):
         tag = chr(tags.pop(0))
         if tag == "t":
@@ -573,10 +635,14 @@ class CommKernel:
         else:
             return msg
 
-    def _serve_rpc(self, embedding_map):
+    def _serve_rpc(self, embedding_map, debug_format=False):
         is_async = self._read_bool()
         service_id = self._read_int32()
-        args, kwargs = self._receive_rpc_args(embedding_map)
+        error = None
+        if debug_format:
+            args, kwargs, error = self._receive_rpc_args_debug(embedding_map, service_id)
+        else:
+            args, kwargs = self._receive_rpc_args(embedding_map)
         return_tags = self._read_bytes()
 
         if service_id == 0:
@@ -587,10 +653,16 @@ class CommKernel:
                      (" (async)" if is_async else ""), args, kwargs, return_tags)
 
         if is_async:
+            if error is not None:
+                logger.error("async RPC dropped: %s", error)
+                return
             service(*args, **kwargs)
             return
 
         try:
+            if error is not None:
+                # passed on to the kernel, which is waiting for the reply
+                raise error
             result = service(*args, **kwargs)
         except RPCReturnValueError as exn:
             raise
@@ -652,6 +724,26 @@ class CommKernel:
                                  result, result, service)
             self._flush()
 
+    def _serve_published_result(self, embedding_map):
+        channel = self._read_int32()
+        args, kwargs = self._receive_rpc_args(embedding_map)
+        self._read_bytes()  # return tag, always None
+        value = args[0] if len(args) == 1 else tuple(args)
+        logger.debug("result published on channel %d: %r", channel, value)
+
+        callback = self.result_subscribers.get(channel)
+        if callback is None:
+            logger.warning("no subscriber for the result published on channel %d",
+                           channel)
+            return
+        # The kernel does not wait for the callback, so its exceptions
+        # cannot be passed on.
+        try:
+            callback(value)
+        except Exception:
+            logger.error("subscriber for channel %d raised an exception",
+                         channel, exc_info=True)
+
     def _serve_exception(self, embedding_map, symbolizer, demangler):
         exception_count = self._read_int32()
         nested_exceptions = []
@@ -693,11 +785,16 @@ class CommKernel:
             backtrace.append(self._read_int32())
             stack_pointers.append(self._read_int32())
 
+        # older firmware and the Zynq firmware do not send the frame sizes
+        frame_sizes, self.frame_sizes = self.frame_sizes, None
+        if frame_sizes is not None and len(frame_sizes) != len(backtrace):
+            frame_sizes = None
+
         self._process_async_error()
 
         traceback = list(symbolizer(backtrace))
         core_exn = exceptions.CoreException(nested_exceptions, exception_info,
-                                            traceback, stack_pointers)
+                                            traceback, stack_pointers, frame_sizes)
 
         if core_exn.id == 0:
             python_exn_type = getattr(exceptions, core_exn.name.split('.')[-1])
@@ -705,7 +802,8 @@ class CommKernel:
             python_exn_type = embedding_map.retrieve_object(core_exn.id)
 
         try:
-            message = nested_exceptions[0][1].format(*nested_exceptions[0][2])
+            message = nested_exceptions[0][1].format(
+                *exceptions.decode_params(nested_exceptions[0][1], nested_exceptions[0][2]))
         except:
             message = nested_exceptions[0][1]
             logger.error("Couldn't format exception message", exc_info=True)
@@ -718,6 +816,7 @@ class CommKernel:
                 f"reconstructed ({ex})"
             )
         python_exn.artiq_core_exception = core_exn
+        python_exn.artiq_partial_result = self.partial_result
         raise python_exn
 
     def _process_async_error(self):
@@ -735,10 +834,30 @@ class CommKernel:
             self._read_header()
             if self._read_type == Reply.RPCRequest:
                 self._serve_rpc(embedding_map)
+            elif self._read_type == Reply.RPCRequestDebug:
+                self._serve_rpc(embedding_map, debug_format=True)
+            elif self._read_type == Reply.PublishResult:
+                self._serve_published_result(embedding_map)
+            elif self._read_type == Reply.BacktraceFrameSizes:
+                self.frame_sizes = [self._read_int32()
+                                    for _ in range(self._read_int32())]
             elif self._read_type == Reply.KernelException:
                 self._serve_exception(embedding_map, symbolizer, demangler)
             elif self._read_type == Reply.ClockFailure:
                 raise exceptions.ClockFailure
+            elif self._read_type == Reply.DMADump:
+                name = self._read_string()
+                self.dma_dumps[name] = bytes(self._read_bytes())
+            elif self._read_type == Reply.PartialResult:
+                self.partial_result = bytes(self._read_bytes())
+            elif self._read_type == Reply.KernelPanic:
+                file = self._read_string()
+                line = self._read_int32()
+                column = self._read_int32()
+                message = self._read_string()
+                raise exceptions.KernelPanic(
+                    "{}:{}:{}: {}".format(file, line, column, message),
+                    file, line, column)
             else:
                 self._read_expect(Reply.KernelFinished)
                 self._process_async_error()
@@ -45,14 +45,201 @@ class CompileError(Exception):
 def rtio_init() -> TNone:
     raise NotImplementedError("syscall not simulated")
 
+@syscall
+def rtio_reset(slack_mu: TInt64) -> TNone:
+    raise NotImplementedError("syscall not simulated")
+
 @syscall(flags={"nounwind", "nowrite"})
 def rtio_get_destination_status(linkno: TInt32) -> TBool:
     raise NotImplementedError("syscall not simulated")
 
+@syscall(flags={"nounwind", "nowrite"})
+def drtio_link_up(destination: TInt32) -> TBool:
+    raise NotImplementedError("syscall not simulated")
+
+@syscall(flags={"nounwind", "nowrite"})
+def kernel_self_destination() -> TInt32:
+    raise NotImplementedError("syscall not simulated")
+
 @syscall(flags={"nounwind", "nowrite"})
 def rtio_get_counter() -> TInt64:
     raise NotImplementedError("syscall not simulated")
 
+@syscall(flags={"nowrite"})
+def rtio_wait_until(target_mu: TInt64) -> TInt64:
+    raise NotImplementedError("syscall not simulated")
+
+@syscall(flags={"nounwind", "nowrite"})
+def rtio_save_cursor() -> TInt64:
+    raise NotImplementedError("syscall not simulated")
+
+@syscall(flags={"nounwind"})
+def rtio_restore_cursor(t: TInt64) -> TNone:
+    raise NotImplementedError("syscall not simulated")
+
+@syscall(flags={"nounwind"})
+def rtio_max_cursor(t: TInt64) -> TInt64:
+    raise NotImplementedError("syscall not simulated")
+
+@syscall
+def rtio_now_push() -> TNone:
+    raise NotImplementedError("syscall not simulated")
+
+@syscall
+def rtio_now_pop() -> TNone:
+    raise NotImplementedError("syscall not simulated")
+
+@syscall(flags={"nounwind", "nowrite"})
+def rtio_resolution() -> TTuple([TInt64, TInt64]):
+    raise NotImplementedError("syscall not simulated")
+
+@syscall(flags={"nounwind", "nowrite"})
+def rtio_mu_to_coarse(mu: TInt64) -> TInt64:
+    raise NotImplementedError("syscall not simulated")
+
+@syscall(flags={"nounwind", "nowrite"})
+def rtio_coarse_to_mu(coarse: TInt64) -> TInt64:
+    raise NotImplementedError("syscall not simulated")
+
+@syscall(flags={"nounwind", "nowrite"})
+def rtio_seconds_to_mu(seconds: TFloat) -> TInt64:
+    raise NotImplementedError("syscall not simulated")
+
+@syscall(flags={"nounwind", "nowrite"})
+def rtio_mu_to_seconds(mu: TInt64) -> TFloat:
+    raise NotImplementedError("syscall not simulated")
+
+@syscall(flags={"nounwind", "nowrite"})
+def rpc_keepalive(interval_mu: TInt64) -> TNone:
+    raise NotImplementedError("syscall not simulated")
+
+@syscall(flags={"nounwind"})
+def rpc_drain() -> TNone:
+    raise NotImplementedError("syscall not simulated")
+
+@syscall(flags={"nowrite"})
+def set_rpc_format(format: TInt32) -> TNone:
+    raise NotImplementedError("syscall not simulated")
+
+@syscall(flags={"nounwind", "nowrite"})
+def set_max_stack_depth(depth: TInt32) -> TNone:
+    raise NotImplementedError("syscall not simulated")
+
+@syscall(flags={"nounwind"})
+def pmp_dump(buffer: TByteArray) -> TInt32:
+    raise NotImplementedError("syscall not simulated")
+
+@syscall(flags={"nowrite"})
+def stack_check() -> TNone:
+    raise NotImplementedError("syscall not simulated")
+
+@syscall(flags={"nounwind", "nowrite"})
+def mailbox_idle_spins(spins: TInt32) -> TNone:
+    raise NotImplementedError("syscall not simulated")
+
+@syscall(flags={"nounwind"})
+def mailbox_selftest() -> TBool:
+    raise NotImplementedError("syscall not simulated")
+
+@syscall(flags={"nounwind", "nowrite"})
+def watchdog_set(timeout_mu: TInt64) -> TNone:
+    raise NotImplementedError("syscall not simulated")
+
+@syscall(flags={"nounwind", "nowrite"})
+def watchdog_pet() -> TNone:
+    raise NotImplementedError("syscall not simulated")
+
+@syscall(flags={"nounwind", "nowrite"})
+def perf_cycles() -> TInt64:
+    raise NotImplementedError("syscall not simulated")
+
+@syscall(flags={"nounwind", "nowrite"})
+def kernel_delay_cycles(n: TInt64) -> TNone:
+    raise NotImplementedError("syscall not simulated")
+
+@syscall(flags={"nowrite"})
+def perf_section_begin(name: TStr) -> TNone:
+    raise NotImplementedError("syscall not simulated")
+
+@syscall(flags={"nowrite"})
+def perf_section_end() -> TNone:
+    raise NotImplementedError("syscall not simulated")
+
+@syscall(flags={"nounwind", "nowrite"})
+def mailbox_latency_stats() -> TTuple([TInt64, TInt64, TInt64, TInt64]):
+    raise NotImplementedError("syscall not simulated")
+
+@syscall(flags={"nounwind", "nowrite"})
+def core_log_rate_limit(burst: TInt32, refill_mu: TInt64) -> TNone:
+    raise NotImplementedError("syscall not simulated")
+
+@syscall(flags={"nounwind", "nowrite"})
+def core_log_binary(data: TBytes) -> TNone:
+    raise NotImplementedError("syscall not simulated")
+
+@syscall(flags={"nounwind", "nowrite"})
+def heap_largest_free_block() -> TInt32:
+    raise NotImplementedError("syscall not simulated")
+
+@syscall(flags={"nounwind"})
+def set_partial_result(data: TByteArray) -> TNone:
+    raise NotImplementedError("syscall not simulated")
+
+@syscall(flags={"nounwind"})
+def set_backtrace_keep_outermost(enable: TBool) -> TNone:
+    raise NotImplementedError("syscall not simulated")
+
+@syscall(flags={"nounwind", "nowrite"})
+def kernel_image_sizes() -> TTuple([TInt32, TInt32, TInt32]):
+    raise NotImplementedError("syscall not simulated")
+
+@syscall(flags={"nounwind", "nowrite"})
+def core_log_flush() -> TNone:
+    raise NotImplementedError("syscall not simulated")
+
+@syscall(flags={"nounwind"})
+def core_log_tail(buffer: TByteArray) -> TInt32:
+    raise NotImplementedError("syscall not simulated")
+
+@syscall(flags={"nowrite"})
+def set_log_level(level: TInt32) -> TNone:
+    raise NotImplementedError("syscall not simulated")
+
+@syscall
+def subkernel_send_message_stream(msg_id: TInt32, destination: TInt32, data: TBytes,
+                                  chunk_size: TInt32) -> TNone:
+    """Send ``data`` to the subkernel or kernel on ``destination`` as a
+    stream of messages of at most ``chunk_size`` bytes each, with the message
+    ID ``msg_id``. Use :func:`subkernel_await_message_stream` to receive it.
+
+    Unlike ``subkernel_send``, the payload is not limited to what the
+    receiver can hold in a single message.
+    """
+    raise NotImplementedError("syscall not simulated")
+
+@syscall
+def subkernel_await_message_stream(msg_id: TInt32, timeout_ms: TInt64,
+                                   buffer: TByteArray) -> TInt32:
+    """Receive a stream sent with :func:`subkernel_send_message_stream`
+    into ``buffer``, waiting at most ``timeout_ms`` for each message, and
+    return its length.
+
+    :raises SubkernelError: if a message is lost or out of order, or if the
+        stream does not fit in ``buffer``. The remaining messages of the
+        stream are then left queued.
+    """
+    raise NotImplementedError("syscall not simulated")
+
+@syscall(flags={"nounwind", "nowrite"})
+def subkernel_is_available(subkernel_id: TInt32, destination: TInt32) -> TBool:
+    """Return whether the subkernel with ID ``subkernel_id`` has been
+    uploaded to ``destination`` and can be run there, without loading it.
+
+    Unknown subkernels and unreachable destinations give ``False``. On a
+    satellite, only the subkernels uploaded to that satellite are known.
+    """
+    raise NotImplementedError("syscall not simulated")
+
 @syscall
 def test_exception_id_sync(id: TInt32) -> TNone:
     raise NotImplementedError("syscall not simulated")
@@ -127,6 +314,18 @@ class Core:
         """
         self.comm.close()
 
+    def subscribe_result(self, channel, callback):
+        """Call ``callback`` with each value that a kernel publishes on
+        ``channel`` with ``publish_result(channel, value)``, as soon as
+        it arrives, while the kernel keeps running. A value is delivered after
+        the async RPCs that the kernel issued before publishing it.
+
+        Exceptions raised by ``callback`` are logged, as the kernel does not
+        wait for it. Values published on a channel without a subscriber are
+        dropped with a warning.
+        """
+        self.comm.result_subscribers[channel] = callback
+
     def compile(self, function, args, kwargs, set_result=None,
                 attribute_writeback=True, print_as_rpc=True,
                 target=None, destination=0, subkernel_arg_types=[],
@@ -310,6 +509,50 @@ class Core:
         """
         return rtio_get_counter()
 
+    @kernel
+    def save_cursor_mu(self):
+        """Return the position of the timeline cursor, to be reinstated
+        later with :meth:`restore_cursor_mu`, e.g. by a helper that schedules
+        events relative to a base time of its own.
+        """
+        return rtio_save_cursor()
+
+    @kernel
+    def restore_cursor_mu(self, cursor_mu):
+        """Move the timeline cursor to a position returned by
+        :meth:`save_cursor_mu`.
+        """
+        rtio_restore_cursor(cursor_mu)
+
+    @kernel
+    def max_cursor_mu(self, cursor_mu):
+        """Move the timeline cursor to ``cursor_mu`` if it is later than
+        the current position, and return the new position. This joins a
+        sequence that ran from a position saved with :meth:`save_cursor_mu`
+        with the current timeline, like the end of a ``parallel`` block.
+        """
+        return rtio_max_cursor(cursor_mu)
+
+    @kernel
+    def push_cursor(self):
+        """Save the position of the timeline cursor on a stack kept by the
+        core device, to be reinstated by the matching :meth:`pop_cursor`.
+        Nested sequences can use this instead of keeping the values of
+        :meth:`save_cursor_mu` themselves.
+
+        :raises RTIOError: if 16 positions are already saved.
+        """
+        rtio_now_push()
+
+    @kernel
+    def pop_cursor(self):
+        """Move the timeline cursor to the position saved by the last
+        :meth:`push_cursor`, and remove it from the stack.
+
+        :raises RTIOError: if no position is saved.
+        """
+        rtio_now_pop()
+
     @kernel
     def wait_until_mu(self, cursor_mu):
         """Block execution until the hardware RTIO counter reaches the given
@@ -321,6 +564,337 @@ class Core:
         while self.get_rtio_counter_mu() < cursor_mu:
             pass
 
+    @kernel
+    def wait_until_checked_mu(self, cursor_mu):
+        """Block execution until the hardware RTIO counter reaches the given
+        value, like :meth:`wait_until_mu`, and return the value of the counter
+        at release.
+
+        The difference between the returned value and the timestamps of the
+        events submitted next is the slack left for them.
+
+        :raises RTIOUnderflow: if the hardware counter has already passed the
+            given time.
+        """
+        return rtio_wait_until(cursor_mu)
+
+    @kernel
+    def get_rtio_resolution(self):
+        """Return the RTIO timestamp resolution of the core device gateware,
+        as a tuple ``(coarse_period_ps, fine_bits)``: the period of the RTIO
+        coarse clock in picoseconds, and the number of fine timestamp bits
+        within it. The machine unit is ``coarse_period_ps/2**fine_bits``
+        picoseconds.
+
+        Unlike :attr:`ref_period`, which comes from the device database, this
+        is read from the gateware.
+        """
+        return rtio_resolution()
+
+    @kernel
+    def mu_to_coarse_cycles(self, mu):
+        """Convert machine units to cycles of the RTIO coarse clock, rounding
+        down, using the timestamp resolution of the gateware."""
+        return rtio_mu_to_coarse(mu)
+
+    @kernel
+    def coarse_cycles_to_mu(self, coarse):
+        """Convert cycles of the RTIO coarse clock to machine units, using
+        the timestamp resolution of the gateware."""
+        return rtio_coarse_to_mu(coarse)
+
+    @kernel
+    def gateware_seconds_to_mu(self, seconds):
+        """Like :meth:`seconds_to_mu`, but using the timestamp resolution of
+        the gateware (see :meth:`get_rtio_resolution`) instead of
+        :attr:`ref_period`."""
+        return rtio_seconds_to_mu(seconds)
+
+    @kernel
+    def gateware_mu_to_seconds(self, mu):
+        """Like :meth:`mu_to_seconds`, but using the timestamp resolution of
+        the gateware instead of :attr:`ref_period`."""
+        return rtio_mu_to_seconds(mu)
+
+    @kernel
+    def set_rpc_keepalive_mu(self, interval_mu):
+        """Check that the connection to the host is still alive, at most
+        every ``interval_mu`` (on the RTIO counter), whenever the kernel
+        issues an RPC or calls :meth:`wait_until_checked_mu`.
+
+        The check is handled by the core device CPU and does not send
+        anything to the host, so it does not affect the order of RPCs.
+        A non-positive interval disables the check.
+
+        :raises RPCError: at a later check, if the host connection was lost
+            or the core device CPU did not respond within four intervals.
+        """
+        rpc_keepalive(interval_mu)
+
+    @kernel
+    def flush_async_rpcs(self):
+        """Wait until the core device CPU has sent all the async RPCs issued
+        so far to the host, e.g. before a long stretch of real-time code
+        that must not be delayed by them. They are also flushed when the
+        kernel finishes.
+        """
+        rpc_drain()
+
+    @kernel
+    def set_rpc_format(self, format):
+        """Select how the arguments of the following RPCs are sent to the
+        host: 0 for the compact format, the default, or 1 for a debug format
+        that also sends the size of each argument.
+
+        With the debug format, an argument that the host cannot decode raises
+        an ``IOError`` naming the argument and its tag, instead of corrupting
+        the arguments that follow. Async RPCs are sent in the order they were
+        issued, whichever format each uses.
+
+        :raises ValueError: for other formats.
+        """
+        set_rpc_format(format)
+
+    @kernel
+    def set_core_log_rate_limit_mu(self, burst, refill_mu):
+        """Limit the rate of the messages the kernel sends to the core log
+        with ``core_log``, e.g. to keep a log call in a tight loop from
+        stalling the kernel.
+
+        At most ``burst`` messages are logged at once, and one more is
+        allowed every ``refill_mu`` (on the RTIO counter). Messages beyond
+        that are dropped, and their number is logged after the next message
+        that goes through. A non-positive ``burst`` or ``refill_mu`` removes
+        the limit, which is the default.
+        """
+        core_log_rate_limit(burst, refill_mu)
+
+    @kernel
+    def set_max_stack_depth(self, depth):
+        """Set a soft limit of ``depth`` bytes on the kernel stack, checked
+        by :meth:`check_stack`. A non-positive ``depth`` removes the limit,
+        which is the default.
+        """
+        set_max_stack_depth(depth)
+
+    @kernel
+    def check_stack(self):
+        """Raise ``RecursionError`` if the kernel stack is deeper than the
+        limit set with :meth:`set_max_stack_depth`.
+
+        Call this at the recursion points of data-dependent recursion, so
+        that a runaway recursion can be caught instead of overflowing the
+        stack, which aborts the kernel.
+        """
+        stack_check()
+
+    @kernel
+    def get_pmp_entries(self, buffer):
+        """Write the active PMP (physical memory protection) entries of the
+        kernel CPU into the bytearray ``buffer``, e.g. to check which regions,
+        such as the stack guard, are protected, and return the number of
+        bytes written.
+
+        Each entry takes 10 bytes: its index, its configuration byte (the
+        RISC-V ``pmpcfg`` permission and mode bits), and its base address
+        and size as big-endian 32-bit integers. A size of 0 means that the
+        region extends to the end of the address space. Entries that do not
+        fit in ``buffer`` are left out.
+        """
+        return pmp_dump(buffer)
+
+    @kernel
+    def set_idle_wait_spins(self, spins):
+        """Let the kernel CPU idle, to save power, in the waits that are
+        expected to be long: awaiting a subkernel or a message from one.
+
+        Such a wait polls for the reply ``spins`` times, then stops the CPU
+        until the comms CPU writes to the mailbox, at the cost of some wake-up
+        latency. Other waits, such as RPCs, keep polling. A non-positive
+        ``spins`` disables idling, which is the default. Waits do not idle
+        while the watchdog is armed, or on gateware without the mailbox
+        interrupt.
+        """
+        mailbox_idle_spins(spins)
+
+    @kernel
+    def check_mailbox(self):
+        """Exchange a test message with the comms CPU and return whether it
+        was echoed back within a bounded number of polls, e.g. to check
+        that the link between the kernel and comms CPUs is healthy during
+        hardware validation. Returns ``False`` instead of hanging if the
+        comms CPU does not answer.
+        """
+        return mailbox_selftest()
+
+    @kernel
+    def set_watchdog_mu(self, timeout_mu):
+        """Arm the kernel watchdog to expire ``timeout_mu`` (on the RTIO
+        counter) from now. A non-positive ``timeout_mu`` disarms it, which
+        is the default when a kernel is loaded.
+
+        The watchdog is checked while the kernel CPU waits on the comms CPU,
+        e.g. for an RPC or a subkernel, or on the DMA engine, so that a wait
+        that never ends raises an exception instead of hanging the kernel.
+        Call :meth:`pet_watchdog` to push the deadline back while the kernel
+        is making progress.
+
+        :raises WatchdogTimeout: in a wait that goes past the deadline. The
+            watchdog is disarmed when it expires.
+        """
+        watchdog_set(timeout_mu)
+
+    @kernel
+    def pet_watchdog(self):
+        """Push the deadline of the kernel watchdog back to the timeout
+        given to :meth:`set_watchdog_mu` from now. Does nothing if the
+        watchdog is not armed.
+        """
+        watchdog_pet()
+
+    @kernel
+    def get_cpu_cycles(self):
+        """Return the number of cycles of the kernel CPU since it was
+        reset, e.g. to time short stretches of kernel code. Unlike the RTIO
+        counter, this counts CPU clock cycles, not machine units.
+        """
+        return perf_cycles()
+
+    @kernel
+    def delay_cpu_cycles(self, n):
+        """Busy-wait for ``n`` cycles of the kernel CPU, e.g. for short
+        settling times when bit-banging.
+
+        Unlike :func:`~artiq.language.core.delay_mu`, this blocks the CPU for
+        the whole delay and does not advance the timeline cursor, so the
+        timestamps of RTIO events are unaffected.
+        """
+        kernel_delay_cycles(n)
+
+    @kernel
+    def begin_perf_section(self, name):
+        """Start attributing the CPU cycles taken by the kernel to the
+        section ``name``, until the matching :meth:`end_perf_section`.
+        Sections can be nested; the cycles of an inner section also count
+        towards the sections around it.
+
+        The cycles and number of runs of each section are logged to the
+        core log when the kernel finishes.
+
+        :raises ValueError: if ``name`` is longer than 32 bytes.
+        :raises RuntimeError: if more than 32 sections are named, or
+            sections are nested more than 16 deep.
+        """
+        perf_section_begin(name)
+
+    @kernel
+    def end_perf_section(self):
+        """End the innermost section started with :meth:`begin_perf_section`.
+
+        :raises RuntimeError: if no section is open.
+        """
+        perf_section_end()
+
+    @kernel
+    def get_mailbox_latency_stats(self):
+        """Return how long the kernel CPU waited on the mailbox since the
+        kernel started, as a tuple ``(count, min, max, sum)`` of the number
+        of waits and of their minimum, maximum and total number of polling
+        iterations. Each message sent to the comms CPU, and each reply, is
+        one wait; long waits point at a busy comms CPU.
+
+        The waits are only sampled when the firmware is built with the
+        ``mailbox_latency`` feature; otherwise all the values are 0.
+        """
+        return mailbox_latency_stats()
+
+    @kernel
+    def get_heap_largest_free_block(self):
+        """Return the size in bytes of the largest block that can be
+        allocated on the kernel heap, e.g. to check that a large RPC return
+        value or DMA trace will fit before attempting it. Fragmentation can
+        make this much less than the free memory.
+        """
+        return heap_largest_free_block()
+
+    @kernel
+    def set_partial_result(self, data):
+        """Register ``data`` to be sent to the host if the kernel terminates
+        with an uncaught exception, so that the results computed before a
+        late failure are not lost. Its contents at the time of the failure
+        are attached to the exception raised on the host as
+        ``artiq_partial_result``, if the host connection is still up.
+
+        ``data`` is read when the kernel terminates, so it must remain
+        allocated until then, e.g. by being created in the kernel entry point.
+        """
+        set_partial_result(data)
+
+    @kernel
+    def set_backtrace_keep_outermost(self, enable):
+        """Choose which frames are kept when the backtrace of an exception
+        is too deep to be recorded in full. By default the innermost frames
+        are kept; if ``enable`` is true, the frame the exception was raised
+        in and the outermost frames are kept instead, which show where in
+        the kernel the failing call was made. Reset when a kernel is loaded.
+        """
+        set_backtrace_keep_outermost(enable)
+
+    @kernel
+    def get_kernel_image_sizes(self):
+        """Return the sizes in bytes of the running kernel, as a tuple
+        ``(code_bytes, bss_bytes, total_payload_bytes)``: its code and
+        initialized data, its zero-initialized data, and the kernel memory
+        available from its start, which also holds the stack.
+        """
+        return kernel_image_sizes()
+
+    @kernel
+    def log_binary(self, data):
+        """Send a binary blob, e.g. a packed register snapshot, to the core
+        log without decoding it as text.
+
+        The blob is logged at the INFO level with the ``kernel::binary``
+        target, hex-encoded; ``bytes.fromhex`` recovers it verbatim.
+        """
+        core_log_binary(data)
+
+    @kernel
+    def flush_core_log(self):
+        """Send the text buffered by ``core_log`` to the core log.
+
+        Core log messages are buffered on the core device and sent when
+        they contain a newline, when the buffer is full, and when the kernel
+        terminates. Use this to send a partial line right away.
+        """
+        core_log_flush()
+
+    @kernel
+    def get_core_log_tail(self, buffer):
+        """Copy the most recent text the kernel sent to the core log into
+        the bytearray ``buffer``, as much of it as fits, and return the
+        number of bytes copied, e.g. to attach the context of a failure to
+        an RPC that reports it.
+
+        The text is copied in the order it was written, before any filtering
+        by the comms CPU. Only the last 1024 bytes are kept, and a character
+        cut at the start of the copied window is left out.
+        """
+        return core_log_tail(buffer)
+
+    @kernel
+    def set_core_log_level(self, level):
+        """Set the most verbose level of the core log messages sent by the
+        kernel: 0 (none), 1 (error), 2 (warning), 3 (info), 4 (debug) or
+        5 (trace). ``core_log`` logs at the info level.
+
+        Messages above that level are dropped on the core device, before
+        they reach the comms CPU, so logging can be left in place and
+        silenced without recompiling. Each kernel starts with the core log
+        level of the comms CPU, which also still filters the messages.
+        """
+        set_log_level(level)
+
     @kernel
     def get_rtio_destination_status(self, destination):
         """Returns whether the specified RTIO destination is up.
@@ -328,6 +902,25 @@ class Core:
         startup until certain DRTIO destinations are available."""
         return rtio_get_destination_status(destination)
 
+    @kernel
+    def is_drtio_link_up(self, destination):
+        """Returns whether the specified DRTIO destination can be reached,
+        e.g. to skip calling a subkernel on a satellite that is offline
+        instead of waiting for it to time out.
+
+        Invalid destinations, and destinations without a route, are reported
+        as unreachable. From a subkernel, other destinations with a route are
+        assumed to be reachable, as satellites do not track their state."""
+        return drtio_link_up(destination)
+
+    @kernel
+    def get_self_destination(self):
+        """Returns the DRTIO destination the kernel is running on, i.e. 0 on
+        the master and the destination of the satellite in a subkernel. This
+        lets a subkernel that is deployed to several satellites adapt to
+        where it runs."""
+        return kernel_self_destination()
+
     @kernel
     def reset(self):
         """Clear RTIO FIFOs, release RTIO PHY reset, and set the time cursor
@@ -336,6 +929,15 @@ class Core:
         rtio_init()
         at_mu(rtio_get_counter() + 125000)
 
+    @kernel
+    def reset_mu(self, slack_mu):
+        """Same as :meth:`reset`, with a margin of ``slack_mu`` machine
+        units, e.g. to start a new phase of an experiment from a known state.
+
+        Raises :exc:`DMAError` if a DMA trace is being recorded.
+        """
+        rtio_reset(slack_mu)
+
     @kernel
     def break_realtime(self):
         """Set the time cursor after the current value of the hardware RTIO
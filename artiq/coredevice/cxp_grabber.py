@@ -1,7 +1,7 @@
 from numpy import array, int32, int64, ndarray
 
 from artiq.language.core import syscall, kernel
-from artiq.language.types import TInt32, TNone, TList
+from artiq.language.types import TInt32, TInt64, TBool, TNone, TList, TByteArray
 from artiq.coredevice.rtio import rtio_output, rtio_input_timestamped_data
 from artiq.experiment import *
 
@@ -19,21 +19,97 @@ class CXPGrabberTimeoutException(Exception):
     pass
 
 
+# The syscalls without a camera index address camera 0 of the grabber.
 @syscall(flags={"nounwind"})
 def cxp_download_xml_file(dest: TInt32, buffer: TList(TInt32)) -> TInt32:
     raise NotImplementedError("syscall not simulated")
 
 
+@syscall(flags={"nounwind"})
+def cxp_download_xml_file_cam(dest: TInt32, cam: TInt32, buffer: TList(TInt32)) -> TInt32:
+    raise NotImplementedError("syscall not simulated")
+
+
 @syscall(flags={"nounwind"})
 def cxp_read32(dest: TInt32, addr: TInt32) -> TInt32:
     raise NotImplementedError("syscall not simulated")
 
 
+@syscall(flags={"nounwind"})
+def cxp_read32_cam(dest: TInt32, cam: TInt32, addr: TInt32) -> TInt32:
+    raise NotImplementedError("syscall not simulated")
+
+
 @syscall(flags={"nounwind"})
 def cxp_write32(dest: TInt32, addr: TInt32, val: TInt32) -> TNone:
     raise NotImplementedError("syscall not simulated")
 
 
+@syscall(flags={"nounwind"})
+def cxp_write32_cam(dest: TInt32, cam: TInt32, addr: TInt32, val: TInt32) -> TNone:
+    raise NotImplementedError("syscall not simulated")
+
+
+@syscall
+def cxp_trigger_at(
+    channel: TInt32, timestamp: TInt64, linktrigger: TInt32, extra_linktrigger: TBool
+) -> TNone:
+    raise NotImplementedError("syscall not simulated")
+
+
+@syscall
+def cxp_trigger_at_cam(
+    channel: TInt32, cam: TInt32, timestamp: TInt64, linktrigger: TInt32,
+    extra_linktrigger: TBool
+) -> TNone:
+    raise NotImplementedError("syscall not simulated")
+
+
+@syscall
+def cxp_num_cameras(dest: TInt32) -> TInt32:
+    raise NotImplementedError("syscall not simulated")
+
+
+@syscall
+def cxp_link_status(dest: TInt32) -> TTuple([TBool, TInt32, TInt32]):
+    raise NotImplementedError("syscall not simulated")
+
+
+@syscall
+def cxp_link_status_cam(dest: TInt32, cam: TInt32) -> TTuple([TBool, TInt32, TInt32]):
+    raise NotImplementedError("syscall not simulated")
+
+
+@syscall
+def cxp_link_status_word(dest: TInt32, cam: TInt32) -> TInt32:
+    raise NotImplementedError("syscall not simulated")
+
+
+@syscall
+def cxp_set_roi(
+    dest: TInt32,
+    registers: TList(TInt32),
+    x: TInt32,
+    y: TInt32,
+    width: TInt32,
+    height: TInt32,
+) -> TNone:
+    raise NotImplementedError("syscall not simulated")
+
+
+@syscall
+def cxp_set_roi_cam(
+    dest: TInt32,
+    cam: TInt32,
+    registers: TList(TInt32),
+    x: TInt32,
+    y: TInt32,
+    width: TInt32,
+    height: TInt32,
+) -> TNone:
+    raise NotImplementedError("syscall not simulated")
+
+
 @syscall(flags={"nounwind"})
 def cxp_start_roi_viewer(
     dest: TInt32, x0: TInt32, y0: TInt32, x1: TInt32, y1: TInt32
@@ -41,6 +117,13 @@ def cxp_start_roi_viewer(
     raise NotImplementedError("syscall not simulated")
 
 
+@syscall(flags={"nounwind"})
+def cxp_start_roi_viewer_cam(
+    dest: TInt32, cam: TInt32, x0: TInt32, y0: TInt32, x1: TInt32, y1: TInt32
+) -> TNone:
+    raise NotImplementedError("syscall not simulated")
+
+
 @syscall(flags={"nounwind"})
 def cxp_download_roi_viewer_frame(
     dest: TInt32,
@@ -49,6 +132,70 @@ def cxp_download_roi_viewer_frame(
     raise NotImplementedError("syscall not simulated")
 
 
+@syscall(flags={"nounwind"})
+def cxp_download_roi_viewer_frame_cam(
+    dest: TInt32,
+    cam: TInt32,
+    buffer: TList(TInt64),
+) -> TTuple([TInt32, TInt32, TInt32]):
+    raise NotImplementedError("syscall not simulated")
+
+
+@syscall(flags={"nounwind"})
+def cxp_stream_frame(dest: TInt32, buffer: TByteArray) -> TInt32:
+    raise NotImplementedError("syscall not simulated")
+
+
+@syscall(flags={"nounwind"})
+def cxp_stream_frame_cam(dest: TInt32, cam: TInt32, buffer: TByteArray) -> TInt32:
+    raise NotImplementedError("syscall not simulated")
+
+
+@syscall
+def cxp_grab_frame(
+    dest: TInt32, cam: TInt32, buffer: TByteArray, timeout_mu: TInt64
+) -> TTuple([TInt32, TBool]):
+    raise NotImplementedError("syscall not simulated")
+
+
+@syscall
+def cxp_convert_frame(
+    src: TByteArray, src_fmt: TInt32, dst: TByteArray, dst_fmt: TInt32
+) -> TInt32:
+    """
+    Unpack monochrome pixels, packed as sent by the camera, on the core device.
+
+    :param src: packed pixels
+    :param src_fmt: pixel format code of ``src`` (Mono8 to Mono16, 0x0101 to 0x0105)
+    :param dst: bytearray receiving the pixels
+    :param dst_fmt: 0x0105 (Mono16) to store each pixel as a little-endian 16-bit
+        value, or 0x0101 (Mono8) to keep its 8 most significant bits
+    :returns: the number of pixels converted
+    """
+    raise NotImplementedError("syscall not simulated")
+
+
+# Fields of the word returned by :meth:`CXPGrabber.link_status_word`
+LINK_STATUS_UP = 0x00000001
+LINK_STATUS_LANES = 0x0000ff00
+LINK_STATUS_BITRATE_CODE = 0x00ff0000
+LINK_STATUS_ERRORS = 0xff000000
+
+
+def decode_link_status(word):
+    """
+    Decode the word returned by :meth:`CXPGrabber.link_status_word`.
+
+    :returns: a tuple of whether the link is up, the number of connections
+        in use and the bitrate code, as returned by :meth:`CXPGrabber.link_status`
+    """
+    return (
+        bool(word & LINK_STATUS_UP),
+        (word & LINK_STATUS_LANES) >> 8,
+        (word & LINK_STATUS_BITRATE_CODE) >> 16,
+    )
+
+
 def write_file(data, file_path):
     """
     Write big-endian encoded data to PC
@@ -123,7 +270,13 @@ def write_pgm(frame, file_path, pixel_width):
 
 
 class CXPGrabber:
-    """Driver for the CoaXPress Grabber camera interface."""
+    """Driver for the CoaXPress Grabber camera interface.
+
+    :param camera: index of the camera on the grabber, see :meth:`num_cameras`.
+    :param roi_registers: addresses of the OffsetX, OffsetY, Width, Height,
+        WidthMax and HeightMax registers of the camera, in that order, as listed
+        in its XML file. Required by :meth:`set_camera_roi`.
+    """
 
     kernel_invariants = {
         "core",
@@ -132,20 +285,31 @@ class CXPGrabber:
         "roi_config_ch",
         "roi_gating_ch",
         "sentinel",
+        "camera",
+        "roi_registers",
     }
 
-    def __init__(self, dmgr, channel, core_device="core", count_width=31):
+    def __init__(self, dmgr, channel, core_device="core", count_width=31,
+                 camera=0, roi_registers=None):
         self.core = dmgr.get(core_device)
 
         self.channel = channel
         self.trigger_ch = channel
         self.roi_config_ch = channel + 1
         self.roi_gating_ch = channel + 2
+        self.camera = camera
 
         # This value is inserted by the gateware to mark the start of a series of
         # ROI engine outputs for one video frame.
         self.sentinel = int32(int64(2**count_width))
 
+        # negative addresses are rejected by the firmware as not configured
+        if roi_registers is None:
+            roi_registers = [-1] * 6
+        if len(roi_registers) != 6:
+            raise ValueError("roi_registers must list 6 register addresses")
+        self.roi_registers = [int32(addr) for addr in roi_registers]
+
     @staticmethod
     def get_rtio_channels(channel, **kwargs):
         return [
@@ -170,6 +334,22 @@ class CXPGrabber:
         extra_linktrigger_mask = 1 if extra_linktrigger else 0
         rtio_output(self.trigger_ch << 8, linktrigger << 1 | extra_linktrigger_mask)
 
+    @kernel
+    def trigger_at_mu(self, timestamp_mu, linktrigger, extra_linktrigger=False):
+        """
+        Send CoaXPress fixed-latency linktrigger to camera at the given timestamp,
+        without advancing the timeline.
+
+        Raises :class:`~artiq.coredevice.exceptions.CXPError` if the linktrigger
+        type is not available with the given ``extra_linktrigger`` setting.
+
+        :param timestamp_mu: RTIO timestamp of the trigger, in machine units
+        :param linktrigger: Set linktrigger type, as in :meth:`send_cxp_linktrigger`
+        :param extra_linktrigger: Boolean, set to True when ExtraLsTriggerEnable is set to 1 on camera
+        """
+        cxp_trigger_at_cam(self.trigger_ch, self.camera, timestamp_mu, linktrigger,
+                           extra_linktrigger)
+
     @kernel
     def setup_roi(self, n, x0, y0, x1, y1):
         """
@@ -274,10 +454,13 @@ class CXPGrabber:
 
         .. warning:: This is NOT a real-time operation.
 
+        Raises :class:`~artiq.coredevice.exceptions.CXPError` if the address is
+        not aligned to 4 bytes, or if the camera does not acknowledge the read.
+
         :param address: 32-bit register address to read from
         :returns: 32-bit value from register
         """
-        return cxp_read32(self.channel >> 16, address)
+        return cxp_read32_cam(self.channel >> 16, self.camera, address)
 
     @kernel
     def write32(self, address: TInt32, value: TInt32):
@@ -286,10 +469,66 @@ class CXPGrabber:
 
         .. warning:: This is NOT a real-time operation.
 
+        Raises :class:`~artiq.coredevice.exceptions.CXPError` if the address is
+        not aligned to 4 bytes, or if the camera does not acknowledge the write.
+
         :param address: 32-bit register address to write to
         :param value: 32-bit value to be written
         """
-        cxp_write32(self.channel >> 16, address, value)
+        cxp_write32_cam(self.channel >> 16, self.camera, address, value)
+
+    @kernel
+    def set_camera_roi(self, x, y, width, height):
+        """
+        Program the region of interest of the camera sensor, to reduce the frame size.
+
+        Raises :class:`~artiq.coredevice.exceptions.CXPError` if the ROI does not
+        fit in the sensor or if ``roi_registers`` was not given.
+
+        .. warning:: This is NOT a real-time operation.
+
+        :param x: horizontal offset of the ROI, in pixels
+        :param y: vertical offset of the ROI, in pixels
+        :param width: width of the ROI, in pixels
+        :param height: height of the ROI, in pixels
+        """
+        cxp_set_roi_cam(self.channel >> 16, self.camera, self.roi_registers, x, y, width, height)
+
+    @kernel
+    def num_cameras(self):
+        """
+        Return the number of cameras attached to the grabber.
+
+        .. warning:: This is NOT a real-time operation.
+        """
+        return cxp_num_cameras(self.channel >> 16)
+
+    @kernel
+    def link_status(self):
+        """
+        Read the state of the CoaXPress link to the camera.
+
+        .. warning:: This is NOT a real-time operation.
+
+        :returns: a tuple of whether the link is up, the number of connections
+            in use and the bitrate code of the camera ConnectionConfig register
+        """
+        return cxp_link_status_cam(self.channel >> 16, self.camera)
+
+    @kernel
+    def link_status_word(self):
+        """
+        Read the state of the CoaXPress link to the camera, like :meth:`link_status`,
+        packed into a single integer that can be stored in a dataset and decoded
+        with :func:`decode_link_status`.
+
+        Bit 0 is set if the link is up, bits 8-15 hold the number of connections
+        and bits 16-23 the bitrate code. Bits 24-31 are reserved for error flags
+        and read as 0; link errors raise :class:`~artiq.coredevice.exceptions.CXPError`.
+
+        .. warning:: This is NOT a real-time operation.
+        """
+        return cxp_link_status_word(self.channel >> 16, self.camera)
 
     @kernel
     def read_local_xml(self, buffer):
@@ -303,7 +542,7 @@ class CXPGrabber:
         :param buffer: list to be filled
         :returns: number of 32-bit words read
         """
-        return cxp_download_xml_file(self.channel >> 16, buffer)
+        return cxp_download_xml_file_cam(self.channel >> 16, self.camera, buffer)
 
     @kernel
     def start_roi_viewer(self, x0, y0, x1, y1):
@@ -314,7 +553,7 @@ class CXPGrabber:
 
         .. warning:: This is NOT a real-time operation.
         """
-        cxp_start_roi_viewer(self.channel >> 16, x0, y0, x1, y1)
+        cxp_start_roi_viewer_cam(self.channel >> 16, self.camera, x0, y0, x1, y1)
 
     @kernel
     def read_roi_viewer_frame(self, frame):
@@ -329,8 +568,8 @@ class CXPGrabber:
         :returns: the frame bit depth
         """
         buffer = [0] * 1024
-        width, height, pixel_width = cxp_download_roi_viewer_frame(
-            self.channel >> 16, buffer
+        width, height, pixel_width = cxp_download_roi_viewer_frame_cam(
+            self.channel >> 16, self.camera, buffer
         )
         if height != len(frame) or width != len(frame[0]):
             raise ValueError(
@@ -343,3 +582,42 @@ class CXPGrabber:
                 # each buffer element holds 4 pixels
                 frame[y][x] = (buffer[offset + (x // 4)] >> (16 * (x % 4))) & 0xFFFF
         return pixel_width
+
+    @kernel
+    def stream_frame(self, buffer):
+        """
+        Copy the ROI viewer frame into a bytearray, to be processed on the core device.
+
+        Pixels are packed four per 64-bit little-endian word, with each row padded
+        to a multiple of four pixels, as in :meth:`read_roi_viewer_frame`.
+
+        The user must :exc:`start_roi_viewer` and trigger the camera before the frame is available.
+
+        .. warning:: This is NOT a real-time operation.
+
+        :param buffer: bytearray to be filled
+        :returns: the frame size in bytes. If it exceeds the length of ``buffer``,
+            the frame has been truncated.
+        """
+        return cxp_stream_frame_cam(self.channel >> 16, self.camera, buffer)
+
+    @kernel
+    def grab_frame(self, buffer, timeout_mu):
+        """
+        Copy the next ROI viewer frame into a bytearray, like :meth:`stream_frame`,
+        waiting at most ``timeout_mu`` on the RTIO counter.
+
+        If the timeout expires while the frame is being received, the part received
+        so far is returned and marked as incomplete; restart the ROI viewer with
+        :meth:`start_roi_viewer` before grabbing the next frame.
+
+        .. warning:: This is NOT a real-time operation.
+
+        :param buffer: bytearray to be filled
+        :param timeout_mu: how long to wait for the frame, in machine units
+        :returns: a tuple of the frame size in bytes, which exceeds the length
+            of ``buffer`` if the frame has been truncated, and whether the
+            frame is complete
+        :raises CXPTimeout: if no part of a frame was received in time
+        """
+        return cxp_grab_frame(self.channel >> 16, self.camera, buffer, timeout_mu)
@@ -6,7 +6,7 @@ alone could achieve.
 """
 
 from artiq.language.core import syscall, kernel
-from artiq.language.types import TInt32, TInt64, TStr, TNone, TTuple, TBool
+from artiq.language.types import TInt32, TInt64, TStr, TNone, TTuple, TBool, TList
 from artiq.coredevice.exceptions import DMAError
 
 from numpy import int64
@@ -16,14 +16,26 @@ from numpy import int64
 def dma_record_start(name: TStr) -> TNone:
     raise NotImplementedError("syscall not simulated")
 
+@syscall
+def dma_record_start_reserve(name: TStr, expected_bytes: TInt32) -> TNone:
+    raise NotImplementedError("syscall not simulated")
+
 @syscall
 def dma_record_stop(duration: TInt64, enable_ddma: TBool) -> TNone:
     raise NotImplementedError("syscall not simulated")
 
+@syscall
+def dma_record_checkpoint() -> TInt64:
+    raise NotImplementedError("syscall not simulated")
+
 @syscall
 def dma_erase(name: TStr) -> TNone:
     raise NotImplementedError("syscall not simulated")
 
+@syscall
+def dma_dump(name: TStr, raise_if_missing: TBool) -> TBool:
+    raise NotImplementedError("syscall not simulated")
+
 @syscall
 def dma_retrieve(name: TStr) -> TTuple([TInt64, TInt32, TBool]):
     raise NotImplementedError("syscall not simulated")
@@ -32,6 +44,46 @@ def dma_retrieve(name: TStr) -> TTuple([TInt64, TInt32, TBool]):
 def dma_playback(timestamp: TInt64, ptr: TInt32, enable_ddma: TBool) -> TNone:
     raise NotImplementedError("syscall not simulated")
 
+@syscall
+def dma_playback_timed(timestamp: TInt64, ptr: TInt32, enable_ddma: TBool) -> TInt64:
+    raise NotImplementedError("syscall not simulated")
+
+@syscall
+def dma_playback_offset(timestamp: TInt64, ptr: TInt32, enable_ddma: TBool, extra_offset_mu: TInt64) -> TNone:
+    raise NotImplementedError("syscall not simulated")
+
+@syscall(flags={"nounwind", "nowrite"})
+def rtio_error_is_recoverable(error_bits: TInt32) -> TBool:
+    raise NotImplementedError("syscall not simulated")
+
+@syscall(flags={"nounwind", "nowrite"})
+def dma_state() -> TInt32:
+    raise NotImplementedError("syscall not simulated")
+
+@syscall
+def dma_alloc(size: TInt32, align: TInt32) -> TInt32:
+    raise NotImplementedError("syscall not simulated")
+
+@syscall
+def dma_free(ptr: TInt32) -> TNone:
+    raise NotImplementedError("syscall not simulated")
+
+@syscall
+def dma_concat(name: TStr, parts: TList(TStr), offsets: TList(TInt64)) -> TNone:
+    raise NotImplementedError("syscall not simulated")
+
+@syscall
+def dma_concat_pair(name: TStr, a: TStr, b: TStr, gap_mu: TInt64) -> TNone:
+    raise NotImplementedError("syscall not simulated")
+
+
+#: :meth:`CoreDMA.state` when DMA is neither recording nor playing back.
+DMA_STATE_IDLE = 0
+#: :meth:`CoreDMA.state` while a recording is in progress.
+DMA_STATE_RECORDING = 1
+#: :meth:`CoreDMA.state` while a trace is being played back.
+DMA_STATE_PLAYING = 2
+
 
 class DMARecordContextManager:
     """Context manager returned by :meth:`CoreDMA.record()`.
@@ -61,17 +113,35 @@ class DMARecordContextManager:
         at_mu(self.saved_now_mu)
 
 
+class DMAReserveRecordContextManager(DMARecordContextManager):
+    """Context manager returned by :meth:`CoreDMA.record_reserve()`.
+
+    Same as :class:`DMARecordContextManager`, but reserves the recording
+    buffer before starting to record.
+    """
+    def __init__(self):
+        DMARecordContextManager.__init__(self)
+        self.expected_bytes = 0
+
+    @kernel
+    def __enter__(self):
+        dma_record_start_reserve(self.name, self.expected_bytes)
+        self.saved_now_mu = now_mu()
+        at_mu(0)
+
+
 class CoreDMA:
     """Core device Direct Memory Access (DMA) driver.
 
     Gives access to the DMA functionality of the core device.
     """
 
-    kernel_invariants = {"core", "recorder"}
+    kernel_invariants = {"core", "recorder", "reserve_recorder"}
 
     def __init__(self, dmgr, core_device="core"):
         self.core     = dmgr.get(core_device)
         self.recorder = DMARecordContextManager()
+        self.reserve_recorder = DMAReserveRecordContextManager()
         self.epoch    = 0
 
     @kernel
@@ -91,12 +161,82 @@ class CoreDMA:
         self.recorder.enable_ddma = enable_ddma
         return self.recorder
 
+    @kernel
+    def record_reserve(self, name, expected_bytes, enable_ddma=False):
+        """Same as :meth:`record`, but reserves a recording buffer of at
+        least ``expected_bytes`` up front, e.g. as returned by
+        :meth:`checkpoint` at the end of an earlier recording of the same
+        sequence. The events are then not sent to the comms CPU while they
+        are recorded, unless the trace outgrows the estimate, which keeps
+        the timing of the recording steady.
+
+        :raises DMAError: if ``expected_bytes`` is negative, or the buffer
+            cannot be allocated."""
+        self.epoch += 1
+        self.reserve_recorder.name = name
+        self.reserve_recorder.expected_bytes = expected_bytes
+        self.reserve_recorder.enable_ddma = enable_ddma
+        return self.reserve_recorder
+
+    @kernel
+    def checkpoint(self):
+        """Sends the events recorded so far in the current trace to the
+        comms CPU, instead of waiting for the recording buffer to fill up,
+        and returns the size of the trace so far in bytes. This does not
+        change the trace.
+
+        It must be called while recording, otherwise :exc:`DMAError` is
+        raised."""
+        return dma_record_checkpoint()
+
+    @kernel
+    def error_is_recoverable(self, error_bits):
+        """Returns whether a playback that failed with the DMA error bits
+        ``error_bits`` may succeed if retried: bit 0 (underflow) is
+        recoverable, bit 1 (destination unreachable) and any unknown bit are
+        not. Kernels can interpret the bits themselves for another policy."""
+        return rtio_error_is_recoverable(error_bits)
+
     @kernel
     def erase(self, name):
         """Removes the DMA trace with the given name from storage."""
         self.epoch += 1
         dma_erase(name)
 
+    @kernel
+    def dump(self, name, raise_if_missing=False):
+        """Sends the raw bytes of the DMA trace with the given name to the
+        host, where they are stored by name in the ``dma_dumps`` dictionary
+        of ``core.comm`` once the kernel returns. Returns whether the trace
+        exists; if it does not, :exc:`DMAError` is raised instead when
+        ``raise_if_missing`` is set.
+
+        Traces cannot be dumped from subkernels, where this only logs a
+        warning."""
+        return dma_dump(name, raise_if_missing)
+
+    @kernel
+    def concat(self, name, parts, offsets):
+        """Records a DMA trace called `name` that plays back the traces
+        named in `parts` in order, the events of each one being delayed by
+        the matching element of `offsets` (in machine units). Any previously
+        recorded trace with the same name is overwritten.
+
+        No part may start before the events of the previous parts end, and
+        traces recorded with distributed DMA cannot be concatenated; a
+        :exc:`DMAError` is raised otherwise."""
+        self.epoch += 1
+        dma_concat(name, parts, offsets)
+
+    @kernel
+    def concat_pair(self, name, a, b, gap_mu=0):
+        """Records a DMA trace called `name` that plays back the trace `a`,
+        then the trace `b` starting ``gap_mu`` machine units after the end of
+        `a`. The duration of the result is that of `a`, plus ``gap_mu``, plus
+        that of `b`. The same restrictions as for :meth:`concat` apply."""
+        self.epoch += 1
+        dma_concat_pair(name, a, b, gap_mu)
+
     @kernel
     def playback(self, name):
         """Replays a previously recorded DMA trace. This function blocks until
@@ -122,3 +262,65 @@ class CoreDMA:
             raise DMAError("Invalid handle")
         dma_playback(now_mu(), ptr, uses_ddma)
         delay_mu(advance_mu)
+
+    @kernel
+    def playback_handle_timed(self, handle):
+        """Replays a handle obtained with :meth:`get_handle`, like
+        :meth:`playback_handle`, and returns how long the DMA engine took to
+        submit the trace, in machine units. A value exceeding the duration
+        of the trace points at backpressure from the RTIO FIFOs."""
+        (epoch, advance_mu, ptr, uses_ddma) = handle
+        if self.epoch != epoch:
+            raise DMAError("Invalid handle")
+        elapsed_mu = dma_playback_timed(now_mu(), ptr, uses_ddma)
+        delay_mu(advance_mu)
+        return elapsed_mu
+
+    @kernel
+    def playback_handle_offset(self, handle, offset_mu):
+        """Replays a handle obtained with :meth:`get_handle`, like
+        :meth:`playback_handle`, with all its events shifted by ``offset_mu``,
+        e.g. to compensate for the latency of a device without recording the
+        trace again.
+
+        The timeline cursor is advanced by the duration of the trace, as with
+        :meth:`playback_handle`, regardless of the offset.
+
+        :raises ValueError: if the shifted timestamp overflows."""
+        (epoch, advance_mu, ptr, uses_ddma) = handle
+        if self.epoch != epoch:
+            raise DMAError("Invalid handle")
+        dma_playback_offset(now_mu(), ptr, uses_ddma, offset_mu)
+        delay_mu(advance_mu)
+
+    @kernel
+    def state(self):
+        """Returns :data:`DMA_STATE_RECORDING` while a recording is in
+        progress, in which case RTIO outputs go to the trace instead of the
+        hardware, :data:`DMA_STATE_PLAYING` while a trace is being played
+        back, and :data:`DMA_STATE_IDLE` otherwise."""
+        return dma_state()
+
+    @kernel
+    def alloc_buffer(self, size, align):
+        """Allocates ``size`` bytes on the core device for gateware to access
+        directly, and returns their address.
+
+        The buffer is aligned to ``align`` bytes, or to 64 bytes if that is
+        larger, as :meth:`playback` requires, and does not share a cache
+        line with other data. It stays at the same address until it is
+        freed with :meth:`free_buffer`, or until the kernel terminates.
+
+        :raises ValueError: if ``size`` is not positive, or ``align`` is not
+            a power of two.
+        :raises DMAError: if the memory is exhausted, or 16 buffers are
+            already allocated."""
+        return dma_alloc(size, align)
+
+    @kernel
+    def free_buffer(self, address):
+        """Frees a buffer allocated with :meth:`alloc_buffer`.
+
+        :raises DMAError: if ``address`` is not that of an allocated
+            buffer."""
+        dma_free(address)
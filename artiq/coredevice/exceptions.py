@@ -2,6 +2,8 @@ import builtins
 import linecache
 import re
 import os
+import string
+import struct
 from numpy.linalg import LinAlgError
 
 from artiq import __artiq_dir__ as artiq_dir
@@ -26,21 +28,50 @@ TimeoutError = builtins.TimeoutError
 TypeError = builtins.TypeError
 ValueError = builtins.ValueError
 ZeroDivisionError = builtins.ZeroDivisionError
+RecursionError = builtins.RecursionError
 OSError = builtins.OSError
 
 
+def decode_params(message, params):
+    """Decodes the float parameters of an exception message.
+
+    The core device encodes float parameters as the bits of an IEEE 754 double
+    in the int64 slot. A parameter is treated as a float if the message formats
+    it with a floating-point presentation type, e.g. ``{0:.3e}`` or ``{1:f}``.
+    """
+    params = list(params)
+    try:
+        fields = list(string.Formatter().parse(message))
+    except ValueError:
+        return params
+    for _, field, spec, _ in fields:
+        if field is None or not field.isdigit() or not spec or spec[-1] not in "eEfFgG%":
+            continue
+        index = int(field)
+        if index < len(params) and isinstance(params[index], int):
+            params[index] = struct.unpack("<d", struct.pack("<q", params[index]))[0]
+    return params
+
+
 class CoreException:
     """Information about an exception raised or passed through the core device.
 
      If the exception message contains positional format arguments, it
      will attempt to substitute them with the provided parameters.
      If the substitution fails, the original message will remain unchanged.
+
+     ``frame_sizes`` holds the stack extent in bytes of the frame of each
+     backtrace entry, or 0 if it is not known, e.g. for the outermost frame.
     """
-    def __init__(self, exceptions, exception_info, traceback, stack_pointers):
+    def __init__(self, exceptions, exception_info, traceback, stack_pointers,
+                 frame_sizes=None):
         self.exceptions = exceptions
         self.exception_info = exception_info
         self.traceback = list(traceback)
         self.stack_pointers = stack_pointers
+        if frame_sizes is None:
+            frame_sizes = [0] * len(stack_pointers)
+        self.frame_sizes = frame_sizes
 
         first_exception = exceptions[0]
         name = first_exception[0]
@@ -98,7 +129,8 @@ class CoreException:
         else:
             exn_id = 0
         try:
-            lines.append("{}({}): {}".format(name, exn_id, message.format(*params)))
+            lines.append("{}({}): {}".format(name, exn_id,
+                                             message.format(*decode_params(message, params))))
         except:
             lines.append("{}({}): {}".format(name, exn_id, message))
         zipped.append(((exception[3], exception[4], exception[5], exception[6],
@@ -161,6 +193,13 @@ class RTIODestinationUnreachable(Exception):
     artiq_builtin = True
 
 
+class RTIOError(Exception):
+    """Raised when the timeline cursor stack (see
+    :meth:`artiq.coredevice.core.Core.push_cursor`) overflows or underflows.
+    """
+    artiq_builtin = True
+
+
 class DMAError(Exception):
     """Raised when performing an invalid DMA operation."""
     artiq_builtin = True
@@ -177,13 +216,41 @@ class ClockFailure(Exception):
     """Raised when RTIO PLL has lost lock."""
     artiq_builtin = True
 
+class KernelPanic(Exception):
+    """Raised on the host when the kernel CPU firmware panicked.
+
+    The ``file``, ``line`` and ``column`` attributes give the location
+    of the panic in the firmware."""
+    def __init__(self, message, file, line, column):
+        Exception.__init__(self, message)
+        self.file = file
+        self.line = line
+        self.column = column
+
 class I2CError(Exception):
-    """Raised when a I2C transaction fails."""
+    """Raised when a I2C transaction fails.
+
+    For transactions performed by the core device CPU, the message gives
+    the device address, when the transaction addresses one, and an error
+    code:
+
+    * 1: the bus does not exist or is not supported by the core device,
+    * 2: the device did not acknowledge (e.g. it is missing),
+    * 3: SCL or SDA is stuck low,
+    * 4: arbitration was lost,
+    * 5: other bus fault,
+    * 6: the address is invalid (see :func:`artiq.coredevice.i2c.nrt_address`).
+    """
     artiq_builtin = True
 
 
 class SPIError(Exception):
-    """Raised when a SPI transaction fails."""
+    """Raised when a SPI transaction fails.
+
+    For transactions performed by the core device CPU, the message gives an
+    error code: 1 if the bus does not exist or is not supported by the core
+    device, 5 for other bus faults.
+    """
     artiq_builtin = True
 
 
@@ -194,3 +261,19 @@ class UnwrapNoneError(Exception):
 class CXPError(Exception):
     """Raised when CXP transaction fails."""
     artiq_builtin = True
+
+class WatchdogTimeout(Exception):
+    """Raised when the kernel watchdog expires while the kernel CPU is
+    blocked waiting on the comms CPU or the DMA engine."""
+    artiq_builtin = True
+
+class RPCError(Exception):
+    """Raised by the RPC keepalive (see :meth:`artiq.coredevice.core.Core.set_rpc_keepalive_mu`)
+    when the connection to the host was lost or the core device CPU stopped
+    responding."""
+    artiq_builtin = True
+
+class CXPTimeout(Exception):
+    """Raised when no CoaXPress frame is received before the timeout
+    (see :meth:`artiq.coredevice.cxp_grabber.CXPGrabber.grab_frame`)."""
+    artiq_builtin = True
@@ -4,7 +4,7 @@ Non-realtime drivers for I2C chips on the core device.
 
 
 from artiq.language.core import syscall, kernel
-from artiq.language.types import TBool, TInt32, TNone
+from artiq.language.types import TBool, TInt32, TNone, TList, TTuple
 from artiq.coredevice.exceptions import I2CError
 
 
@@ -38,6 +38,57 @@ def i2c_switch_select(busno: TInt32, address: TInt32, mask: TInt32) -> TNone:
     raise NotImplementedError("syscall not simulated")
 
 
+@syscall(flags={"nounwind", "nowrite"})
+def i2c_write_batch(busno: TInt32, busaddr: TInt32,
+                    writes: TList(TTuple([TInt32, TInt32]))) -> TNone:
+    raise NotImplementedError("syscall not simulated")
+
+
+@syscall(flags={"nounwind", "nowrite"})
+def i2c_read_async(busno: TInt32, busaddr: TInt32, addr: TInt32) -> TInt32:
+    raise NotImplementedError("syscall not simulated")
+
+
+@syscall(flags={"nounwind", "nowrite"})
+def i2c_read_collect(token: TInt32) -> TInt32:
+    raise NotImplementedError("syscall not simulated")
+
+
+@syscall(flags={"nounwind"})
+def i2c_read_batch(busno: TInt32, busaddr: TInt32, registers: TList(TInt32),
+                   values: TList(TInt32)) -> TNone:
+    raise NotImplementedError("syscall not simulated")
+
+
+@syscall(flags={"nounwind", "nowrite"})
+def nrt_write(address: TInt32, value: TInt32) -> TNone:
+    raise NotImplementedError("syscall not simulated")
+
+
+@syscall(flags={"nounwind", "nowrite"})
+def nrt_read(address: TInt32) -> TInt32:
+    raise NotImplementedError("syscall not simulated")
+
+
+@syscall(flags={"nounwind", "nowrite"})
+def nrt_write_batch(addresses: TList(TInt32), values: TList(TInt32)) -> TNone:
+    raise NotImplementedError("syscall not simulated")
+
+
+def nrt_address(busno, busaddr, register):
+    """Build the address of a peripheral register for :func:`nrt_write`
+    and :func:`nrt_read`.
+
+    The address holds the bus number in bits 16-23, the 8-bit device address
+    in bits 8-15 and the register in bits 0-7.
+
+    :param busno: I2C bus number
+    :param busaddr: 8-bit I2C device address (LSB=0)
+    :param register: 8-bit register address
+    """
+    return (busno & 0xff) << 16 | (busaddr & 0xfe) << 8 | (register & 0xff)
+
+
 @kernel
 def i2c_poll(busno, busaddr):
     """Poll I2C device at address.
@@ -142,6 +193,106 @@ def i2c_read_many(busno, busaddr, addr, data):
         i2c_stop(busno)
 
 
+@kernel
+def i2c_write_registers(busno, busaddr, writes):
+    """Write several 8-bit registers of a device in a single request to
+    the core device CPU.
+
+    Each register is written in its own I2C transaction.
+
+    :param busno: I2C bus number
+    :param busaddr: 8-bit I2C device address (LSB=0)
+    :param writes: List of ``(register, value)`` tuples
+    :raises I2CError: naming the index of the write that failed; the
+        following writes are not performed
+    """
+    i2c_write_batch(busno, busaddr, writes)
+
+
+@kernel
+def i2c_read_registers(busno, busaddr, registers, values):
+    """Read several 8-bit registers of a device in a single request to
+    the core device CPU.
+
+    :param busno: I2C bus number
+    :param busaddr: 8-bit I2C device address (LSB=0)
+    :param registers: List of register addresses
+    :param values: List of integers to be filled with the values read,
+        at least as long as ``registers``
+    :raises I2CError: naming the index of the read that failed; the
+        values before it are filled in
+    """
+    i2c_read_batch(busno, busaddr, registers, values)
+
+
+@kernel
+def i2c_read_register_async(busno, busaddr, addr):
+    """Start reading an 8-bit register of a device, without waiting for
+    the transaction to complete.
+
+    At most 8 reads can be in flight at once.
+
+    :param busno: I2C bus number
+    :param busaddr: 8-bit I2C device address (LSB=0)
+    :param addr: 8-bit register address
+    :returns: Token to pass to :func:`i2c_read_register_collect`
+    """
+    return i2c_read_async(busno, busaddr, addr)
+
+
+@kernel
+def i2c_read_register_collect(token):
+    """Wait for a read started with :func:`i2c_read_register_async`.
+
+    :param token: Token returned when the read was started
+    :returns: Byte read
+    :raises I2CError: if the read failed
+    """
+    return i2c_read_collect(token)
+
+
+@kernel
+def nrt_write_register(address, value):
+    """Write an 8-bit register of a peripheral, off the RTIO timeline.
+
+    :param address: Register address, see :func:`nrt_address`
+    :param value: 8-bit value
+    :raises I2CError: if the address is invalid (error code 6) or the
+        transaction failed
+    """
+    nrt_write(address, value)
+
+
+@kernel
+def nrt_read_register(address):
+    """Read an 8-bit register of a peripheral, off the RTIO timeline.
+
+    :param address: Register address, see :func:`nrt_address`
+    :returns: Byte read
+    :raises I2CError: if the address is invalid (error code 6) or the
+        transaction failed
+    """
+    return nrt_read(address)
+
+
+@kernel
+def nrt_write_registers(addresses, values):
+    """Write 8-bit registers of peripherals, off the RTIO timeline.
+
+    ``values[i]`` is written to ``addresses[i]``, in order. All addresses
+    and values are checked before the first write. Each register is written
+    in its own I2C transaction; consecutive writes to the same device are
+    only sent to the comms CPU as a single request.
+
+    :param addresses: List of register addresses, see :func:`nrt_address`
+    :param values: List of 8-bit values, of the same length
+    :raises I2CError: if an address is invalid (error code 6) or a write
+        failed. The batch is not atomic: a failed write stops it, its index
+        is given in the message, and the writes before it remain done
+    """
+    nrt_write_batch(addresses, values)
+
+
 class I2CSwitch:
     """Driver for the I2C bus switch.
 
@@ -1,5 +1,5 @@
 from artiq.language.core import syscall
-from artiq.language.types import TInt32, TInt64, TList, TNone, TTuple
+from artiq.language.types import TBool, TInt32, TInt64, TList, TNone, TTuple
 
 
 @syscall(flags={"nowrite"})
@@ -12,6 +12,42 @@ def rtio_output_wide(target: TInt32, data: TList(TInt32)) -> TNone:
     raise NotImplementedError("syscall not simulated")
 
 
+@syscall(flags={"nowrite"})
+def rtio_set_late_policy(policy: TInt32) -> TNone:
+    """Choose what happens to output events, direct or from DMA playback,
+    that are submitted too late: with ``policy`` 0 (the default),
+    :exc:`~artiq.coredevice.exceptions.RTIOUnderflow` is raised; with 1, the
+    events are dropped and counted by :func:`rtio_dropped_count`. The policy
+    is reset when a kernel is loaded."""
+    raise NotImplementedError("syscall not simulated")
+
+
+@syscall(flags={"nounwind", "nowrite"})
+def rtio_dropped_count() -> TInt32:
+    """Return the number of late output events dropped since the kernel
+    started. A DMA playback that underflows counts as one."""
+    raise NotImplementedError("syscall not simulated")
+
+
+@syscall(flags={"nounwind", "nowrite"})
+def rtio_min_slack() -> TInt64:
+    """Return the smallest slack, in machine units, with which an output
+    event was submitted since the kernel started, or the largest 64-bit
+    integer if none was. A value close to 0 means the kernel came close to
+    an underflow, even if none happened."""
+    raise NotImplementedError("syscall not simulated")
+
+
+@syscall(flags={"nounwind"})
+def rtio_sed_lane_levels(levels: TList(TInt32)) -> TInt32:
+    """Write the number of events waiting in each SED lane of the local RTIO
+    core into ``levels``, as many lanes as fit, and return the number of lanes.
+
+    Uneven loading of the lanes can cause underflows before the FIFOs are full.
+    The levels are those of the master only; satellites report 0 lanes."""
+    raise NotImplementedError("syscall not simulated")
+
+
 @syscall(flags={"nowrite"})
 def rtio_input_timestamp(timeout_mu: TInt64, channel: TInt32) -> TInt64:
     raise NotImplementedError("syscall not simulated")
@@ -29,3 +65,27 @@ def rtio_input_timestamped_data(timeout_mu: TInt64,
     return a tuple of timestamp and attached data, or (-1, 0) if the timeout is
     reached."""
     raise NotImplementedError("syscall not simulated")
+
+
+@syscall(flags={"nowrite"})
+def rtio_set_input_overflow_sticky(channel: TInt32, sticky: TBool) -> TNone:
+    """Make the input overflows of ``channel`` set a flag, read with
+    :func:`rtio_input_overflowed`, instead of raising
+    :exc:`~artiq.coredevice.exceptions.RTIOOverflow` (``sticky``), or raise
+    again. The events lost to an overflow are skipped, and reads continue with
+    those that were kept. At most 16 channels can have sticky overflows at
+    once. This is reset when a kernel is loaded."""
+    raise NotImplementedError("syscall not simulated")
+
+
+@syscall(flags={"nounwind", "nowrite"})
+def rtio_input_overflowed(channel: TInt32) -> TBool:
+    """Return whether an input overflow happened on ``channel``, which must
+    have sticky overflows, since its flag was last cleared."""
+    raise NotImplementedError("syscall not simulated")
+
+
+@syscall(flags={"nounwind", "nowrite"})
+def rtio_clear_input_overflow(channel: TInt32) -> TNone:
+    """Clear the input overflow flag of ``channel``."""
+    raise NotImplementedError("syscall not simulated")
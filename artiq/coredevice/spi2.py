@@ -8,7 +8,7 @@ time results in collision errors.
 """
 
 from artiq.language.core import syscall, kernel, portable, delay_mu
-from artiq.language.types import TInt32, TNone
+from artiq.language.types import TInt32, TNone, TList
 from artiq.coredevice.rtio import rtio_output, rtio_input_data
 
 
@@ -256,6 +256,16 @@ def spi_read(busno: TInt32) -> TInt32:
     raise NotImplementedError("syscall not simulated")
 
 
+@syscall(flags={"nounwind", "nowrite"})
+def spi_write_wide(busno: TInt32, data: TList(TInt32)) -> TNone:
+    raise NotImplementedError("syscall not simulated")
+
+
+@syscall(flags={"nounwind"})
+def spi_read_wide(busno: TInt32, data: TList(TInt32)) -> TNone:
+    raise NotImplementedError("syscall not simulated")
+
+
 class NRTSPIMaster:
     """Core device non-realtime Serial Peripheral Interface (SPI) bus master.
     Owns one non-realtime SPI bus.
@@ -288,3 +298,22 @@ class NRTSPIMaster:
     @kernel
     def read(self):
         return spi_read(self.busno)
+
+    @kernel
+    def write_wide(self, data):
+        """Write up to 16 words in a single request to the core device CPU.
+
+        :param data: List of SPI output data words.
+        """
+        spi_write_wide(self.busno, data)
+
+    @kernel
+    def read_wide(self, data):
+        """Transfer up to 16 words in a single request to the core device
+        CPU, replacing each element of ``data`` with the word read back
+        during its transfer.
+
+        :param data: List of SPI output data words, overwritten with the
+            input data.
+        """
+        spi_read_wide(self.busno, data)
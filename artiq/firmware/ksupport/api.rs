@@ -123,6 +123,7 @@ static mut API: &'static [(&'static str, *const ())] = &[
     api!(__nac3_raise = ::eh_artiq::raise),
     api!(__nac3_resume = ::eh_artiq::resume),
     api!(__nac3_end_catch = ::eh_artiq::end_catch),
+    api!(set_backtrace_keep_outermost = ::eh_artiq::set_backtrace_keep_outermost),
     /* legacy exception symbols */
     api!(__artiq_personality = ::eh_artiq::personality),
     api!(__artiq_raise = ::eh_artiq::raise),
@@ -131,37 +132,106 @@ static mut API: &'static [(&'static str, *const ())] = &[
 
     /* proxified syscalls */
     api!(core_log),
+    api!(core_log_binary = ::send_binary_to_core_log),
+    api!(core_log_flush = ::log_buffer::flush_extern),
+    api!(core_log_tail = ::log_buffer::tail),
+    api!(set_log_level = ::set_log_level),
 
     api!(now = csr::rtio::NOW_HI_ADDR as *const _),
 
+    api!(kernel_image_sizes = ::kernel_image_sizes),
+    api!(heap_largest_free_block = ::heap_largest_free_block),
+
     api!(rpc_send = ::rpc_send),
     api!(rpc_send_async = ::rpc_send_async),
     api!(rpc_recv = ::rpc_recv),
+    api!(rpc_drain = ::rpc_drain),
+    api!(publish_result = ::publish_result),
 
     api!(cache_get = ::cache_get),
     api!(cache_put = ::cache_put),
+    api!(cache_get_bytes = ::cache_get_bytes),
+    api!(cache_put_bytes = ::cache_put_bytes),
+    api!(cache_try_put = ::cache_try_put),
+    api!(cache_cas = ::cache_cas),
 
     /* direct syscalls */
     api!(rtio_init = ::rtio::init),
+    api!(rtio_reset = ::rtio_reset),
     api!(rtio_get_destination_status = ::rtio::get_destination_status),
+    api!(drtio_link_up = ::rtio::drtio_link_up),
     api!(rtio_get_counter = ::rtio::get_counter),
+    api!(rtio_wait_until = ::rtio::wait_until),
+    api!(rtio_save_cursor = ::rtio::save_cursor),
+    api!(rtio_restore_cursor = ::rtio::restore_cursor),
+    api!(rtio_max_cursor = ::rtio::max_cursor),
+    api!(rtio_now_push = ::rtio::now_push),
+    api!(rtio_now_pop = ::rtio::now_pop),
+    api!(rtio_resolution = ::rtio::resolution),
+    api!(rtio_mu_to_coarse = ::rtio::mu_to_coarse),
+    api!(rtio_coarse_to_mu = ::rtio::coarse_to_mu),
+    api!(rtio_seconds_to_mu = ::rtio::seconds_to_mu),
+    api!(rtio_mu_to_seconds = ::rtio::mu_to_seconds),
     api!(rtio_log),
     api!(rtio_output = ::rtio::output),
     api!(rtio_output_wide = ::rtio::output_wide),
+    api!(rtio_set_late_policy = ::rtio::set_late_policy),
+    api!(rtio_dropped_count = ::rtio::dropped_count),
+    api!(rtio_min_slack = ::rtio::min_slack),
+    api!(rtio_sed_lane_levels = ::rtio::sed_lane_levels),
     api!(rtio_input_timestamp = ::rtio::input_timestamp),
     api!(rtio_input_data = ::rtio::input_data),
     api!(rtio_input_timestamped_data = ::rtio::input_timestamped_data),
+    api!(rtio_set_input_overflow_sticky = ::rtio::set_input_overflow_sticky),
+    api!(rtio_input_overflowed = ::rtio::input_overflowed),
+    api!(rtio_clear_input_overflow = ::rtio::clear_input_overflow),
+
+    api!(watchdog_set = ::watchdog::set),
+    api!(watchdog_pet = ::watchdog::pet),
+    api!(rpc_keepalive = ::keepalive::set),
+    api!(set_max_stack_depth = ::stack_limit::set),
+    api!(stack_check = ::stack_limit::check),
+    api!(core_log_rate_limit = ::log_limit::set),
+
+    api!(perf_cycles = ::perf::cycles_extern),
+    api!(perf_section_begin = ::perf::section_begin),
+    api!(perf_section_end = ::perf::section_end),
+    api!(kernel_delay_cycles = ::perf::delay_cycles),
+    api!(mailbox_latency_stats = ::perf::mailbox_latency_stats),
+    api!(mailbox_idle_spins = ::idle::set),
 
     api!(dma_record_start = ::dma_record_start),
+    api!(dma_record_start_reserve = ::dma_record_start_reserve),
     api!(dma_record_stop = ::dma_record_stop),
+    api!(dma_record_checkpoint = ::dma_record_checkpoint),
     api!(dma_erase = ::dma_erase),
     api!(dma_retrieve = ::dma_retrieve),
+    api!(dma_dump = ::dma_dump),
+    api!(dma_concat = ::dma_concat),
+    api!(dma_concat_pair = ::dma_concat_pair),
     api!(dma_playback = ::dma_playback),
+    api!(dma_playback_timed = ::dma_playback_timed),
+    api!(rtio_error_is_recoverable = ::rtio_error_is_recoverable),
+    api!(dma_playback_offset = ::dma_playback_offset),
+    api!(dma_state = ::dma_state),
+    api!(dma_alloc = ::dma_alloc),
+    api!(dma_free = ::dma_free),
 
+    api!(kernel_self_destination = ::kernel_self_destination),
+    api!(mailbox_selftest = ::mailbox_selftest),
+    api!(pmp_dump = ::pmp_dump),
+    api!(set_rpc_format = ::set_rpc_format),
+    api!(set_partial_result = ::set_partial_result),
     api!(subkernel_load_run = ::subkernel_load_run),
+    api!(subkernel_load_group = ::subkernel_load_group),
+    api!(subkernel_is_available = ::subkernel_is_available),
     api!(subkernel_send_message = ::subkernel_send_message),
     api!(subkernel_await_message = ::subkernel_await_message),
+    api!(subkernel_send_message_stream = ::subkernel_send_message_stream),
+    api!(subkernel_await_message_stream = ::subkernel_await_message_stream),
     api!(subkernel_await_finish = ::subkernel_await_finish),
+    api!(subkernel_cache_results = ::subkernel_cache_results),
+    api!(subkernel_cache_clear = ::subkernel_cache_clear),
 
     api!(i2c_start = ::nrt_bus::i2c::start),
     api!(i2c_restart = ::nrt_bus::i2c::restart),
@@ -169,16 +239,43 @@ static mut API: &'static [(&'static str, *const ())] = &[
     api!(i2c_write = ::nrt_bus::i2c::write),
     api!(i2c_read = ::nrt_bus::i2c::read),
     api!(i2c_switch_select = ::nrt_bus::i2c::switch_select),
+    api!(i2c_write_batch = ::nrt_bus::i2c::write_batch),
+    api!(i2c_read_batch = ::nrt_bus::i2c::read_batch),
+    api!(i2c_read_async = ::nrt_bus::i2c::read_async),
+    api!(i2c_read_collect = ::nrt_bus::i2c::read_collect),
 
     api!(spi_set_config = ::nrt_bus::spi::set_config),
     api!(spi_write = ::nrt_bus::spi::write),
     api!(spi_read = ::nrt_bus::spi::read),
+    api!(spi_write_wide = ::nrt_bus::spi::write_wide),
+    api!(spi_read_wide = ::nrt_bus::spi::read_wide),
+
+    api!(nrt_write = ::nrt_bus::reg::write),
+    api!(nrt_write_batch = ::nrt_bus::reg::write_batch),
+    api!(nrt_read = ::nrt_bus::reg::read),
 
     api!(cxp_download_xml_file = ::cxp::download_xml_file),
+    api!(cxp_download_xml_file_cam = ::cxp::download_xml_file_cam),
     api!(cxp_read32 = ::cxp::read32),
+    api!(cxp_read32_cam = ::cxp::read32_cam),
     api!(cxp_write32 = ::cxp::write32),
+    api!(cxp_write32_cam = ::cxp::write32_cam),
+    api!(cxp_trigger_at = ::cxp::trigger_at),
+    api!(cxp_trigger_at_cam = ::cxp::trigger_at_cam),
+    api!(cxp_link_status = ::cxp::link_status),
+    api!(cxp_link_status_cam = ::cxp::link_status_cam),
+    api!(cxp_link_status_word = ::cxp::link_status_word),
+    api!(cxp_set_roi = ::cxp::set_roi),
+    api!(cxp_set_roi_cam = ::cxp::set_roi_cam),
+    api!(cxp_num_cameras = ::cxp::num_cameras),
+    api!(cxp_convert_frame = ::cxp::convert_frame),
     api!(cxp_start_roi_viewer = ::cxp::start_roi_viewer),
+    api!(cxp_start_roi_viewer_cam = ::cxp::start_roi_viewer_cam),
     api!(cxp_download_roi_viewer_frame = ::cxp::download_roi_viewer_frame),
+    api!(cxp_download_roi_viewer_frame_cam = ::cxp::download_roi_viewer_frame_cam),
+    api!(cxp_stream_frame = ::cxp::stream_frame),
+    api!(cxp_stream_frame_cam = ::cxp::stream_frame_cam),
+    api!(cxp_grab_frame = ::cxp::grab_frame),
 
     api!(nac3_free = ::mem::nac3_free),
     api!(nac3_malloc = ::mem::nac3_malloc),
@@ -1,11 +1,13 @@
 use core::{fmt, str};
 
-use byteorder::{ByteOrder, NetworkEndian};
-use cslice::CMutSlice;
+use byteorder::{ByteOrder, NativeEndian, NetworkEndian};
+use cslice::{CMutSlice, CSlice};
 use io::{Cursor, Write};
 use proto_artiq::drtioaux_proto::CXP_PAYLOAD_MAX_SIZE;
 
-use crate::{recv, send, Message};
+use board_misoc::csr;
+
+use crate::{recv, rtio, send, Message};
 
 const URL_BUF_SIZE: usize = 256;
 const ROI_MAX_SIZE: usize = 4096;
@@ -21,10 +23,40 @@ pub struct ROIViewerFrame {
     pixel_width: i32,
 }
 
+// Pixel format codes of the monochrome formats, as reported by the camera
+const PIXEL_FORMAT_MONO8: i32 = 0x0101;
+const PIXEL_FORMAT_MONO16: i32 = 0x0105;
+
+fn mono_pixel_width(pixel_code: i32) -> Option<usize> {
+    match pixel_code {
+        0x0101 => Some(8),
+        0x0102 => Some(10),
+        0x0103 => Some(12),
+        0x0104 => Some(14),
+        0x0105 => Some(16),
+        _ => None,
+    }
+}
+
+#[repr(C)]
+pub struct FrameGrab {
+    size: i32,
+    complete: bool,
+}
+
+#[repr(C)]
+pub struct LinkStatus {
+    up: bool,
+    lanes: i32,
+    bitrate_code: i32,
+}
+
 enum Error {
     BufferSizeTooSmall(usize, usize),
     ROISizeTooBig(usize, usize),
     InvalidLocalUrl(URLBuffer),
+    ROIOutOfRange(i32, i32, i32, i32, i32, i32),
+    UnsupportedConversion(i32, i32),
 }
 
 impl fmt::Display for Error {
@@ -56,6 +88,20 @@ impl fmt::Display for Error {
                     str::from_utf8(&buf).unwrap()
                 )
             }
+            &Error::ROIOutOfRange(x, y, width, height, width_max, height_max) => {
+                write!(
+                    f,
+                    "ROIOutOfRange - The ROI of {}x{} pixels at ({}, {}) does not fit in the {}x{} pixels sensor",
+                    width, height, x, y, width_max, height_max
+                )
+            }
+            &Error::UnsupportedConversion(src_fmt, dst_fmt) => {
+                write!(
+                    f,
+                    "UnsupportedConversion - Cannot convert from pixel format {:#06x} to {:#06x}",
+                    src_fmt, dst_fmt
+                )
+            }
         }
     }
 }
@@ -180,7 +226,7 @@ where
     Ok(((size + padding) / 4) as u32)
 }
 
-fn drtio_read_bytes(dest: u8, addr: u32, bytes: &mut [u8]) {
+fn drtio_read_bytes(dest: u8, cam: u8, addr: u32, bytes: &mut [u8]) {
     let length = bytes.len() as u16;
     if length as usize > CXP_PAYLOAD_MAX_SIZE {
         panic!("CXPReadRequest length is too long")
@@ -188,6 +234,7 @@ fn drtio_read_bytes(dest: u8, addr: u32, bytes: &mut [u8]) {
 
     send(&Message::CXPReadRequest {
         destination: dest,
+        cam,
         address: addr,
         length,
     });
@@ -200,14 +247,14 @@ fn drtio_read_bytes(dest: u8, addr: u32, bytes: &mut [u8]) {
     })
 }
 
-pub extern "C" fn download_xml_file(dest: i32, buffer: &mut CMutSlice<i32>) -> i32 {
+pub extern "C" fn download_xml_file_cam(dest: i32, cam: i32, buffer: &mut CMutSlice<i32>) -> i32 {
     match dest {
         0 => {
             raise!("CXPError", "CXP Grabber is not available on destination 0");
         }
         _ => match read_xml_file(
             buffer.as_mut_slice(),
-            |addr, bytes| drtio_read_bytes(dest as u8, addr, bytes),
+            |addr, bytes| drtio_read_bytes(dest as u8, cam as u8, addr, bytes),
             CXP_PAYLOAD_MAX_SIZE,
         ) {
             Ok(size_read) => size_read as i32,
@@ -220,27 +267,48 @@ pub extern "C" fn download_xml_file(dest: i32, buffer: &mut CMutSlice<i32>) -> i
     }
 }
 
-pub extern "C" fn read32(dest: i32, addr: i32) -> i32 {
+/// Same as `download_xml_file_cam`, on camera 0.
+pub extern "C" fn download_xml_file(dest: i32, buffer: &mut CMutSlice<i32>) -> i32 {
+    download_xml_file_cam(dest, 0, buffer)
+}
+
+fn check_reg_address(addr: i32) {
+    // Register accesses are 4 bytes wide and shall be aligned to 4 bytes - Section 12.2 (CXP-001-2021)
+    if addr & 0b11 != 0 {
+        raise!("CXPError", "UnalignedAddress - Register address {0:#010x} is not aligned to 4 bytes",
+               addr as u32 as i64, 0, 0);
+    }
+}
+
+pub extern "C" fn read32_cam(dest: i32, cam: i32, addr: i32) -> i32 {
     match dest {
         0 => {
             raise!("CXPError", "CXP Grabber is not available on destination 0");
         }
         _ => {
+            check_reg_address(addr);
             let mut bytes: [u8; 4] = [0; 4];
-            drtio_read_bytes(dest as u8, addr as u32, &mut bytes);
+            drtio_read_bytes(dest as u8, cam as u8, addr as u32, &mut bytes);
             NetworkEndian::read_i32(&bytes)
         }
     }
 }
 
-pub extern "C" fn write32(dest: i32, addr: i32, val: i32) {
+/// Same as `read32_cam`, on camera 0.
+pub extern "C" fn read32(dest: i32, addr: i32) -> i32 {
+    read32_cam(dest, 0, addr)
+}
+
+pub extern "C" fn write32_cam(dest: i32, cam: i32, addr: i32, val: i32) {
     match dest {
         0 => {
             raise!("CXPError", "CXP Grabber is not available on destination 0");
         }
         _ => {
+            check_reg_address(addr);
             send(&Message::CXPWrite32Request {
                 destination: dest as u8,
+                cam: cam as u8,
                 address: addr as u32,
                 value: val as u32,
             });
@@ -253,7 +321,167 @@ pub extern "C" fn write32(dest: i32, addr: i32, val: i32) {
     }
 }
 
-pub extern "C" fn start_roi_viewer(dest: i32, x0: i32, y0: i32, x1: i32, y1: i32) {
+/// Same as `write32_cam`, on camera 0.
+pub extern "C" fn write32(dest: i32, addr: i32, val: i32) {
+    write32_cam(dest, 0, addr, val)
+}
+
+// The driver passes -1 for the addresses when no ROI registers were given
+fn roi_registers_valid(registers: &[i32]) -> bool {
+    registers.len() == 6 && registers.iter().all(|&addr| addr >= 0)
+}
+
+fn roi_in_range(x: i32, y: i32, width: i32, height: i32, width_max: i32, height_max: i32) -> bool {
+    x >= 0 && y >= 0 && width > 0 && height > 0
+        && x as i64 + width as i64 <= width_max as i64
+        && y as i64 + height as i64 <= height_max as i64
+}
+
+/// Programs the camera region of interest.
+///
+/// `registers` holds the addresses of the OffsetX, OffsetY, Width, Height,
+/// WidthMax and HeightMax registers of the camera, in that order, as listed
+/// in its XML file. The ROI is checked against WidthMax and HeightMax.
+pub extern "C" fn set_roi_cam(dest: i32, cam: i32, registers: &CSlice<i32>, x: i32, y: i32, width: i32, height: i32) {
+    let registers = registers.as_ref();
+    if !roi_registers_valid(registers) {
+        raise!("CXPError", "ROIRegistersNotConfigured - The camera ROI register addresses are not set");
+    }
+    let (offset_x, offset_y, width_reg, height_reg) = (registers[0], registers[1], registers[2], registers[3]);
+    let width_max = read32_cam(dest, cam, registers[4]);
+    let height_max = read32_cam(dest, cam, registers[5]);
+
+    if !roi_in_range(x, y, width, height, width_max, height_max) {
+        let msg_buf = ErrMsgBuffer::from(Error::ROIOutOfRange(x, y, width, height, width_max, height_max));
+        raise!("CXPError", msg_buf);
+    }
+
+    // Offset + size may not exceed the sensor size at any time,
+    // so move the ROI to the origin before resizing it
+    write32_cam(dest, cam, offset_x, 0);
+    write32_cam(dest, cam, offset_y, 0);
+    write32_cam(dest, cam, width_reg, width);
+    write32_cam(dest, cam, height_reg, height);
+    write32_cam(dest, cam, offset_x, x);
+    write32_cam(dest, cam, offset_y, y);
+}
+
+/// Same as `set_roi_cam`, on camera 0.
+pub extern "C" fn set_roi(dest: i32, registers: &CSlice<i32>, x: i32, y: i32, width: i32, height: i32) {
+    set_roi_cam(dest, 0, registers, x, y, width, height)
+}
+
+// Width of the camera index, which is the address on the trigger RTIO channel
+const TRIGGER_CAMERA_BITS: i32 = 2;
+
+/// Sends a linktrigger to camera 0 on the trigger RTIO channel of a grabber at
+/// `timestamp`, leaving the timeline cursor where it was.
+///
+/// Underflows and unreachable destinations raise the usual RTIO exceptions.
+pub extern "C" fn trigger_at(channel: i32, timestamp: i64, linktrigger: i32, extra_linktrigger: bool) {
+    trigger_at_cam(channel, 0, timestamp, linktrigger, extra_linktrigger)
+}
+
+/// Same as `trigger_at`, for camera `cam` of the grabber.
+pub extern "C" fn trigger_at_cam(channel: i32, cam: i32, timestamp: i64, linktrigger: i32,
+                                 extra_linktrigger: bool) {
+    if cam < 0 || cam >= 1 << TRIGGER_CAMERA_BITS {
+        raise!("CXPError", "InvalidCamera - camera {0} does not exist", cam as i64, 0, 0);
+    }
+    // linktrigger 2 and 3 are only available when ExtraLsTriggerEnable is set on the camera
+    let max_linktrigger = if extra_linktrigger { 3 } else { 1 };
+    if linktrigger < 0 || linktrigger > max_linktrigger {
+        raise!(
+            "CXPError",
+            "InvalidLinkTrigger - linktrigger {0} is out of the supported range 0-{1}",
+            linktrigger as i64,
+            max_linktrigger,
+            0
+        );
+    }
+
+    unsafe {
+        let now_hi = csr::rtio::now_hi_read();
+        let now_lo = csr::rtio::now_lo_read();
+        csr::rtio::now_hi_write((timestamp >> 32) as u32);
+        csr::rtio::now_lo_write(timestamp as u32);
+        rtio::output(channel << 8 | cam, linktrigger << 1 | extra_linktrigger as i32);
+        csr::rtio::now_hi_write(now_hi);
+        csr::rtio::now_lo_write(now_lo);
+    }
+}
+
+/// Reports whether the camera link is up, with its number of connections and
+/// the bitrate code, as encoded in the ConnectionConfig register.
+pub extern "C" fn link_status_cam(dest: i32, cam: i32) -> LinkStatus {
+    match dest {
+        0 => {
+            raise!("CXPError", "CXP Grabber is not available on destination 0");
+        }
+        _ => {
+            send(&Message::CXPLinkStatusRequest {
+                destination: dest as u8,
+                cam: cam as u8,
+            });
+            recv(|result| match result {
+                Message::CXPLinkStatusReply {
+                    up,
+                    lanes,
+                    bitrate_code,
+                } => LinkStatus {
+                    up: *up,
+                    lanes: *lanes as i32,
+                    bitrate_code: *bitrate_code as i32,
+                },
+                Message::CXPError(err_msg) => raise!("CXPError", err_msg),
+                _ => unreachable!(),
+            })
+        }
+    }
+}
+
+/// Same as `link_status_cam`, on camera 0.
+pub extern "C" fn link_status(dest: i32) -> LinkStatus {
+    link_status_cam(dest, 0)
+}
+
+// Layout of the word returned by `link_status_word`
+const LINK_STATUS_UP: i32 = 1 << 0;
+const LINK_STATUS_LANES_SHIFT: i32 = 8;
+const LINK_STATUS_BITRATE_SHIFT: i32 = 16;
+
+/// Same as `link_status`, packed into a single word: bit 0 is set if the link
+/// is up, bits 8-15 hold the number of connections and bits 16-23 the bitrate
+/// code. Bits 24-31 are reserved for error flags and read as 0, since the
+/// grabber reports link errors by raising `CXPError`.
+pub extern "C" fn link_status_word(dest: i32, cam: i32) -> i32 {
+    if cam < 0 || cam > u8::MAX as i32 {
+        raise!("CXPError", "InvalidCamera - camera {0} does not exist", cam as i64, 0, 0);
+    }
+    let status = link_status_cam(dest, cam);
+    let up = if status.up { LINK_STATUS_UP } else { 0 };
+    up | (status.lanes & 0xff) << LINK_STATUS_LANES_SHIFT
+       | (status.bitrate_code & 0xff) << LINK_STATUS_BITRATE_SHIFT
+}
+
+/// Number of cameras attached to the grabber at `dest`.
+pub extern "C" fn num_cameras(dest: i32) -> i32 {
+    match dest {
+        0 => 0,
+        _ => {
+            send(&Message::CXPCameraCountRequest {
+                destination: dest as u8,
+            });
+            recv(|result| match result {
+                Message::CXPCameraCountReply { count } => *count as i32,
+                Message::CXPError(err_msg) => raise!("CXPError", err_msg),
+                _ => unreachable!(),
+            })
+        }
+    }
+}
+
+pub extern "C" fn start_roi_viewer_cam(dest: i32, cam: i32, x0: i32, y0: i32, x1: i32, y1: i32) {
     let (width, height) = ((x1 - x0) as usize, (y1 - y0) as usize);
     if width * height > ROI_MAX_SIZE || height > ROI_MAX_SIZE / 4 {
         let msg_buf = ErrMsgBuffer::from(Error::ROISizeTooBig(width, height));
@@ -267,6 +495,7 @@ pub extern "C" fn start_roi_viewer(dest: i32, x0: i32, y0: i32, x1: i32, y1: i32
         _ => {
             send(&Message::CXPROIViewerSetupRequest {
                 destination: dest as u8,
+                cam: cam as u8,
                 x0: x0 as u16,
                 y0: y0 as u16,
                 x1: x1 as u16,
@@ -280,8 +509,14 @@ pub extern "C" fn start_roi_viewer(dest: i32, x0: i32, y0: i32, x1: i32, y1: i32
     }
 }
 
-pub extern "C" fn download_roi_viewer_frame(
+/// Same as `start_roi_viewer_cam`, on camera 0.
+pub extern "C" fn start_roi_viewer(dest: i32, x0: i32, y0: i32, x1: i32, y1: i32) {
+    start_roi_viewer_cam(dest, 0, x0, y0, x1, y1)
+}
+
+pub extern "C" fn download_roi_viewer_frame_cam(
     dest: i32,
+    cam: i32,
     buffer: &mut CMutSlice<i64>,
 ) -> ROIViewerFrame {
     if buffer.len() * 4 < ROI_MAX_SIZE {
@@ -304,6 +539,7 @@ pub extern "C" fn download_roi_viewer_frame(
             loop {
                 send(&Message::CXPROIViewerDataRequest {
                     destination: dest as u8,
+                    cam: cam as u8,
                 });
                 let frame_data = recv(|result| match result {
                     Message::CXPROIVIewerPixelDataReply { length, data } => {
@@ -329,13 +565,9 @@ pub extern "C" fn download_roi_viewer_frame(
             }
         }
     };
-    let pixel_width = match pixel_code {
-        0x0101 => 8,
-        0x0102 => 10,
-        0x0103 => 12,
-        0x0104 => 14,
-        0x0105 => 16,
-        _ => raise!("CXPError", "UnsupportedPixelFormat"),
+    let pixel_width = match mono_pixel_width(pixel_code as i32) {
+        Some(pixel_width) => pixel_width,
+        None => raise!("CXPError", "UnsupportedPixelFormat"),
     };
     ROIViewerFrame {
         width: width as i32,
@@ -343,3 +575,247 @@ pub extern "C" fn download_roi_viewer_frame(
         pixel_width: pixel_width as i32,
     }
 }
+
+/// Same as `download_roi_viewer_frame_cam`, on camera 0.
+pub extern "C" fn download_roi_viewer_frame(dest: i32, buffer: &mut CMutSlice<i64>) -> ROIViewerFrame {
+    download_roi_viewer_frame_cam(dest, 0, buffer)
+}
+
+/// Copies the next ROI viewer frame into `buffer` and returns its size in bytes.
+///
+/// The frame is stored as native-endian 64-bit words holding four 16-bit pixels
+/// each, in the same layout as `download_roi_viewer_frame`. A frame larger than
+/// `buffer` is truncated, in which case the returned size exceeds `buffer.len()`.
+/// A short frame is stored as received.
+pub extern "C" fn stream_frame_cam(dest: i32, cam: i32, mut buffer: CMutSlice<u8>) -> i32 {
+    let buf = buffer.as_mut_slice();
+    let mut size = 0;
+    match dest {
+        0 => {
+            raise!("CXPError", "CXP Grabber is not available on destination 0");
+        }
+        _ => loop {
+            send(&Message::CXPROIViewerDataRequest {
+                destination: dest as u8,
+                cam: cam as u8,
+            });
+            let done = recv(|result| match result {
+                Message::CXPROIVIewerPixelDataReply { length, data } => {
+                    for d in &data[..*length as usize] {
+                        if size + 8 <= buf.len() {
+                            NativeEndian::write_u64(&mut buf[size..size + 8], *d);
+                        }
+                        size += 8;
+                    }
+                    false
+                }
+                Message::CXPROIVIewerFrameDataReply { .. } => true,
+                Message::CXPError(err_msg) => raise!("CXPError", err_msg),
+                _ => unreachable!(),
+            });
+            if done {
+                break;
+            }
+        },
+    }
+    size as i32
+}
+
+/// Same as `stream_frame_cam`, on camera 0.
+pub extern "C" fn stream_frame(dest: i32, buffer: CMutSlice<u8>) -> i32 {
+    stream_frame_cam(dest, 0, buffer)
+}
+
+/// Copies the next ROI viewer frame into `buffer`, in the same layout as
+/// `stream_frame`, waiting at most `timeout_mu` on the RTIO counter.
+///
+/// Returns the size of the frame in bytes, which exceeds `buffer.len()` if the
+/// frame was truncated, and whether the frame is complete. If the timeout
+/// expires while the frame is being received, the part received so far is
+/// returned as incomplete; the ROI viewer should then be restarted before
+/// grabbing the next frame. If nothing was received, `CXPTimeout` is raised.
+///
+/// Pixel data arrive as 64-bit words over the DRTIO aux channel and are copied
+/// bytewise, so `buffer` needs no particular alignment; only whole words are
+/// stored.
+pub extern "C" fn grab_frame(dest: i32, cam: i32, mut buffer: CMutSlice<u8>, timeout_mu: i64) -> FrameGrab {
+    if dest == 0 {
+        raise!("CXPError", "CXP Grabber is not available on destination 0");
+    }
+    let deadline = rtio::get_counter().saturating_add(timeout_mu);
+    let buf = buffer.as_mut_slice();
+    let mut size = 0;
+    loop {
+        send(&Message::CXPROIViewerPollRequest {
+            destination: dest as u8,
+            cam: cam as u8,
+        });
+        let done = recv(|result| match result {
+            Message::CXPROIViewerWaitReply => false,
+            Message::CXPROIVIewerPixelDataReply { length, data } => {
+                for d in &data[..*length as usize] {
+                    if size + 8 <= buf.len() {
+                        NativeEndian::write_u64(&mut buf[size..size + 8], *d);
+                    }
+                    size += 8;
+                }
+                false
+            }
+            Message::CXPROIVIewerFrameDataReply { .. } => true,
+            Message::CXPError(err_msg) => raise!("CXPError", err_msg),
+            _ => unreachable!(),
+        });
+        if done {
+            return FrameGrab { size: size as i32, complete: true };
+        }
+        if rtio::get_counter() > deadline {
+            if size == 0 {
+                raise!("CXPTimeout", "no frame received within {0} mu", timeout_mu, 0, 0);
+            }
+            return FrameGrab { size: size as i32, complete: false };
+        }
+        ::watchdog::check();
+    }
+}
+
+/// Unpacks the monochrome pixels of `src`, packed back to back starting from the
+/// least significant bit as sent by the camera, into `dst` and returns the
+/// number of pixels converted.
+///
+/// `dst_fmt` is either Mono16, with each pixel stored unscaled as a
+/// little-endian 16-bit value, or Mono8, keeping the 8 most significant bits.
+pub extern "C" fn convert_frame(src: CSlice<u8>, src_fmt: i32, mut dst: CMutSlice<u8>, dst_fmt: i32) -> i32 {
+    let (src_width, dst_bytes) = match (mono_pixel_width(src_fmt), dst_fmt) {
+        (Some(src_width), PIXEL_FORMAT_MONO8) => (src_width, 1),
+        (Some(src_width), PIXEL_FORMAT_MONO16) => (src_width, 2),
+        _ => {
+            let msg_buf = ErrMsgBuffer::from(Error::UnsupportedConversion(src_fmt, dst_fmt));
+            raise!("CXPError", msg_buf);
+        }
+    };
+    let src = src.as_ref();
+    let dst = dst.as_mut_slice();
+    let count = src.len() * 8 / src_width;
+    if dst.len() < count * dst_bytes {
+        let msg_buf = ErrMsgBuffer::from(Error::BufferSizeTooSmall(count * dst_bytes, dst.len()));
+        raise!("CXPError", msg_buf);
+    }
+
+    unpack_mono(src, src_width, dst, dst_bytes, count);
+    count as i32
+}
+
+// Unpacks `count` pixels of `src_width` bits into `dst`, with `dst_bytes`
+// bytes per pixel; `dst` must hold at least `count * dst_bytes` bytes
+fn unpack_mono(src: &[u8], src_width: usize, dst: &mut [u8], dst_bytes: usize, count: usize) {
+    let mask = (1u32 << src_width) - 1;
+    let (mut bits, mut bit_count) = (0u32, 0);
+    let mut i = 0;
+    for byte in src {
+        bits |= (*byte as u32) << bit_count;
+        bit_count += 8;
+        while bit_count >= src_width && i < count {
+            let pixel = bits & mask;
+            bits >>= src_width;
+            bit_count -= src_width;
+            if dst_bytes == 1 {
+                dst[i] = (pixel >> (src_width - 8)) as u8;
+            } else {
+                dst[2 * i] = pixel as u8;
+                dst[2 * i + 1] = (pixel >> 8) as u8;
+            }
+            i += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roi_registers() {
+        assert!(roi_registers_valid(&[0x3000, 0x3004, 0x3008, 0x300c, 0x3010, 0x3014]));
+        assert!(roi_registers_valid(&[0; 6]));
+        assert!(!roi_registers_valid(&[-1; 6]));
+        assert!(!roi_registers_valid(&[0x3000, 0x3004, 0x3008, -1, 0x3010, 0x3014]));
+        assert!(!roi_registers_valid(&[0x3000, 0x3004, 0x3008, 0x300c, 0x3010]));
+        assert!(!roi_registers_valid(&[0; 7]));
+        assert!(!roi_registers_valid(&[]));
+    }
+
+    #[test]
+    fn roi_range() {
+        assert!(roi_in_range(0, 0, 640, 480, 640, 480));
+        assert!(roi_in_range(10, 20, 630, 460, 640, 480));
+        assert!(roi_in_range(639, 479, 1, 1, 640, 480));
+        assert!(!roi_in_range(1, 0, 640, 480, 640, 480));
+        assert!(!roi_in_range(0, 1, 640, 480, 640, 480));
+        assert!(!roi_in_range(-1, 0, 16, 16, 640, 480));
+        assert!(!roi_in_range(0, -1, 16, 16, 640, 480));
+        assert!(!roi_in_range(0, 0, 0, 16, 640, 480));
+        assert!(!roi_in_range(0, 0, 16, -16, 640, 480));
+        // The sum must not wrap around
+        assert!(!roi_in_range(i32::MAX, 0, i32::MAX, 16, 640, 480));
+    }
+
+    #[test]
+    fn unpack_mono8() {
+        let src = [0x00, 0x7f, 0x80, 0xff];
+        let mut dst = [0; 4];
+        unpack_mono(&src, 8, &mut dst, 1, 4);
+        assert_eq!(dst, src);
+
+        let mut dst = [0; 8];
+        unpack_mono(&src, 8, &mut dst, 2, 4);
+        assert_eq!(dst, [0x00, 0x00, 0x7f, 0x00, 0x80, 0x00, 0xff, 0x00]);
+    }
+
+    #[test]
+    fn unpack_mono10() {
+        // Pixels 0x001, 0x3ff, 0x155 and 0x2aa, least significant bit first
+        let src = [0x01, 0xfc, 0x5f, 0x95, 0xaa];
+        let count = src.len() * 8 / mono_pixel_width(0x0102).unwrap();
+        assert_eq!(count, 4);
+
+        let mut dst = [0; 8];
+        unpack_mono(&src, 10, &mut dst, 2, count);
+        assert_eq!(dst, [0x01, 0x00, 0xff, 0x03, 0x55, 0x01, 0xaa, 0x02]);
+
+        let mut dst = [0; 4];
+        unpack_mono(&src, 10, &mut dst, 1, count);
+        assert_eq!(dst, [0x00, 0xff, 0x55, 0xaa]);
+    }
+
+    #[test]
+    fn unpack_mono12() {
+        // Pixels 0xabc and 0x123, least significant bit first
+        let src = [0xbc, 0x3a, 0x12];
+        let mut dst = [0; 4];
+        unpack_mono(&src, 12, &mut dst, 2, 2);
+        assert_eq!(dst, [0xbc, 0x0a, 0x23, 0x01]);
+    }
+
+    #[test]
+    fn unpack_partial() {
+        // The trailing bits of an incomplete pixel are dropped
+        let src = [0xff, 0xff, 0x0f];
+        let count = src.len() * 8 / 14;
+        assert_eq!(count, 1);
+        let mut dst = [0; 2];
+        unpack_mono(&src, 14, &mut dst, 2, count);
+        assert_eq!(dst, [0xff, 0x3f]);
+    }
+
+    #[test]
+    fn unpack_mono16() {
+        let src = [0x34, 0x12, 0xcd, 0xab];
+        let mut dst = [0; 4];
+        unpack_mono(&src, 16, &mut dst, 2, 2);
+        assert_eq!(dst, src);
+
+        let mut dst = [0; 2];
+        unpack_mono(&src, 16, &mut dst, 1, 2);
+        assert_eq!(dst, [0x12, 0xab]);
+    }
+}
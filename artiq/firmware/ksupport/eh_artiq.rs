@@ -40,14 +40,29 @@ const EXCEPTION_CLASS: uw::_Unwind_Exception_Class = 0x4d_4c_42_53_41_52_54_51;
 const MAX_INFLIGHT_EXCEPTIONS: usize = 10;
 const MAX_BACKTRACE_SIZE: usize = 128;
 
+// Whether a backtrace that does not fit keeps its outermost frames, which
+// lead to the kernel entry point, rather than its innermost ones.
+static mut KEEP_OUTERMOST_FRAMES: bool = false;
+
+// The backtrace entry recorded for the frame unwound just before the current
+// one, whose size is the distance to the SP of the current frame.
+static mut PREVIOUS_FRAME: Option<usize> = None;
+
+/// Makes the backtraces that overflow the buffer keep the frame they were
+/// raised in and their outermost frames, instead of their innermost frames.
+pub extern fn set_backtrace_keep_outermost(enable: bool) {
+    unsafe { KEEP_OUTERMOST_FRAMES = enable }
+}
+
 struct ExceptionBuffer {
     // we need n _Unwind_Exception, because each will have their own private data
     uw_exceptions: [uw::_Unwind_Exception; MAX_INFLIGHT_EXCEPTIONS],
     exceptions: [Option<Exception<'static>>; MAX_INFLIGHT_EXCEPTIONS + 1],
     exception_stack: [isize; MAX_INFLIGHT_EXCEPTIONS + 1],
     // nested exceptions will share the backtrace buffer, treated as a tree
-    // backtrace contains a tuple of IP and SP
-    backtrace: [(usize, usize); MAX_BACKTRACE_SIZE],
+    // backtrace contains a tuple of IP, SP and the size of the frame, which is
+    // only known once the caller has been unwound (0 until then)
+    backtrace: [(usize, usize, usize); MAX_BACKTRACE_SIZE],
     backtrace_size: usize,
     // stack pointers are stored to reconstruct backtrace for each exception
     stack_pointers: [StackPointerBacktrace; MAX_INFLIGHT_EXCEPTIONS + 1],
@@ -65,7 +80,7 @@ static mut EXCEPTION_BUFFER: ExceptionBuffer = ExceptionBuffer {
     uw_exceptions: [EXCEPTION; MAX_INFLIGHT_EXCEPTIONS],
     exceptions: [None; MAX_INFLIGHT_EXCEPTIONS + 1],
     exception_stack: [-1; MAX_INFLIGHT_EXCEPTIONS + 1],
-    backtrace: [(0, 0); MAX_BACKTRACE_SIZE],
+    backtrace: [(0, 0, 0); MAX_BACKTRACE_SIZE],
     backtrace_size: 0,
     stack_pointers: [StackPointerBacktrace {
         stack_pointer: 0,
@@ -81,6 +96,7 @@ pub unsafe extern fn reset_exception_buffer(payload_addr: usize) {
     EXCEPTION_BUFFER.exception_stack = [-1; MAX_INFLIGHT_EXCEPTIONS + 1];
     EXCEPTION_BUFFER.backtrace_size = 0;
     EXCEPTION_BUFFER.exception_count = 0;
+    KEEP_OUTERMOST_FRAMES = false;
     PAYLOAD_ADDRESS = payload_addr;
 }
 
@@ -172,6 +188,7 @@ pub unsafe extern "C-unwind" fn raise(exception: *const Exception) -> ! {
             }
         }
         assert!(found);
+        PREVIOUS_FRAME = None;
         let _result = _Unwind_ForcedUnwind(&mut EXCEPTION_BUFFER.uw_exceptions[stack[count - 1] as usize],
                                            stop_fn, core::ptr::null_mut());
     } else {
@@ -192,6 +209,7 @@ pub unsafe extern "C-unwind" fn raise(exception: *const Exception) -> ! {
                         current_backtrace_size: 0,
                     };
                     EXCEPTION_BUFFER.exception_count += 1;
+                    PREVIOUS_FRAME = None;
                     let _result = _Unwind_ForcedUnwind(&mut EXCEPTION_BUFFER.uw_exceptions[i],
                                                        stop_fn, core::ptr::null_mut());
                 }
@@ -223,6 +241,7 @@ pub unsafe extern "C-unwind" fn resume() -> ! {
     assert!(EXCEPTION_BUFFER.exception_count != 0);
     let i = EXCEPTION_BUFFER.exception_stack[EXCEPTION_BUFFER.exception_count - 1];
     assert!(i != -1);
+    PREVIOUS_FRAME = None;
     let _result = _Unwind_ForcedUnwind(&mut EXCEPTION_BUFFER.uw_exceptions[i as usize],
                                        stop_fn, core::ptr::null_mut());
     unreachable!()
@@ -305,20 +324,42 @@ extern fn stop_fn(_version: c_int,
                   context: *mut uw::_Unwind_Context,
                   _stop_parameter: *mut c_void) -> uw::_Unwind_Reason_Code {
     unsafe {
-        let backtrace_size = EXCEPTION_BUFFER.backtrace_size;
-        if backtrace_size < MAX_BACKTRACE_SIZE {
-            let ip = uw::_Unwind_GetIP(context);
-            let fp = uw::_Unwind_GetGR(context, UNW_FP_REG);
-            if PAYLOAD_ADDRESS == 0 || ip > PAYLOAD_ADDRESS {
-                let ip = ip - PAYLOAD_ADDRESS;
-                EXCEPTION_BUFFER.backtrace[backtrace_size] = (ip, fp);
+        let ip = uw::_Unwind_GetIP(context);
+        let fp = uw::_Unwind_GetGR(context, UNW_FP_REG);
+        if PAYLOAD_ADDRESS == 0 || ip > PAYLOAD_ADDRESS {
+            let ip = ip - PAYLOAD_ADDRESS;
+            if let Some(previous) = PREVIOUS_FRAME {
+                let (_, previous_fp, _) = EXCEPTION_BUFFER.backtrace[previous];
+                if fp >= previous_fp {
+                    EXCEPTION_BUFFER.backtrace[previous].2 = fp - previous_fp;
+                }
+            }
+            let last_index = EXCEPTION_BUFFER.exception_stack[EXCEPTION_BUFFER.exception_count - 1];
+            assert!(last_index != -1);
+            let initial_size = EXCEPTION_BUFFER.stack_pointers[last_index as usize].initial_backtrace_size;
+            let recorded = if EXCEPTION_BUFFER.backtrace_size < MAX_BACKTRACE_SIZE {
                 EXCEPTION_BUFFER.backtrace_size += 1;
-                let last_index = EXCEPTION_BUFFER.exception_stack[EXCEPTION_BUFFER.exception_count - 1];
-                assert!(last_index != -1);
+                true
+            } else if KEEP_OUTERMOST_FRAMES && MAX_BACKTRACE_SIZE - initial_size >= 2 {
+                // Keep the frame the exception was raised in, and make room
+                // by dropping the innermost frame of this exception after it.
+                EXCEPTION_BUFFER.backtrace.copy_within(initial_size + 2.., initial_size + 1);
+                true
+            } else {
+                false
+            };
+            if recorded {
+                let backtrace_size = EXCEPTION_BUFFER.backtrace_size;
+                EXCEPTION_BUFFER.backtrace[backtrace_size - 1] = (ip, fp, 0);
                 let sp_info = &mut EXCEPTION_BUFFER.stack_pointers[last_index as usize];
                 sp_info.stack_pointer = fp;
-                sp_info.current_backtrace_size = backtrace_size + 1;
+                sp_info.current_backtrace_size = backtrace_size;
+                PREVIOUS_FRAME = Some(backtrace_size - 1);
+            } else {
+                PREVIOUS_FRAME = None;
             }
+        } else {
+            PREVIOUS_FRAME = None;
         }
         if actions as u32 & uw::_UA_END_OF_STACK as u32 != 0 {
             uncaught_exception()
@@ -329,7 +370,7 @@ extern fn stop_fn(_version: c_int,
 }
 
 // Must be kept in sync with `artiq.compiler.embedding`
-static EXCEPTION_ID_LOOKUP: [(&str, u32); 23] = [
+static EXCEPTION_ID_LOOKUP: [(&str, u32); 28] = [
     ("RTIOUnderflow", 0),
     ("RTIOOverflow", 1),
     ("RTIODestinationUnreachable", 2),
@@ -352,7 +393,12 @@ static EXCEPTION_ID_LOOKUP: [(&str, u32); 23] = [
     ("ZeroDivisionError", 19),
     ("LinAlgError", 20),
     ("UnwrapNoneError", 21),
-    ("CXPError", 22)
+    ("CXPError", 22),
+    ("WatchdogTimeout", 23),
+    ("RPCError", 24),
+    ("CXPTimeout", 25),
+    ("RecursionError", 26),
+    ("RTIOError", 27)
 ];
 
 pub fn get_exception_id(name: &str) -> u32 {
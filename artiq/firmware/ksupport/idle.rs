@@ -0,0 +1,56 @@
+//! Optional idling of the kernel CPU in the mailbox waits that are expected
+//! to be long, such as awaiting a subkernel.
+//!
+//! Once such a wait has polled the mailbox a set number of times, it stops
+//! the CPU with `wfi` between polls. The comms CPU writing to the mailbox
+//! raises the mailbox interrupt of the kernel CPU, which wakes it up; the
+//! interrupt is never taken, as interrupts stay globally disabled. Waits
+//! stay tight while the watchdog is armed, as it is only checked by polls.
+
+#[cfg(kernel_has_mailbox_irq)]
+use board_misoc::irq;
+
+// Kernel CPU interrupt line of the mailbox, see `artiq.gateware.amp.soc`.
+#[cfg(kernel_has_mailbox_irq)]
+const MAILBOX_IRQ: u32 = 0;
+
+// 0 keeps all the waits tight.
+static mut SPINS: u64 = 0;
+
+pub fn reset() {
+    unsafe { SPINS = 0 }
+}
+
+/// Makes the waits that allow it idle after polling the mailbox `spins`
+/// times. A non-positive count keeps them tight. Does nothing on gateware
+/// without the mailbox interrupt.
+pub extern fn set(spins: i32) {
+    unsafe {
+        SPINS = if spins > 0 { spins as u64 } else { 0 };
+        #[cfg(kernel_has_mailbox_irq)]
+        {
+            if SPINS > 0 {
+                irq::enable(MAILBOX_IRQ);
+                riscv::register::mie::set_mext();
+            }
+        }
+    }
+}
+
+/// Called by a wait that allows idling after each fruitless poll of the
+/// mailbox, with the number of polls so far.
+#[cfg(kernel_has_mailbox_irq)]
+#[inline(always)]
+pub fn poll(spins: u64) {
+    unsafe {
+        if SPINS > 0 && spins >= SPINS && !::watchdog::armed() {
+            // a write since the last poll leaves the interrupt pending,
+            // so this returns at once
+            riscv::asm::wfi()
+        }
+    }
+}
+
+#[cfg(not(kernel_has_mailbox_irq))]
+#[inline(always)]
+pub fn poll(_spins: u64) {}
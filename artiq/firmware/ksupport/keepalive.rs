@@ -0,0 +1,75 @@
+//! Opt-in keepalive for the host connection.
+//!
+//! Once armed, the kernel asks the comms CPU whether the host connection is
+//! still open whenever it reaches a poll point (RPCs and `rtio_wait_until`)
+//! and the interval has elapsed. The ping is a kernel message handled by the
+//! comms CPU, not an RPC, so it never reaches the host nor reorders RPCs.
+//!
+//! If the comms CPU does not answer within `TIMEOUT_INTERVALS` intervals,
+//! `RPCError` is raised. The ping is then still outstanding, and the next
+//! message to the comms CPU, e.g. reporting the exception, first waits for
+//! its reply.
+
+use kernel_proto::*;
+use rtio;
+
+// The comms CPU may be busy for longer than a short interval without the
+// host connection being lost.
+const TIMEOUT_INTERVALS: i64 = 4;
+
+static mut INTERVAL: i64 = 0;
+static mut TIMEOUT: i64 = 0;
+static mut NEXT_PING: i64 = 0;
+// The ping may be abandoned before the comms CPU reads it, so it must not
+// live on the stack.
+static mut REQUEST: Message<'static> = KeepaliveRequest;
+
+pub fn reset() {
+    unsafe {
+        INTERVAL = 0;
+        TIMEOUT = 0;
+        NEXT_PING = 0;
+    }
+}
+
+/// Pings the comms CPU every `interval_mu` at most. A non-positive interval
+/// disarms the keepalive.
+pub extern fn set(interval_mu: i64) {
+    unsafe {
+        if interval_mu > 0 {
+            INTERVAL = interval_mu;
+            TIMEOUT = interval_mu.saturating_mul(TIMEOUT_INTERVALS);
+            NEXT_PING = rtio::get_counter() + interval_mu;
+        } else {
+            reset();
+        }
+    }
+}
+
+fn ping() {
+    let deadline = rtio::get_counter().saturating_add(unsafe { TIMEOUT });
+    let dead = || {
+        reset();
+        raise!("RPCError", "host connection is not responding (no keepalive reply)")
+    };
+
+    if !::send_timeout(unsafe { &REQUEST }, deadline) { dead() }
+    let alive = ::recv_timeout(deadline, |reply| match reply {
+        &KeepaliveReply { alive } => alive,
+        _ => false
+    }).unwrap_or_else(|| { dead(); false });
+    if !alive {
+        reset();
+        raise!("RPCError", "host connection was lost")
+    }
+}
+
+#[inline(always)]
+pub fn poll() {
+    unsafe {
+        if INTERVAL > 0 && rtio::get_counter() >= NEXT_PING {
+            ping();
+            NEXT_PING = rtio::get_counter() + INTERVAL;
+        }
+    }
+}
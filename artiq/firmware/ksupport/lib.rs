@@ -6,6 +6,7 @@ extern crate byteorder;
 extern crate libc;
 extern crate unwind;
 extern crate cslice;
+extern crate crc;
 
 extern crate eh;
 extern crate io;
@@ -16,7 +17,9 @@ extern crate proto_artiq;
 extern crate riscv;
 
 use core::{mem, ptr, slice, str, convert::TryFrom};
-use cslice::CSlice;
+use core::alloc::{GlobalAlloc, Layout};
+use cslice::{CSlice, CMutSlice};
+use crc::crc32;
 use io::Cursor;
 use dyld::Library;
 use board_artiq::{mailbox, rpc_queue};
@@ -26,16 +29,105 @@ use board_misoc::csr;
 use riscv::register::{mcause, mepc, mtval};
 
 fn send(request: &Message) {
+    send_wait(request, false, NO_DEADLINE);
+}
+
+/// Same as `send`, for requests the comms CPU may take long to handle,
+/// during which the kernel CPU may idle (see `idle`).
+fn send_idle(request: &Message) {
+    send_wait(request, true, NO_DEADLINE);
+}
+
+/// Sends `requests` in order with a single handshake. They must be requests
+/// the comms CPU only acknowledges, i.e. logs and DMA record appends.
+fn send_batch(requests: &[Message]) {
+    send(&Batch(requests))
+}
+
+/// Same as `send`, for a request the comms CPU replies to, but returns false
+/// if the comms CPU has not acknowledged it after `deadline_mu` on the RTIO
+/// counter, e.g. because it is unresponsive. The request is then still posted
+/// and may be read later, so it must outlive the kernel, i.e. not be on the
+/// stack; the next `send` first waits for its reply (see `drain_reply`).
+fn send_timeout(request: &Message, deadline_mu: i64) -> bool {
+    send_wait(request, false, deadline_mu)
+}
+
+const NO_DEADLINE: i64 = i64::max_value();
+
+// Set when the kernel gave up on the reply to a request it posted.
+static mut REPLY_PENDING: bool = false;
+
+/// Waits for the late reply to a request the kernel gave up on, and
+/// acknowledges it, so that the comms CPU does not write it over a later
+/// request, nor is it taken for the reply to one.
+#[inline(never)]
+fn drain_reply() {
+    unsafe { REPLY_PENDING = false }
+    recv(|_| ())
+}
+
+#[inline(always)]
+fn send_wait(request: &Message, idle: bool, deadline_mu: i64) -> bool {
+    if unsafe { REPLY_PENDING } { drain_reply() }
     unsafe { mailbox::send(request as *const _ as usize) }
-    while !mailbox::acknowledged() {}
+    let mut spins = 0;
+    while !mailbox::acknowledged() {
+        if deadline_mu != NO_DEADLINE && rtio::get_counter() > deadline_mu {
+            unsafe { REPLY_PENDING = true }
+            return false
+        }
+        spins += 1;
+        if idle { idle::poll(spins) }
+    }
+    perf::record_mailbox_spins(spins);
+    true
 }
 
 fn recv<R, F: FnOnce(&Message) -> R>(f: F) -> R {
-    let mut msg_ptr = 0;
-    while msg_ptr == 0 { msg_ptr = mailbox::receive(); }
+    recv_wait(false, NO_DEADLINE, f).unwrap()
+}
+
+/// Same as `recv`, for replies that may take long to come, during which
+/// the kernel CPU may idle (see `idle`).
+fn recv_idle<R, F: FnOnce(&Message) -> R>(f: F) -> R {
+    recv_wait(true, NO_DEADLINE, f).unwrap()
+}
+
+/// Same as `recv`, but returns `None` if no message has arrived after
+/// `deadline_mu` on the RTIO counter, e.g. because the comms CPU is
+/// unresponsive. The reply may still arrive later; the next `send` then
+/// first waits for it (see `drain_reply`).
+fn recv_timeout<R, F: FnOnce(&Message) -> R>(deadline_mu: i64, f: F) -> Option<R> {
+    recv_wait(false, deadline_mu, f)
+}
+
+#[inline(always)]
+fn recv_wait<R, F: FnOnce(&Message) -> R>(idle: bool, deadline_mu: i64, f: F) -> Option<R> {
+    let msg_ptr = perf::timed(perf::Phase::Recv, || {
+        let mut msg_ptr = 0;
+        let mut spins = 0;
+        while msg_ptr == 0 {
+            msg_ptr = mailbox::receive();
+            if msg_ptr == 0 {
+                watchdog::check();
+                if deadline_mu != NO_DEADLINE && rtio::get_counter() > deadline_mu {
+                    unsafe { REPLY_PENDING = true }
+                    break
+                }
+                spins += 1;
+                if idle { idle::poll(spins) }
+            }
+        }
+        perf::record_mailbox_spins(spins);
+        msg_ptr
+    });
+    if msg_ptr == 0 {
+        return None
+    }
     let result = f(unsafe { &*(msg_ptr as *const Message) });
     mailbox::acknowledge();
-    result
+    Some(result)
 }
 
 fn try_recv<F: FnOnce(&Message)>(f: F) {
@@ -46,6 +138,8 @@ fn try_recv<F: FnOnce(&Message)>(f: F) {
     }
 }
 
+// With a deadline, evaluates to `None` if no reply arrived in time
+// (see `recv_timeout`).
 macro_rules! recv {
     ($p:pat => $e:expr) => {
         recv(move |request| {
@@ -56,29 +150,50 @@ macro_rules! recv {
                 loop {}
             }
         })
+    };
+    ($deadline_mu:expr; $p:pat => $e:expr) => {
+        recv_timeout($deadline_mu, move |request| {
+            if let $p = request {
+                $e
+            } else {
+                send(&Log(format_args!("unexpected reply: {:?}\n", request)));
+                loop {}
+            }
+        })
     }
 }
 
 #[no_mangle] // https://github.com/rust-lang/rust/issues/{38281,51647}
 #[panic_handler]
 pub fn panic_fmt(info: &core::panic::PanicInfo) -> ! {
-    if let Some(location) = info.location() {
-        send(&Log(format_args!("panic at {}:{}:{}",
-                               location.file(), location.line(), location.column())));
-    } else {
-        send(&Log(format_args!("panic at unknown location")));
-    }
-    if let Some(message) = info.message() {
-        send(&Log(format_args!(": {}\n", message)));
-    } else {
-        send(&Log(format_args!("\n")));
-    }
-    send(&RunAborted);
+    let (file, line, column) = match info.location() {
+        Some(location) => (location.file(), location.line(), location.column()),
+        None => ("<unknown>", 0, 0)
+    };
+    // the comms CPU stops the kernel CPU once it has the panic
+    log_buffer::flush();
+    send(&RunPanic {
+        file:    file,
+        line:    line,
+        column:  column,
+        message: info.message().cloned()
+    });
     loop {}
 }
 
+// Sends a message to the core log if `level` is enabled, checking the level
+// before anything is formatted. Send `Log` directly for messages that must
+// always get through.
+macro_rules! log_at {
+    ($level:expr, $($arg:tt)*) => ({
+        if $crate::log_enabled($level) {
+            $crate::send(&$crate::kernel_proto::Log(format_args!($($arg)*)))
+        }
+    });
+}
+
 macro_rules! print {
-    ($($arg:tt)*) => ($crate::send(&$crate::kernel_proto::Log(format_args!($($arg)*))));
+    ($($arg:tt)*) => (log_at!($crate::LOG_LEVEL_INFO, $($arg)*));
 }
 
 macro_rules! println {
@@ -108,24 +223,137 @@ macro_rules! raise {
     });
 }
 
+// Like `assert!`, but raises a catchable exception instead of aborting the kernel.
+// Use it for preconditions of the kernel API; keep `assert!` for firmware bugs.
+macro_rules! ensure {
+    ($cond:expr, $name:expr, $message:expr, $param0:expr, $param1:expr, $param2:expr) => ({
+        if !$cond {
+            raise!($name, $message, $param0, $param1, $param2)
+        }
+    });
+    ($cond:expr, $name:expr, $message:expr) => ({
+        ensure!($cond, $name, $message, 0, 0, 0)
+    });
+}
+
 mod eh_artiq;
 mod api;
 mod rtio;
 mod nrt_bus;
 mod cxp;
 mod mem;
+mod watchdog;
+mod keepalive;
+mod log_buffer;
+mod log_limit;
+mod stack_limit;
+mod idle;
+mod perf;
 
 static mut LIBRARY: Option<Library<'static>> = None;
 
+#[repr(C)]
+struct ImageSizes {
+    code_bytes: i32,
+    bss_bytes: i32,
+    total_payload_bytes: i32,
+}
+
+/// Returns the sizes of the running kernel: its code and initialized data,
+/// up to `__bss_start`, its zero-initialized data, up to `_end`, and the
+/// payload memory from its start to the end of kernel memory, which the
+/// two must fit in together with the stack.
+extern fn kernel_image_sizes() -> ImageSizes {
+    unsafe {
+        let library = LIBRARY.as_ref().unwrap();
+        let start = library.image_start() as usize;
+        let __bss_start = library.lookup(b"__bss_start").unwrap() as usize;
+        let _end = library.lookup(b"_end").unwrap() as usize;
+        ImageSizes {
+            code_bytes: (__bss_start - start) as i32,
+            bss_bytes: (_end - __bss_start) as i32,
+            total_payload_bytes: (KERNELCPU_LAST_ADDRESS - start) as i32,
+        }
+    }
+}
+
+extern {
+    static _ftext: u8;
+    static _etext: u8;
+}
+
+/// Points the calls of the running kernel to the ksupport function `name` at
+/// `target` instead, e.g. to divert RTIO output while recording DMA. This is
+/// for ksupport itself and is not exported to kernels.
+///
+/// Returns false, leaving the kernel untouched, if `name` is not a function
+/// ksupport provides to kernels, or if `target` is not within the code of
+/// ksupport or of the running kernel.
+unsafe fn rebind_symbol(name: &[u8], target: *const ()) -> bool {
+    let library = LIBRARY.as_ref().unwrap();
+    let target = target as usize;
+    let in_ksupport = &_ftext as *const u8 as usize <= target &&
+                      target < &_etext as *const u8 as usize;
+    let in_kernel = library.image_start() as usize <= target &&
+                    target < library.lookup(b"_end").unwrap() as usize;
+    if api::resolve(name).is_none() || !(in_ksupport || in_kernel) {
+        return false
+    }
+    if library.rebind(name, target as u32).is_err() {
+        return false
+    }
+    board_misoc::cache::flush_cpu_icache();
+    true
+}
+
+static mut SELF_DESTINATION: u8 = 0;
+
+// Log levels are encoded as in `log::LevelFilter`, from 1 (error) to 5 (trace).
+const LOG_LEVEL_INFO: u8 = 3;
+static mut LOG_LEVEL: u8 = 5;
+
+fn log_enabled(level: u8) -> bool {
+    level <= unsafe { LOG_LEVEL }
+}
+
+/// Sets the most verbose level of the core log messages the kernel sends,
+/// from 0 (none) to 5 (trace). The comms CPU still applies its own level,
+/// with which the kernel starts.
+extern fn set_log_level(level: u8) {
+    ensure!(level <= 5, "ValueError",
+        "log level {0} is not between 0 and 5", level as i64, 0, 0);
+    unsafe { LOG_LEVEL = level }
+}
+
 #[no_mangle]
 pub extern fn send_to_core_log(text: CSlice<u8>) {
+    if !log_enabled(LOG_LEVEL_INFO) {
+        return
+    }
+    let suppressed = match log_limit::admit() {
+        Some(suppressed) => suppressed,
+        None => return
+    };
     match str::from_utf8(text.as_ref()) {
-        Ok(s) => send(&LogSlice(s)),
+        Ok(s) => log_buffer::write(s),
         Err(e) => {
-            send(&LogSlice(str::from_utf8(&text.as_ref()[..e.valid_up_to()]).unwrap()));
-            send(&LogSlice("(invalid utf-8)\n"));
+            log_buffer::write(str::from_utf8(&text.as_ref()[..e.valid_up_to()]).unwrap());
+            log_buffer::write("(invalid utf-8)\n");
         }
     }
+    if suppressed > 0 {
+        use core::fmt::Write;
+        let _ = write!(log_buffer::Writer, "({} messages suppressed)\n", suppressed);
+    }
+}
+
+/// Logs `data` verbatim, without decoding it as UTF-8. The comms CPU logs
+/// it in hex with the `kernel::binary` target.
+extern fn send_binary_to_core_log(data: CSlice<u8>) {
+    if !log_enabled(LOG_LEVEL_INFO) || log_limit::admit().is_none() {
+        return
+    }
+    log_buffer::flush_then(LogBinary(data.as_ref()))
 }
 
 #[no_mangle]
@@ -133,10 +361,36 @@ pub extern fn send_to_rtio_log(text: CSlice<u8>) {
     rtio::log(text.as_ref())
 }
 
+/// Raises `RPCError` if `tag` is not a well-formed RPC tag. With the
+/// `rpc_tag_check` feature, every RPC tag is checked before it is sent.
+#[cfg(feature = "rpc_tag_check")]
+fn rpc_validate_tag(tag: &CSlice<u8>) {
+    if !rpc_proto::validate_tag(tag.as_ref()) {
+        raise!("RPCError", "malformed RPC tag")
+    }
+}
+
+static mut RPC_FORMAT: u8 = rpc_proto::FORMAT_COMPACT;
+
+/// Selects how the arguments of the following RPCs are serialized for the
+/// host: `rpc_proto::FORMAT_COMPACT` (the default), or `FORMAT_DEBUG`, which
+/// adds the size of each argument so that the host can point out the one it
+/// cannot decode. Raises `ValueError` for other formats.
+extern "C-unwind" fn set_rpc_format(format: i32) {
+    ensure!(format == rpc_proto::FORMAT_COMPACT as i32 ||
+            format == rpc_proto::FORMAT_DEBUG as i32, "ValueError",
+        "unknown RPC format {0}", format as i64, 0, 0);
+    unsafe { RPC_FORMAT = format as u8 }
+}
+
 extern fn rpc_send(service: u32, tag: &CSlice<u8>, data: *const *const ()) {
-    while !rpc_queue::empty() {}
+    #[cfg(feature = "rpc_tag_check")]
+    rpc_validate_tag(tag);
+    keepalive::poll();
+    while !rpc_queue::empty() { watchdog::check() }
     send(&RpcSend {
         async:   false,
+        format:  unsafe { RPC_FORMAT },
         service: service,
         tag:     tag.as_ref(),
         data:    data
@@ -144,20 +398,30 @@ extern fn rpc_send(service: u32, tag: &CSlice<u8>, data: *const *const ()) {
 }
 
 extern fn rpc_send_async(service: u32, tag: &CSlice<u8>, data: *const *const ()) {
-    while rpc_queue::full() {}
+    #[cfg(feature = "rpc_tag_check")]
+    rpc_validate_tag(tag);
+    keepalive::poll();
+    while rpc_queue::full() { watchdog::check() }
+    let format = unsafe { RPC_FORMAT };
     rpc_queue::enqueue(|mut slice| {
+        slice[4] = format;
         let length = {
-            let mut writer = Cursor::new(&mut slice[4..]);
-            rpc_proto::send_args(&mut writer, service, tag.as_ref(), data, true)?;
+            let mut writer = Cursor::new(&mut slice[5..]);
+            match format {
+                rpc_proto::FORMAT_DEBUG =>
+                    rpc_proto::send_args_debug(&mut writer, service, tag.as_ref(), data)?,
+                _ => rpc_proto::send_args(&mut writer, service, tag.as_ref(), data, true)?
+            }
             writer.position()
         };
         io::ProtoWrite::write_u32(&mut slice, length as u32)
     }).unwrap_or_else(|err| {
         assert!(err == io::Error::UnexpectedEnd);
 
-        while !rpc_queue::empty() {}
+        while !rpc_queue::empty() { watchdog::check() }
         send(&RpcSend {
             async:   true,
+            format:  format,
             service: service,
             tag:     tag.as_ref(),
             data:    data
@@ -165,6 +429,20 @@ extern fn rpc_send_async(service: u32, tag: &CSlice<u8>, data: *const *const ())
     })
 }
 
+/// Raises in the kernel an exception raised by an RPC on the host.
+fn raise_rpc_exception(exception: &eh_artiq::Exception) -> ! {
+    unsafe {
+        eh_artiq::raise(&eh_artiq::Exception {
+            id:       exception.id,
+            file:     exception.file,
+            line:     exception.line,
+            column:   exception.column,
+            function: exception.function,
+            message:  exception.message,
+            param:    exception.param
+        })
+    }
+}
 
 /// Receives the result from an RPC call into the given memory buffer.
 ///
@@ -185,25 +463,58 @@ extern "C-unwind" fn rpc_recv(slot: *mut ()) -> usize {
     recv!(&RpcRecvReply(ref result) => {
         match result {
             &Ok(alloc_size) => alloc_size,
-            &Err(ref exception) =>
-            unsafe {
-                eh_artiq::raise(&eh_artiq::Exception {
-                    id:       exception.id,
-                    file:     exception.file,
-                    line:     exception.line,
-                    column:   exception.column,
-                    function: exception.function,
-                    message:  exception.message,
-                    param:    exception.param
-                })
-            }
+            &Err(ref exception) => raise_rpc_exception(exception)
         }
     })
 }
 
+/// Blocks until the comms CPU has processed all the async RPCs posted so far.
+/// The comms CPU empties the RPC queue before reading the mailbox, so the
+/// acknowledgement of `RpcFlush` implies the queue has been drained.
+extern fn rpc_drain() {
+    send(&RpcFlush);
+}
+
+/// Sends a value, given by `data` and `tag` like the arguments of an RPC, to
+/// the host subscribers of `channel`, which are called as soon as it arrives.
+/// There is no reply to wait for, but the async RPCs issued before are sent
+/// first, so that the host sees the value after them.
+extern fn publish_result(channel: u32, data: *const *const (), tag: &CSlice<u8>) {
+    #[cfg(feature = "rpc_tag_check")]
+    rpc_validate_tag(tag);
+    keepalive::poll();
+    while !rpc_queue::empty() { watchdog::check() }
+    send(&PublishResult {
+        channel: channel,
+        tag:     tag.as_ref(),
+        data:    data
+    })
+}
+
+static mut PARTIAL_RESULT: (*const u8, usize) = (ptr::null(), 0);
+
+/// Registers `data` to be sent to the host if the kernel terminates with an
+/// uncaught exception, so that the results computed before a late failure
+/// are not lost. It is read when the kernel terminates, so it must remain
+/// allocated until then, e.g. by being created in the kernel entry point.
+extern fn set_partial_result(data: CSlice<u8>) {
+    unsafe { PARTIAL_RESULT = (data.as_ptr(), data.len()) }
+}
+
+fn send_partial_result() {
+    unsafe {
+        let (data, len) = mem::replace(&mut PARTIAL_RESULT, (ptr::null(), 0));
+        if !data.is_null() {
+            send(&PartialResult(slice::from_raw_parts(data, len)));
+        }
+    }
+}
+
 fn terminate(exceptions: &'static [Option<eh_artiq::Exception<'static>>],
              stack_pointers: &'static [eh_artiq::StackPointerBacktrace],
-             backtrace: &mut [(usize, usize)]) -> ! {
+             backtrace: &mut [(usize, usize, usize)]) -> ! {
+    log_buffer::flush();
+    send_partial_result();
     send(&RunException {
         exceptions,
         stack_pointers,
@@ -221,15 +532,53 @@ extern fn cache_get<'a>(key: CSlice<u8>) -> *const CSlice<'a, i32> {
     })
 }
 
-extern "C-unwind" fn cache_put(key: CSlice<u8>, list: &CSlice<i32>) {
+extern fn cache_try_put(key: CSlice<u8>, list: &CSlice<i32>) -> bool {
     send(&CachePutRequest {
         key:   str::from_utf8(key.as_ref()).unwrap(),
         value: list.as_ref()
     });
     recv!(&CachePutReply { succeeded } => {
-        if !succeeded {
-            raise!("CacheError", "cannot put into a busy cache row")
-        }
+        succeeded
+    })
+}
+
+extern "C-unwind" fn cache_put(key: CSlice<u8>, list: &CSlice<i32>) {
+    if !cache_try_put(key, list) {
+        raise!("CacheError", "cannot put into a busy cache row")
+    }
+}
+
+extern "C-unwind" fn cache_cas(key: CSlice<u8>, expected: i32, new: i32) -> bool {
+    send(&CacheCasRequest {
+        key:      str::from_utf8(key.as_ref()).unwrap(),
+        expected: expected,
+        new:      new
+    });
+    let (succeeded, swapped) = recv!(&CacheCasReply { succeeded, swapped } => (succeeded, swapped));
+    if !succeeded {
+        raise!("CacheError", "cannot swap in a busy cache row")
+    }
+    swapped
+}
+
+// Byte values are kept apart from the lists of integers, under their own keys.
+extern fn cache_get_bytes<'a>(key: CSlice<u8>) -> CSlice<'a, u8> {
+    use cslice::AsCSlice;
+    send(&CacheGetBytesRequest {
+        key:   str::from_utf8(key.as_ref()).unwrap()
+    });
+    recv!(&CacheGetBytesReply { value } => {
+        unsafe { (*value).as_ref().as_c_slice() }
+    })
+}
+
+extern "C-unwind" fn cache_put_bytes(key: CSlice<u8>, value: CSlice<u8>) {
+    send(&CachePutBytesRequest {
+        key:   str::from_utf8(key.as_ref()).unwrap(),
+        value: value.as_ref()
+    });
+    recv!(&CachePutReply { succeeded } => if !succeeded {
+        raise!("CacheError", "cannot put into a busy cache row")
     })
 }
 
@@ -238,23 +587,65 @@ const DMA_BUFFER_SIZE: usize = 64 * 1024;
 struct DmaRecorder {
     active:   bool,
     data_len: usize,
+    // Bytes already sent to the comms CPU in this recording.
+    flushed:  usize,
     buffer:   [u8; DMA_BUFFER_SIZE],
+    // Heap buffer from dma_record_start_reserve, used instead of `buffer` if present.
+    reserved: Option<&'static mut [u8]>,
 }
 
 static mut DMA_RECORDER: DmaRecorder = DmaRecorder {
     active:   false,
     data_len: 0,
+    flushed:  0,
     buffer:   [0; DMA_BUFFER_SIZE],
+    reserved: None,
 };
 
+unsafe fn dma_record_buffer() -> &'static mut [u8] {
+    match DMA_RECORDER.reserved {
+        Some(ref mut buffer) => buffer,
+        None => &mut DMA_RECORDER.buffer
+    }
+}
+
 fn dma_record_flush() {
     unsafe {
-        send(&DmaRecordAppend(&DMA_RECORDER.buffer[..DMA_RECORDER.data_len]));
+        send(&DmaRecordAppend(&dma_record_buffer()[..DMA_RECORDER.data_len]));
+        DMA_RECORDER.flushed += DMA_RECORDER.data_len;
         DMA_RECORDER.data_len = 0;
     }
 }
 
+/// Sends the events recorded so far to the comms CPU, if there are any, and
+/// returns the number of bytes recorded so far. The trace is the same as if
+/// the events had been sent when the buffer filled up.
+extern "C-unwind" fn dma_record_checkpoint() -> i64 {
+    unsafe {
+        if !DMA_RECORDER.active {
+            raise!("DMAError", "DMA is not recording")
+        }
+        if DMA_RECORDER.data_len > 0 {
+            dma_record_flush();
+        }
+        DMA_RECORDER.flushed as i64
+    }
+}
+
 extern "C-unwind" fn dma_record_start(name: CSlice<u8>) {
+    dma_record_begin(name, 0)
+}
+
+/// Same as `dma_record_start`, but first reserves a buffer of at least
+/// `expected_bytes`, so that the recording is not flushed to the comms CPU
+/// until it outgrows the estimate.
+extern "C-unwind" fn dma_record_start_reserve(name: CSlice<u8>, expected_bytes: i32) {
+    ensure!(expected_bytes >= 0, "DMAError",
+        "cannot reserve a negative DMA buffer size ({0} bytes)", expected_bytes as i64, 0, 0);
+    dma_record_begin(name, expected_bytes as usize)
+}
+
+fn dma_record_begin(name: CSlice<u8>, reserve: usize) {
     let name = str::from_utf8(name.as_ref()).unwrap();
 
     unsafe {
@@ -262,32 +653,47 @@ extern "C-unwind" fn dma_record_start(name: CSlice<u8>) {
             raise!("DMAError", "DMA is already recording")
         }
 
-        let library = LIBRARY.as_ref().unwrap();
-        library.rebind(b"rtio_output",
-                       dma_record_output as *const () as u32).unwrap();
-        library.rebind(b"rtio_output_wide",
-                       dma_record_output_wide as *const () as u32).unwrap();
-        board_misoc::cache::flush_cpu_icache();
+        // Reserved last, so that the checks above cannot leak the buffer.
+        if reserve > DMA_BUFFER_SIZE {
+            let layout = Layout::from_size_align_unchecked(reserve, mem::align_of::<u64>());
+            let buffer = ALLOC.alloc(layout);
+            ensure!(!buffer.is_null(), "DMAError",
+                "cannot reserve {0} bytes for DMA recording", reserve as i64, 0, 0);
+            DMA_RECORDER.reserved = Some(slice::from_raw_parts_mut(buffer, reserve));
+        }
+
+        if !(rebind_symbol(b"rtio_output", dma_record_output as *const ()) &&
+             rebind_symbol(b"rtio_output_wide", dma_record_output_wide as *const ())) {
+            dma_record_release();
+            raise!("DMAError", "cannot redirect RTIO output for recording")
+        }
 
         DMA_RECORDER.active = true;
+        DMA_RECORDER.flushed = 0;
         send(&DmaRecordStart(name));
     }
 }
 
+unsafe fn dma_record_release() {
+    if let Some(buffer) = DMA_RECORDER.reserved.take() {
+        ALLOC.dealloc(buffer.as_mut_ptr(),
+            Layout::from_size_align_unchecked(buffer.len(), mem::align_of::<u64>()));
+    }
+}
+
 extern "C-unwind" fn dma_record_stop(duration: i64, enable_ddma: bool) {
     unsafe {
         dma_record_flush();
+        dma_record_release();
 
         if !DMA_RECORDER.active {
             raise!("DMAError", "DMA is not recording")
         }
 
-        let library = LIBRARY.as_ref().unwrap();
-        library.rebind(b"rtio_output",
-                       rtio::output as *const () as u32).unwrap();
-        library.rebind(b"rtio_output_wide",
-                       rtio::output_wide as *const () as u32).unwrap();
-        board_misoc::cache::flush_cpu_icache();
+        if !(rebind_symbol(b"rtio_output", rtio::output as *const ()) &&
+             rebind_symbol(b"rtio_output_wide", rtio::output_wide as *const ())) {
+            raise!("DMAError", "cannot restore RTIO output after recording")
+        }
 
         DMA_RECORDER.active = false;
         send(&DmaRecordStop {
@@ -297,22 +703,23 @@ extern "C-unwind" fn dma_record_stop(duration: i64, enable_ddma: bool) {
     }
 }
 
+// See gateware/rtio/dma.py.
+const DMA_HEADER_LENGTH: usize = /*length*/1 + /*channel*/3 + /*timestamp*/8 + /*address*/1;
+
 #[inline(always)]
 unsafe fn dma_record_output_prepare(timestamp: i64, target: i32,
                                     words: usize) -> &'static mut [u8] {
-    // See gateware/rtio/dma.py.
-    const HEADER_LENGTH: usize = /*length*/1 + /*channel*/3 + /*timestamp*/8 + /*address*/1;
-    let length = HEADER_LENGTH + /*data*/words * 4;
+    let length = DMA_HEADER_LENGTH + /*data*/words * 4;
 
-    if DMA_RECORDER.buffer.len() - DMA_RECORDER.data_len < length {
+    if dma_record_buffer().len() - DMA_RECORDER.data_len < length {
         dma_record_flush()
     }
 
-    let record = &mut DMA_RECORDER.buffer[DMA_RECORDER.data_len..
+    let record = &mut dma_record_buffer()[DMA_RECORDER.data_len..
                                           DMA_RECORDER.data_len + length];
     DMA_RECORDER.data_len += length;
 
-    let (header, data) = record.split_at_mut(HEADER_LENGTH);
+    let (header, data) = record.split_at_mut(DMA_HEADER_LENGTH);
 
     header.copy_from_slice(&[
         (length    >>  0) as u8,
@@ -347,7 +754,10 @@ extern fn dma_record_output(target: i32, word: i32) {
 }
 
 extern fn dma_record_output_wide(target: i32, words: &CSlice<i32>) {
-    assert!(words.len() <= 16); // enforce the hardware limit
+    // enforce the hardware limit
+    ensure!(words.len() <= 16, "DMAError",
+        "cannot record an output of {0} words, at most 16 are supported",
+        words.len() as i64, 0, 0);
 
     unsafe {
         let timestamp = ((csr::rtio::now_hi_read() as i64) << 32) | (csr::rtio::now_lo_read() as i64);
@@ -380,28 +790,181 @@ struct DmaTrace {
 extern "C-unwind" fn dma_retrieve(name: CSlice<u8>) -> DmaTrace {
     let name = str::from_utf8(name.as_ref()).unwrap();
 
+    let (trace, duration, uses_ddma) = dma_lookup(name);
+    DmaTrace {
+        address:  trace.as_ptr() as i32,
+        duration: duration as i64,
+        uses_ddma: uses_ddma,
+    }
+}
+
+/// Looks up a recorded trace, returning its bytes, duration and whether it
+/// uses DDMA. The bytes live in the comms CPU memory and stay valid until the
+/// trace is erased or replaced.
+fn dma_lookup(name: &str) -> (&'static [u8], u64, bool) {
     send(&DmaRetrieveRequest { name: name });
     recv!(&DmaRetrieveReply { trace, duration, uses_ddma } => {
-        match trace {
-            Some(bytes) => Ok(DmaTrace {
-                address:  bytes.as_ptr() as i32,
-                duration: duration as i64,
-                uses_ddma: uses_ddma,
-            }),
-            None => Err(())
-        }
-    }).unwrap_or_else(|()| {
+        trace.map(|bytes| unsafe {
+            (slice::from_raw_parts(bytes.as_ptr(), bytes.len()), duration, uses_ddma)
+        })
+    }).unwrap_or_else(|| {
         println!("DMA trace called {:?} not found", name);
         raise!("DMAError",
             "DMA trace not found");
     })
 }
 
-#[cfg(kernel_has_rtio_dma)]
-extern "C-unwind" fn dma_playback(timestamp: i64, ptr: i32, _uses_ddma: bool) {
-    assert!(ptr % 64 == 0);
+/// Calls `f` with each event of a recorded trace and its timestamp, in the
+/// layout written by `dma_record_output_prepare`.
+fn dma_for_each_event<F: FnMut(&[u8], i64)>(trace: &[u8], mut f: F) {
+    let mut ptr = 0;
+    while ptr < trace.len() && trace[ptr] != 0 {
+        let length = trace[ptr] as usize;
+        ensure!(length >= DMA_HEADER_LENGTH && (length - DMA_HEADER_LENGTH) % 4 == 0 &&
+                ptr + length <= trace.len(), "DMAError",
+            "malformed DMA trace at byte {0}", ptr as i64, 0, 0);
+        let event = &trace[ptr..ptr + length];
+        let timestamp = event[4..12].iter().rev()
+            .fold(0i64, |timestamp, &byte| (timestamp << 8) | byte as i64);
+        f(event, timestamp);
+        ptr += length;
+    }
+}
 
+/// Records `result_name` as the traces `parts` played one after the other,
+/// the events of each part being delayed by the matching element of
+/// `timestamp_offsets`. As the DMA engine submits events in trace order, no
+/// part may start before the previous ones end.
+extern "C-unwind" fn dma_concat(result_name: CSlice<u8>, parts: &CSlice<CSlice<u8>>,
+                                timestamp_offsets: &CSlice<i64>) {
+    let result = str::from_utf8(result_name.as_ref()).unwrap();
+    let offsets = timestamp_offsets.as_ref();
+    ensure!(parts.len() == offsets.len(), "DMAError",
+        "{0} DMA traces were given with {1} timestamp offsets",
+        parts.len() as i64, offsets.len() as i64, 0);
     unsafe {
+        if DMA_RECORDER.active {
+            raise!("DMAError", "DMA is already recording")
+        }
+    }
+
+    // Validate every part first, so that an error does not leave a partial trace.
+    let mut duration = 0i64;
+    let mut end = i64::min_value();
+    for (index, (name, &offset)) in parts.as_ref().iter().zip(offsets).enumerate() {
+        let name = str::from_utf8(name.as_ref()).unwrap();
+        ensure!(name != result, "DMAError",
+            "DMA trace {0} cannot be concatenated into itself", index as i64, 0, 0);
+        let (trace, part_duration, uses_ddma) = dma_lookup(name);
+        ensure!(!uses_ddma, "DMAError",
+            "DMA trace {0} uses DDMA and cannot be concatenated", index as i64, 0, 0);
+
+        let (mut first, mut last) = (i64::max_value(), i64::min_value());
+        dma_for_each_event(trace, |_, timestamp| {
+            let timestamp = timestamp.checked_add(offset).unwrap_or_else(|| {
+                raise!("DMAError", "timestamp of DMA trace {0} overflows with offset {1}",
+                       index as i64, offset, 0)
+            });
+            first = first.min(timestamp);
+            last = last.max(timestamp);
+        });
+        ensure!(first >= end, "DMAError",
+            "DMA trace {0} starts at {1} mu, before the previous traces end at {2} mu",
+            index as i64, first, end);
+        end = end.max(last);
+        duration = duration.max(offset.saturating_add(part_duration as i64));
+    }
+
+    dma_record_start(result_name);
+    for (name, &offset) in parts.as_ref().iter().zip(offsets) {
+        let (trace, _, _) = dma_lookup(str::from_utf8(name.as_ref()).unwrap());
+        dma_for_each_event(trace, |event, timestamp| unsafe {
+            let target = (event[1] as i32) << 8 | (event[2] as i32) << 16 |
+                         (event[3] as i32) << 24 | event[12] as i32;
+            let words = (event.len() - DMA_HEADER_LENGTH) / 4;
+            dma_record_output_prepare(timestamp + offset, target, words)
+                .copy_from_slice(&event[DMA_HEADER_LENGTH..]);
+        });
+    }
+    dma_record_stop(duration, false);
+}
+
+/// Records `result_name` as the trace `a_name` followed by `b_name`, the
+/// latter starting `gap_mu` after the end of the former.
+extern "C-unwind" fn dma_concat_pair(result_name: CSlice<u8>, a_name: CSlice<u8>,
+                                     b_name: CSlice<u8>, gap_mu: i64) {
+    use cslice::AsCSlice;
+
+    let (_, a_duration, _) = dma_lookup(str::from_utf8(a_name.as_ref()).unwrap());
+    let offset = (a_duration as i64).checked_add(gap_mu).unwrap_or_else(|| {
+        raise!("DMAError", "gap of {0} mu after the first DMA trace overflows",
+               gap_mu, 0, 0)
+    });
+    dma_concat(result_name, &[a_name, b_name][..].as_c_slice(),
+               &[0, offset][..].as_c_slice());
+}
+
+/// Sends the raw bytes of a recorded DMA trace to the host. Returns false if
+/// there is no such trace, unless `raise_if_missing` is set.
+extern "C-unwind" fn dma_dump(name: CSlice<u8>, raise_if_missing: bool) -> bool {
+    let name = str::from_utf8(name.as_ref()).unwrap();
+
+    send(&DmaRetrieveRequest { name: name });
+    let found = recv!(&DmaRetrieveReply { trace, .. } => {
+        match trace {
+            Some(bytes) => {
+                send(&DmaDump { name: name, trace: bytes });
+                true
+            }
+            None => false
+        }
+    });
+    if !found && raise_if_missing {
+        println!("DMA trace called {:?} not found", name);
+        raise!("DMAError",
+            "DMA trace not found");
+    }
+    found
+}
+
+// Error bits of a DMA playback, reported by the engine or by a satellite.
+const DMA_ERROR_UNDERFLOW: u8 = 1;
+const DMA_ERROR_DESTINATION_UNREACHABLE: u8 = 2;
+
+/// Returns whether a DMA playback that failed with `error_bits` may succeed
+/// if retried. An underflow is recoverable, e.g. by playing the trace back
+/// later, while an unreachable destination is not, as the link to it is
+/// down. Unknown bits are not recoverable. Callers wanting another policy
+/// can interpret the bits themselves.
+extern fn rtio_error_is_recoverable(error_bits: i32) -> bool {
+    let recoverable = DMA_ERROR_UNDERFLOW as i32;
+    error_bits & !recoverable == 0
+}
+
+/// Raises the exception for the `error` bits of a DMA playback, if any.
+#[cfg(any(kernel_has_rtio_dma, has_rtio_dma))]
+fn dma_raise_error(error: u8, channel: i64, timestamp: i64) {
+    if error & DMA_ERROR_UNDERFLOW != 0 && !rtio::drop_late_event() {
+        raise!("RTIOUnderflow",
+            "RTIO underflow at channel {rtio_channel_info:0}, {1} mu",
+            channel, timestamp, 0);
+    }
+    if error & DMA_ERROR_DESTINATION_UNREACHABLE != 0 {
+        raise!("RTIODestinationUnreachable",
+            "RTIO destination unreachable, output, at channel {rtio_channel_info:0}, {1} mu",
+            channel, timestamp, 0);
+    }
+}
+
+/// Plays back a DMA trace and returns the time, in machine units, the
+/// engine took to submit it.
+#[cfg(kernel_has_rtio_dma)]
+fn dma_playback_measured(timestamp: i64, ptr: i32, _uses_ddma: bool) -> i64 {
+    ensure!(ptr % 64 == 0, "DMAError",
+        "DMA trace address {0} is not 64-byte aligned",
+        ptr as i64, 0, 0);
+
+    let elapsed = unsafe {
         csr::rtio_dma::base_address_write(ptr as u64);
         csr::rtio_dma::time_offset_write(timestamp as u64);
 
@@ -411,7 +974,16 @@ extern "C-unwind" fn dma_playback(timestamp: i64, ptr: i32, _uses_ddma: bool) {
         if _uses_ddma {
             send(&DmaStartRemoteRequest { id: ptr as i32, timestamp: timestamp });
         }
-        while csr::rtio_dma::enable_read() != 0 {}
+        let start = rtio::get_counter();
+        while csr::rtio_dma::enable_read() != 0 {
+            if watchdog::expired() {
+                // stop the engine and release the CRI before unwinding
+                csr::rtio_dma::enable_write(0);
+                csr::cri_con::selected_write(0);
+                watchdog::expire()
+            }
+        }
+        let elapsed = rtio::get_counter() - start;
         csr::cri_con::selected_write(0);
 
         let error = csr::rtio_dma::error_read();
@@ -419,18 +991,11 @@ extern "C-unwind" fn dma_playback(timestamp: i64, ptr: i32, _uses_ddma: bool) {
             let timestamp = csr::rtio_dma::error_timestamp_read();
             let channel = csr::rtio_dma::error_channel_read();
             csr::rtio_dma::error_write(1);
-            if error & 1 != 0 {
-                raise!("RTIOUnderflow",
-                    "RTIO underflow at channel {rtio_channel_info:0}, {1} mu",
-                    channel as i64, timestamp as i64, 0);
-            }
-            if error & 2 != 0 {
-                raise!("RTIODestinationUnreachable",
-                    "RTIO destination unreachable, output, at channel {rtio_channel_info:0}, {1} mu",
-                    channel as i64, timestamp as i64, 0);
-            }
+            dma_raise_error(error, channel as i64, timestamp as i64);
         }
-    }
+
+        elapsed
+    };
 
     #[cfg(has_drtio)]
     if _uses_ddma {
@@ -440,30 +1005,24 @@ extern "C-unwind" fn dma_playback(timestamp: i64, ptr: i32, _uses_ddma: bool) {
                 raise!("DMAError",
                     "Error running DMA on satellite device, timed out waiting for results");
             }
-            if error & 1 != 0 {
-                raise!("RTIOUnderflow",
-                    "RTIO underflow at channel {rtio_channel_info:0}, {1} mu",
-                    channel as i64, timestamp as i64, 0);
-            }
-            if error & 2 != 0 {
-                raise!("RTIODestinationUnreachable",
-                    "RTIO destination unreachable, output, at channel {rtio_channel_info:0}, {1} mu",
-                    channel as i64, timestamp as i64, 0);
-            }
+            dma_raise_error(error, channel as i64, timestamp as i64);
         });
     }
+
+    elapsed
 }
 
 #[cfg(all(not(kernel_has_rtio_dma), not(has_rtio_dma)))]
-extern "C-unwind" fn dma_playback(_timestamp: i64, _ptr: i32, _uses_ddma: bool) {
+fn dma_playback_measured(_timestamp: i64, _ptr: i32, _uses_ddma: bool) -> i64 {
     unimplemented!("not(kernel_has_rtio_dma)")
 }
 
 // for satellite (has_rtio_dma but not in kernel)
 #[cfg(all(not(kernel_has_rtio_dma), has_rtio_dma))]
-extern "C-unwind" fn dma_playback(timestamp: i64, ptr: i32, _uses_ddma: bool) {
+fn dma_playback_measured(timestamp: i64, ptr: i32, _uses_ddma: bool) -> i64 {
     // DDMA is always used on satellites, so the `uses_ddma` setting is ignored
     // StartRemoteRequest reused as "normal" start request
+    let start = rtio::get_counter();
     send(&DmaStartRemoteRequest { id: ptr as i32, timestamp: timestamp });
     // skip awaitremoterequest - it's a given
     recv!(&DmaAwaitRemoteReply { timeout, error, channel, timestamp } => {
@@ -471,19 +1030,192 @@ extern "C-unwind" fn dma_playback(timestamp: i64, ptr: i32, _uses_ddma: bool) {
             raise!("DMAError",
                 "Error running DMA on satellite device, timed out waiting for results");
         }
-        if error & 1 != 0 {
-            raise!("RTIOUnderflow",
-                "RTIO underflow at channel {rtio_channel_info:0}, {1} mu",
-                channel as i64, timestamp as i64, 0);
+        dma_raise_error(error, channel as i64, timestamp as i64);
+    });
+    rtio::get_counter() - start
+}
+
+extern "C-unwind" fn dma_playback(timestamp: i64, ptr: i32, uses_ddma: bool) {
+    dma_playback_measured(timestamp, ptr, uses_ddma);
+}
+
+/// Same as `dma_playback`, but returns how long the playback took in machine
+/// units. A value exceeding the trace duration indicates backpressure.
+extern "C-unwind" fn dma_playback_timed(timestamp: i64, ptr: i32, uses_ddma: bool) -> i64 {
+    dma_playback_measured(timestamp, ptr, uses_ddma)
+}
+
+/// Same as `dma_playback`, but shifts all the events of the trace by
+/// `extra_offset_mu`, e.g. to compensate for the latency of a device.
+extern "C-unwind" fn dma_playback_offset(timestamp: i64, ptr: i32, uses_ddma: bool, extra_offset_mu: i64) {
+    let timestamp = timestamp.checked_add(extra_offset_mu).unwrap_or_else(|| {
+        raise!("ValueError", "DMA playback offset {0} mu overflows the timestamp {1} mu",
+               extra_offset_mu, timestamp, 0)
+    });
+    dma_playback_measured(timestamp, ptr, uses_ddma);
+}
+
+const DMA_STATE_IDLE: i32 = 0;
+const DMA_STATE_RECORDING: i32 = 1;
+const DMA_STATE_PLAYING: i32 = 2;
+
+/// Returns whether DMA is idle (0), recording (1), i.e. RTIO outputs go to
+/// the trace, or playing back (2), i.e. the DMA engine holds the RTIO core.
+extern fn dma_state() -> i32 {
+    unsafe {
+        if DMA_RECORDER.active {
+            return DMA_STATE_RECORDING
         }
-        if error & 2 != 0 {
-            raise!("RTIODestinationUnreachable",
-                "RTIO destination unreachable, output, at channel {rtio_channel_info:0}, {1} mu",
-                channel as i64, timestamp as i64, 0);
+        #[cfg(kernel_has_rtio_dma)]
+        {
+            if csr::rtio_dma::enable_read() != 0 {
+                return DMA_STATE_PLAYING
+            }
         }
-    });
+    }
+    DMA_STATE_IDLE
+}
+
+/// Returns RTIO to a known state between phases of a kernel: resets the RTIO
+/// core as `rtio_init` does, and sets the timeline cursor `slack_mu` after
+/// the current RTIO counter. Raises `DMAError` if DMA is recording or
+/// playing back, which the reset would corrupt.
+extern "C-unwind" fn rtio_reset(slack_mu: i64) {
+    match dma_state() {
+        DMA_STATE_RECORDING =>
+            raise!("DMAError", "cannot reset RTIO while DMA is recording"),
+        DMA_STATE_PLAYING =>
+            raise!("DMAError", "cannot reset RTIO during DMA playback"),
+        _ => ()
+    }
+    rtio::init();
+    rtio::restore_cursor(rtio::get_counter() + slack_mu);
+}
+
+// Buffers handed to gateware are aligned to, and padded to a multiple of,
+// the DMA alignment, so that they never share a cache line with other data.
+// The kernel CPU has no MMU, so heap memory is physically contiguous and
+// never moves; the heap is reset with each kernel.
+const DMA_ALIGNMENT: usize = 64;
+const MAX_DMA_ALLOCATIONS: usize = 16;
+
+// Live buffers, with the layout they were allocated with.
+static mut DMA_ALLOCATIONS: [Option<(usize, Layout)>; MAX_DMA_ALLOCATIONS] = [None; MAX_DMA_ALLOCATIONS];
+
+/// Allocates `size` bytes aligned to `align`, or to 64 bytes if larger, for
+/// use by gateware (e.g. `dma_playback`). Free them with `dma_free`.
+extern "C-unwind" fn dma_alloc(size: i32, align: i32) -> i32 {
+    ensure!(size > 0, "ValueError",
+        "cannot allocate a DMA buffer of {0} bytes", size as i64, 0, 0);
+    ensure!(align > 0 && (align as usize).is_power_of_two(), "ValueError",
+        "DMA buffer alignment {0} is not a power of two", align as i64, 0, 0);
+
+    unsafe {
+        let slot = DMA_ALLOCATIONS.iter().position(|entry| entry.is_none()).unwrap_or_else(|| {
+            raise!("DMAError", "too many DMA buffers allocated (max {0})",
+                   MAX_DMA_ALLOCATIONS as i64, 0, 0)
+        });
+        let align = core::cmp::max(align as usize, DMA_ALIGNMENT);
+        let size = (size as usize + DMA_ALIGNMENT - 1) & !(DMA_ALIGNMENT - 1);
+        let layout = Layout::from_size_align_unchecked(size, align);
+        let buffer = ALLOC.alloc(layout);
+        ensure!(!buffer.is_null(), "DMAError",
+            "cannot allocate a DMA buffer of {0} bytes", size as i64, 0, 0);
+        DMA_ALLOCATIONS[slot] = Some((buffer as usize, layout));
+        buffer as i32
+    }
+}
+
+/// Frees a buffer returned by `dma_alloc`.
+extern "C-unwind" fn dma_free(ptr: i32) {
+    unsafe {
+        let slot = DMA_ALLOCATIONS.iter()
+            .position(|entry| entry.map_or(false, |(buffer, _)| buffer == ptr as usize))
+            .unwrap_or_else(|| {
+                raise!("DMAError", "address {0} is not an allocated DMA buffer", ptr as i64, 0, 0)
+            });
+        if let Some((buffer, layout)) = DMA_ALLOCATIONS[slot].take() {
+            ALLOC.dealloc(buffer as *mut u8, layout);
+        }
+    }
+}
+
+/// Destination the kernel is running on; 0 on the master.
+extern fn kernel_self_destination() -> i32 {
+    unsafe { SELF_DESTINATION as i32 }
+}
+
+const MAILBOX_SELFTEST_SPINS: u32 = 1_000_000;
+
+// Not on the stack, as the comms CPU may still read it after the selftest gave up.
+static mut MAILBOX_SELFTEST_REQUEST: Message<'static> = MailboxSelftestRequest(0);
+
+/// Exchanges a message with the comms CPU, like `send` followed by `recv`, but
+/// gives up after a bounded number of spins. Returns whether the comms CPU
+/// acknowledged the request and echoed it back.
+///
+/// After a timeout, the next exchange with the comms CPU first waits for the
+/// reply to the request that was given up on.
+extern fn mailbox_selftest() -> bool {
+    if unsafe { REPLY_PENDING } { drain_reply() }
+
+    let nonce = perf::cycles() as u32;
+    unsafe {
+        MAILBOX_SELFTEST_REQUEST = MailboxSelftestRequest(nonce);
+        mailbox::send(&MAILBOX_SELFTEST_REQUEST as *const _ as usize);
+    }
+
+    let mut spins = 0;
+    while !mailbox::acknowledged() {
+        spins += 1;
+        if spins == MAILBOX_SELFTEST_SPINS {
+            unsafe { REPLY_PENDING = true }
+            return false
+        }
+    }
+    let mut msg_ptr = mailbox::receive();
+    while msg_ptr == 0 {
+        spins += 1;
+        if spins == MAILBOX_SELFTEST_SPINS {
+            unsafe { REPLY_PENDING = true }
+            return false
+        }
+        msg_ptr = mailbox::receive();
+    }
+
+    let echoed = match unsafe { &*(msg_ptr as *const Message) } {
+        &MailboxSelftestReply(reply) => reply == nonce,
+        _ => false
+    };
+    mailbox::acknowledge();
+    echoed
 }
 
+const PMP_DUMP_ENTRY_SIZE: usize = 10;
+
+/// Writes the active PMP entries of the kernel CPU into `buffer`, as records of
+/// index (u8), configuration byte (u8), base (u32) and size (u32, big-endian),
+/// and returns the number of bytes written. Entries that do not fit are left out.
+extern fn pmp_dump(mut buffer: CMutSlice<u8>) -> i32 {
+    use byteorder::{ByteOrder, NetworkEndian};
+
+    let buffer = buffer.as_mut_slice();
+    let mut written = 0;
+    for index in 0..board_misoc::pmp::ENTRY_COUNT {
+        if written + PMP_DUMP_ENTRY_SIZE > buffer.len() {
+            break
+        }
+        if let Some(entry) = board_misoc::pmp::read_entry(index) {
+            let record = &mut buffer[written..written + PMP_DUMP_ENTRY_SIZE];
+            record[0] = index as u8;
+            record[1] = entry.cfg;
+            NetworkEndian::write_u32(&mut record[2..6], entry.base as u32);
+            NetworkEndian::write_u32(&mut record[6..10], entry.size as u32);
+            written += PMP_DUMP_ENTRY_SIZE;
+        }
+    }
+    written as i32
+}
 
 extern "C-unwind" fn subkernel_load_run(id: u32, destination: u8, run: bool) {
     let timestamp = unsafe {
@@ -503,10 +1235,79 @@ extern "C-unwind" fn subkernel_load_run(id: u32, destination: u8, run: bool) {
     });
 }
 
+/// Preloads the subkernel `ids[0]` to `destination`, after checking that it
+/// and the subkernels it calls, the rest of `ids`, are all in place, so that
+/// the group is either fully available or not loaded at all.
+extern "C-unwind" fn subkernel_load_group(ids: &CSlice<u32>, destination: u8) {
+    send(&SubkernelLoadGroupRequest {
+        ids: ids.as_ref(),
+        destination: destination
+    });
+    let failed_id = recv!(&SubkernelLoadGroupReply { failed_id } => failed_id);
+    if let Some(id) = failed_id {
+        raise!("SubkernelError",
+            "Error loading subkernel {0} of the group", id as i64, 0, 0);
+    }
+}
+
+/// Returns whether subkernel `id` is in place on `destination`, ready to be
+/// loaded, without loading it. Unknown subkernels and unreachable
+/// destinations give false.
+extern fn subkernel_is_available(id: u32, destination: u8) -> bool {
+    send(&SubkernelAvailableRequest { id: id, destination: destination });
+    recv!(&SubkernelAvailableReply { available } => available)
+}
+
+/// Enables or disables caching of the results of subkernel `id`, keyed by
+/// its arguments. Only has an effect when called from the master.
+extern "C-unwind" fn subkernel_cache_results(id: u32, enable: bool) {
+    send(&SubkernelCacheResultsRequest { id: id, enable: enable });
+}
+
+/// Drops the cached results of subkernel `id`.
+extern "C-unwind" fn subkernel_cache_clear(id: u32) {
+    send(&SubkernelCacheClearRequest { id: id });
+}
+
+static mut DESYNC_MESSAGE: [u8; 64] = [0; 64];
+
+/// Builds the message of the exception raised when a subkernel call gets a
+/// reply it does not expect, naming the variant of that reply. The message
+/// is stashed in a static buffer, as it must outlive the reply.
+fn desync_message(request: &Message) -> &'static str {
+    use core::fmt::{self, Write};
+
+    const PREFIX: &str = "protocol desync: got ";
+
+    struct VariantName { len: usize }
+
+    impl fmt::Write for VariantName {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            let buffer = unsafe { &mut DESYNC_MESSAGE };
+            for &byte in s.as_bytes() {
+                // The variant name ends where its fields start.
+                if !(byte.is_ascii_alphanumeric() || byte == b'_') || self.len == buffer.len() {
+                    return Err(fmt::Error)
+                }
+                buffer[self.len] = byte;
+                self.len += 1;
+            }
+            Ok(())
+        }
+    }
+
+    unsafe {
+        DESYNC_MESSAGE[..PREFIX.len()].copy_from_slice(PREFIX.as_bytes());
+        let mut name = VariantName { len: PREFIX.len() };
+        let _ = write!(name, "{:?}", request);
+        str::from_utf8_unchecked(&DESYNC_MESSAGE[..name.len])
+    }
+}
+
 extern "C-unwind" fn subkernel_await_finish(id: u32, timeout: i64) {
-    send(&SubkernelAwaitFinishRequest { id: id, timeout: timeout });
-    recv(move |request| {
-        if let SubkernelAwaitFinishReply = request { }
+    send_idle(&SubkernelAwaitFinishRequest { id: id, timeout: timeout });
+    recv_idle(move |request| {
+        if let SubkernelAwaitFinishReply = request { Ok(()) }
         else if let SubkernelError(status) = request {
             match status {
                 SubkernelStatus::IncorrectState => raise!("SubkernelError",
@@ -521,9 +1322,9 @@ extern "C-unwind" fn subkernel_await_finish(id: u32, timeout: i64) {
             }
         } else {
             send(&Log(format_args!("unexpected reply: {:?}\n", request)));
-            loop {}
+            Err(desync_message(request))
         }
-    })
+    }).unwrap_or_else(|message| raise!("SubkernelError", message))
 }
 
 extern fn subkernel_send_message(id: u32, is_return: bool, destination: u8, 
@@ -538,14 +1339,14 @@ extern fn subkernel_send_message(id: u32, is_return: bool, destination: u8,
 }
 
 extern "C-unwind" fn subkernel_await_message(id: i32, timeout: i64, tags: &CSlice<u8>, min: u8, max: u8) -> u8 {
-    send(&SubkernelMsgRecvRequest { id: id, timeout: timeout, tags: tags.as_ref() });
-    recv(move |request| {
+    send_idle(&SubkernelMsgRecvRequest { id: id, timeout: timeout, tags: tags.as_ref() });
+    recv_idle(move |request| {
         if let SubkernelMsgRecvReply { count } = request {
             if count < &min || count > &max {
                 raise!("SubkernelError",
                     "Received less or more arguments than expected");
             }
-            *count
+            Ok(*count)
         } else if let SubkernelError(status) = request {
             match status {
                 SubkernelStatus::IncorrectState => raise!("SubkernelError",
@@ -560,12 +1361,98 @@ extern "C-unwind" fn subkernel_await_message(id: i32, timeout: i64, tags: &CSlic
             }
         } else {
             send(&Log(format_args!("unexpected reply: {:?}\n", request)));
-            loop {}
+            Err(desync_message(request))
         }
-    })
+    }).unwrap_or_else(|message| raise!("SubkernelError", message))
     // RpcRecvRequest should be called `count` times after this to receive message data
 }
 
+// Each message of a stream carries its sequence number, the length of the
+// whole stream, and a chunk of it.
+const SUBKERNEL_STREAM_SEND_TAG: &[u8] = b"iiB:";
+const SUBKERNEL_STREAM_RECV_TAG: &[u8] = b"iiB";
+
+/// Sends `data` to a subkernel as successive messages of at most `chunk_size`
+/// bytes each, to be received with `subkernel_await_message_stream`. An
+/// empty buffer is sent as a single empty chunk.
+extern "C-unwind" fn subkernel_send_message_stream(id: u32, destination: u8,
+    data: CSlice<u8>, chunk_size: i32) {
+    use cslice::AsCSlice;
+
+    ensure!(chunk_size > 0, "ValueError",
+        "subkernel stream chunk size {0} is not positive", chunk_size as i64, 0, 0);
+    let data = data.as_ref();
+    let chunk_size = chunk_size as usize;
+    let total = data.len() as i32;
+    let count = core::cmp::max(1, (data.len() + chunk_size - 1) / chunk_size);
+    for seq in 0..count {
+        let start = core::cmp::min(seq * chunk_size, data.len());
+        let end = core::cmp::min(start + chunk_size, data.len());
+        let seq = seq as i32;
+        let chunk = data[start..end].as_c_slice();
+        let args = [&seq as *const i32 as *const (),
+                    &total as *const i32 as *const (),
+                    &chunk as *const CSlice<u8> as *const ()];
+        send(&SubkernelMsgSend {
+            id: id,
+            destination: Some(destination),
+            count: 3,
+            tag: SUBKERNEL_STREAM_SEND_TAG,
+            data: args.as_ptr()
+        });
+    }
+}
+
+/// Receives a stream sent with `subkernel_send_message_stream` into `buffer`,
+/// waiting at most `timeout` for each chunk, and returns its length. If an
+/// exception is raised, the chunks not yet received are left queued.
+extern "C-unwind" fn subkernel_await_message_stream(id: i32, timeout: i64,
+    mut buffer: CMutSlice<u8>) -> i32 {
+    use cslice::AsCSlice;
+
+    let buffer = buffer.as_mut_slice();
+    let mut received = 0;
+    for seq in 0.. {
+        subkernel_await_message(id, timeout, &SUBKERNEL_STREAM_RECV_TAG.as_c_slice(), 3, 3);
+        let (mut chunk_seq, mut total) = (0i32, 0i32);
+        rpc_recv(&mut chunk_seq as *mut i32 as *mut ());
+        rpc_recv(&mut total as *mut i32 as *mut ());
+        // The chunk itself is not kept, only its contents, which are stored
+        // directly in `buffer`.
+        let mut slot = [0usize; 2];
+        let size = rpc_recv(slot.as_mut_ptr() as *mut ());
+        let valid = chunk_seq == seq && total >= 0 && received + size <= total as usize;
+        let fits = valid && total as usize <= buffer.len();
+        if size > 0 {
+            if fits {
+                rpc_recv(buffer[received..].as_mut_ptr() as *mut ());
+            } else {
+                // The chunk must still be consumed before raising.
+                unsafe {
+                    let layout = Layout::from_size_align_unchecked(size, 1);
+                    let scratch = ALLOC.alloc(layout);
+                    ensure!(!scratch.is_null(), "SubkernelError",
+                        "cannot allocate {0} bytes to discard a subkernel stream chunk",
+                        size as i64, 0, 0);
+                    rpc_recv(scratch as *mut ());
+                    ALLOC.dealloc(scratch, layout);
+                }
+            }
+        }
+        ensure!(valid, "SubkernelError",
+            "subkernel stream chunk {0} is out of sequence or malformed, expected chunk {1}",
+            chunk_seq as i64, seq as i64, 0);
+        ensure!(fits, "SubkernelError",
+            "subkernel stream of {0} bytes does not fit in a buffer of {1} bytes",
+            total as i64, buffer.len() as i64, 0);
+        received += size;
+        if received == total as usize {
+            break
+        }
+    }
+    received as i32
+}
+
 unsafe fn attribute_writeback(typeinfo: *const ()) {
     #[repr(C)]
     struct Attr {
@@ -610,59 +1497,100 @@ unsafe fn attribute_writeback(typeinfo: *const ()) {
 #[global_allocator]
 static mut ALLOC: alloc_list::ListAlloc = alloc_list::EMPTY;
 static mut STACK_GUARD_BASE: usize = 0x0;
+static mut STACK_GUARD_SIZE: usize = board_misoc::pmp::STACK_GUARD_SIZE;
 
 extern {
     static mut _fheap_1: u8;
     static mut _eheap_1: u8;
 }
 
+/// Returns the size of the largest block that can be allocated on the kernel
+/// heap, which may be much less than the free memory if it is fragmented.
+extern fn heap_largest_free_block() -> usize {
+    unsafe { ALLOC.largest_free_block() }
+}
+
+/// Loads `data` into the payload area, reporting a failure to the comms CPU.
+unsafe fn load_library(data: &[u8]) {
+    let image = slice::from_raw_parts_mut(KERNELCPU_PAYLOAD_ADDRESS as *mut u8,
+                                          KERNELCPU_LAST_ADDRESS - KERNELCPU_PAYLOAD_ADDRESS);
+    match Library::load(data, image, &api::resolve) {
+        Err(error) => {
+            send(&LoadReply(Err(error)));
+            loop {}
+        },
+        Ok(library) => {
+            // The kernel may override the guard size with an absolute symbol.
+            let _sstack_guard = library.lookup(b"_sstack_guard").unwrap() as usize;
+            let guard_size = library.lookup(b"_stack_guard_size")
+                .map(|size| size as usize)
+                .unwrap_or(board_misoc::pmp::STACK_GUARD_SIZE);
+            if !board_misoc::pmp::is_valid_stack_guard(_sstack_guard, guard_size) {
+                send(&LoadReply(Err(dyld::Error::Parsing(
+                    "stack guard must be a power of two of at least 8 bytes, aligned to its size"))));
+                loop {}
+            }
+            STACK_GUARD_BASE = _sstack_guard;
+            STACK_GUARD_SIZE = guard_size;
+            LIBRARY = Some(library);
+        }
+    }
+}
+
 #[no_mangle]
 pub unsafe fn main() {
     ALLOC.add_range(&mut _fheap_1, &mut _eheap_1);
+    DMA_ALLOCATIONS = [None; MAX_DMA_ALLOCATIONS];
 
     eh_artiq::reset_exception_buffer(KERNELCPU_PAYLOAD_ADDRESS);
-    let image = slice::from_raw_parts_mut(kernel_proto::KERNELCPU_PAYLOAD_ADDRESS as *mut u8,
-                                          kernel_proto::KERNELCPU_LAST_ADDRESS -
-                                          kernel_proto::KERNELCPU_PAYLOAD_ADDRESS);
-
-    let library = recv!(&LoadRequest(library) => {
-        match Library::load(library, image, &api::resolve) {
-            Err(error) => {
-                send(&LoadReply(Err(error)));
+    watchdog::reset();
+    keepalive::reset();
+    log_buffer::reset();
+    log_limit::reset();
+    stack_limit::reset();
+    idle::reset();
+    perf::reset();
+    rtio::reset_late_policy();
+    rtio::reset_min_slack();
+    rtio::reset_input_overflows();
+    rtio::reset_now_stack();
+    recv!(&LoadRequest { library, destination, log_level, expected_crc } => {
+        LOG_LEVEL = log_level;
+        SELF_DESTINATION = destination;
+        if let Some(expected_crc) = expected_crc {
+            if crc32::checksum_ieee(library) != expected_crc {
+                send(&LoadReply(Err(dyld::Error::Parsing(
+                    "kernel does not match its CRC; it was corrupted in transfer"))));
                 loop {}
-            },
-            Ok(library) => {
-                send(&LoadReply(Ok(())));
-                // Master kernel would just acknowledge kernel load
-                // Satellites may send UpdateNow
-                try_recv(move |msg| match msg {
-                    UpdateNow(timestamp) => unsafe {
-                        csr::rtio::now_hi_write((*timestamp >> 32) as u32);
-                        csr::rtio::now_lo_write(*timestamp as u32);
-                    }
-                    _ => unreachable!()
-                });
-                library
             }
         }
+        load_library(library);
+        send(&LoadReply(Ok(())));
+        // Master kernel would just acknowledge kernel load
+        // Satellites may send UpdateNow
+        try_recv(move |msg| match msg {
+            UpdateNow(timestamp) => unsafe {
+                csr::rtio::now_hi_write((*timestamp >> 32) as u32);
+                csr::rtio::now_lo_write(*timestamp as u32);
+            }
+            _ => unreachable!()
+        });
     });
 
+    let library = LIBRARY.as_ref().unwrap();
     let __bss_start = library.lookup(b"__bss_start").unwrap();
     let _end = library.lookup(b"_end").unwrap();
     let __modinit__ = library.lookup(b"__modinit__").unwrap();
     let typeinfo = library.lookup(b"typeinfo");
-    let _sstack_guard = library.lookup(b"_sstack_guard").unwrap();
-
-    LIBRARY = Some(library);
 
     ptr::write_bytes(__bss_start as *mut u8, 0, (_end - __bss_start) as usize);
 
-    board_misoc::pmp::init_stack_guard(_sstack_guard as usize);
-    STACK_GUARD_BASE = _sstack_guard as usize;
+    board_misoc::pmp::init_stack_guard(STACK_GUARD_BASE, STACK_GUARD_SIZE);
     board_misoc::cache::flush_cpu_dcache();
     board_misoc::cache::flush_cpu_icache();
 
-    (mem::transmute::<u32, fn()>(__modinit__))();
+    // includes the time spent in `recv` while running the kernel
+    perf::timed(perf::Phase::ModInit, || (mem::transmute::<u32, fn()>(__modinit__))());
 
     if let Some(typeinfo) = typeinfo {
         attribute_writeback(typeinfo as *const ());
@@ -679,13 +1607,44 @@ pub unsafe fn main() {
     //    check for mailbox
     //
     // the async RPC would be missed.
-    send(&RpcFlush);
+    perf::timed(perf::Phase::RpcFlush, || rpc_drain());
+
+    log_buffer::flush();
+    perf::dump();
+    perf::send_timing_report();
 
     send(&RunFinished);
 
     loop {}
 }
 
+/// Returns a description of a trap cause, and a hint about what usually causes it.
+fn describe_trap(cause: mcause::Trap) -> (&'static str, &'static str) {
+    use mcause::{Trap, Exception};
+    match cause {
+        Trap::Exception(Exception::InstructionMisaligned) =>
+            ("misaligned instruction fetch", "jump to a corrupted function pointer?"),
+        Trap::Exception(Exception::InstructionFault) =>
+            ("instruction access fault", "call through a null or dangling function pointer?"),
+        Trap::Exception(Exception::IllegalInstruction) =>
+            ("illegal instruction", "executing data, or kernel built for another CPU?"),
+        Trap::Exception(Exception::Breakpoint) =>
+            ("breakpoint", "stray ebreak instruction in kernel code?"),
+        Trap::Exception(Exception::LoadMisaligned) =>
+            ("misaligned load", "unaligned pointer cast or corrupted array?"),
+        Trap::Exception(Exception::LoadFault) =>
+            ("load access fault", "null or out-of-bounds pointer dereference?"),
+        Trap::Exception(Exception::StoreMisaligned) =>
+            ("misaligned store", "unaligned pointer cast or corrupted array?"),
+        Trap::Exception(Exception::StoreFault) =>
+            ("store access fault", "null or out-of-bounds pointer write?"),
+        Trap::Exception(_) =>
+            ("unexpected exception", "firmware bug?"),
+        Trap::Interrupt(_) =>
+            ("unexpected interrupt", "firmware bug?"),
+    }
+}
+
 #[no_mangle]
 pub unsafe extern "C-unwind" fn exception(_regs: *const u32) {
     let pc = mepc::read();
@@ -695,13 +1654,15 @@ pub unsafe extern "C-unwind" fn exception(_regs: *const u32) {
     | mcause::Trap::Exception(mcause::Exception::StoreFault) = cause
     {
         if mtval >= STACK_GUARD_BASE
-            && mtval < (STACK_GUARD_BASE + board_misoc::pmp::STACK_GUARD_SIZE)
+            && mtval < (STACK_GUARD_BASE + STACK_GUARD_SIZE)
         {
             panic!("{:?} at PC {:#08x} in stack guard page ({:#08x}); stack overflow in user kernel code?",
                    cause, u32::try_from(pc).unwrap(), mtval);
         }
     }
-    panic!("{:?} at PC {:#08x}, trap value {:#08x}", cause, u32::try_from(pc).unwrap(), mtval);
+    let (description, hint) = describe_trap(cause);
+    panic!("{} ({:?}) at PC {:#08x}, trap value {:#08x}; {}",
+           description, cause, u32::try_from(pc).unwrap(), mtval, hint);
 }
 
 #[no_mangle]
@@ -0,0 +1,131 @@
+//! Coalesces the core log writes of a kernel, which often come a few
+//! characters at a time from formatting, into fewer mailbox messages.
+//!
+//! Text is sent to the comms CPU when a write contains a newline, when the
+//! buffer is full, and before the kernel terminates. The comms CPU assembles
+//! lines itself, so a flush may end in the middle of a line.
+//!
+//! The most recent text is also kept in a ring, which the kernel can read
+//! back with `core_log_tail`, e.g. to include it in an error report.
+
+use core::{cmp, fmt, str};
+use cslice::CMutSlice;
+use kernel_proto::{Message, LogSlice};
+
+const LOG_BUFFER_SIZE: usize = 256;
+
+static mut BUFFER: [u8; LOG_BUFFER_SIZE] = [0; LOG_BUFFER_SIZE];
+static mut LENGTH: usize = 0;
+
+const TAIL_SIZE: usize = 1024;
+
+static mut TAIL: [u8; TAIL_SIZE] = [0; TAIL_SIZE];
+// Where the next byte goes in the ring; the bytes before it are the most recent.
+static mut TAIL_END: usize = 0;
+static mut TAIL_WRAPPED: bool = false;
+
+pub fn reset() {
+    unsafe {
+        LENGTH = 0;
+        TAIL_END = 0;
+        TAIL_WRAPPED = false;
+    }
+}
+
+unsafe fn append_tail(mut bytes: &[u8]) {
+    if bytes.len() > TAIL_SIZE {
+        bytes = &bytes[bytes.len() - TAIL_SIZE..];
+    }
+    let count = cmp::min(bytes.len(), TAIL_SIZE - TAIL_END);
+    TAIL[TAIL_END..TAIL_END + count].copy_from_slice(&bytes[..count]);
+    TAIL[..bytes.len() - count].copy_from_slice(&bytes[count..]);
+    if TAIL_END + bytes.len() >= TAIL_SIZE {
+        TAIL_WRAPPED = true;
+    }
+    TAIL_END = (TAIL_END + bytes.len()) % TAIL_SIZE;
+}
+
+pub fn write(mut text: &str) {
+    let newline = text.contains('\n');
+    unsafe {
+        append_tail(text.as_bytes());
+        while !text.is_empty() {
+            // Only whole characters are buffered, so the buffer is valid UTF-8.
+            let mut count = cmp::min(text.len(), LOG_BUFFER_SIZE - LENGTH);
+            while !text.is_char_boundary(count) { count -= 1 }
+            BUFFER[LENGTH..LENGTH + count].copy_from_slice(text[..count].as_bytes());
+            LENGTH += count;
+            text = &text[count..];
+            if !text.is_empty() { flush() }
+        }
+    }
+    if newline { flush() }
+}
+
+pub fn flush() {
+    unsafe {
+        if LENGTH > 0 {
+            ::send(&LogSlice(str::from_utf8_unchecked(&BUFFER[..LENGTH])));
+            LENGTH = 0;
+        }
+    }
+}
+
+/// Sends the buffered text followed by `request`, in a single batch if there
+/// is any text. `request` must be one that can be batched (see `send_batch`).
+pub fn flush_then(request: Message) {
+    unsafe {
+        if LENGTH > 0 {
+            ::send_batch(&[LogSlice(str::from_utf8_unchecked(&BUFFER[..LENGTH])), request]);
+            LENGTH = 0;
+        } else {
+            ::send(&request);
+        }
+    }
+}
+
+pub struct Writer;
+
+impl fmt::Write for Writer {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        write(s);
+        Ok(())
+    }
+}
+
+/// Sends the buffered text to the core log.
+pub extern fn flush_extern() {
+    flush()
+}
+
+/// Copies the most recent core log text of the kernel into `buffer`, as much
+/// of it as fits, in the order it was written, and returns the number of
+/// bytes copied. Only the last 1024 bytes are kept, and a character cut at
+/// the start of the window is left out.
+pub extern fn tail(mut buffer: CMutSlice<u8>) -> i32 {
+    unsafe {
+        let buffer = buffer.as_mut_slice();
+        let cap = buffer.len();
+        let (older, newer) = if TAIL_WRAPPED {
+            (&TAIL[TAIL_END..], &TAIL[..TAIL_END])
+        } else {
+            (&TAIL[..0], &TAIL[..TAIL_END])
+        };
+        let total = older.len() + newer.len();
+        let byte_at = |index: usize| {
+            if index < older.len() { older[index] } else { newer[index - older.len()] }
+        };
+        let mut skip = total.saturating_sub(cap);
+        // skip UTF-8 continuation bytes, so that the window starts at a character
+        while skip < total && byte_at(skip) & 0xc0 == 0x80 { skip += 1 }
+        let mut written = 0;
+        for part in [older, newer].iter() {
+            let start = cmp::min(skip, part.len());
+            let part = &part[start..];
+            skip -= start;
+            buffer[written..written + part.len()].copy_from_slice(part);
+            written += part.len();
+        }
+        written as i32
+    }
+}
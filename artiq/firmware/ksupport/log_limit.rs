@@ -0,0 +1,61 @@
+//! Optional token-bucket rate limit on the core log messages of a kernel.
+//!
+//! Each message takes a token, and a token is added every `refill_mu` (on the
+//! RTIO counter), up to `burst` tokens. Messages arriving at an empty bucket
+//! are dropped and counted; the count is logged after the next message that
+//! gets through.
+
+use core::{cmp, mem};
+use rtio;
+
+// A burst of 0 disables the limit.
+static mut BURST: i64 = 0;
+static mut REFILL: i64 = 0;
+static mut TOKENS: i64 = 0;
+static mut LAST_REFILL: i64 = 0;
+static mut SUPPRESSED: u32 = 0;
+
+pub fn reset() {
+    unsafe {
+        BURST = 0;
+        SUPPRESSED = 0;
+    }
+}
+
+/// Lets through at most `burst` messages at once, and one more every
+/// `refill_mu`. A non-positive burst or refill period disables the limit.
+pub extern fn set(burst: i32, refill_mu: i64) {
+    unsafe {
+        if burst > 0 && refill_mu > 0 {
+            BURST = burst as i64;
+            REFILL = refill_mu;
+            TOKENS = BURST;
+            LAST_REFILL = rtio::get_counter();
+            SUPPRESSED = 0;
+        } else {
+            reset();
+        }
+    }
+}
+
+/// Takes a token for a message. Returns `None` if the message must be
+/// dropped, or else the number of messages dropped since the previous one
+/// that got through.
+pub fn admit() -> Option<u32> {
+    unsafe {
+        if BURST == 0 {
+            return Some(0)
+        }
+        let refills = (rtio::get_counter() - LAST_REFILL) / REFILL;
+        if refills > 0 {
+            TOKENS = cmp::min(BURST, TOKENS + refills);
+            LAST_REFILL += refills * REFILL;
+        }
+        if TOKENS == 0 {
+            SUPPRESSED = SUPPRESSED.saturating_add(1);
+            return None
+        }
+        TOKENS -= 1;
+        Some(mem::replace(&mut SUPPRESSED, 0))
+    }
+}
@@ -1,34 +1,47 @@
+// Bus errors raise `I2CError` or `SPIError` with the device address (-1 for
+// operations that do not address a device), the error code of the reply
+// (`NRT_BUS_*`), and the bus number or, for batches, the index of the element
+// that failed.
+
 pub mod i2c {
+    use cslice::{CSlice, CMutSlice};
     use ::send;
     use ::recv;
     use kernel_proto::*;
 
+    // Device address of the asynchronous read in each slot.
+    static mut IN_FLIGHT: [Option<u8>; I2C_ASYNC_READ_SLOTS] = [None; I2C_ASYNC_READ_SLOTS];
+
     pub extern fn start(busno: i32) {
         send(&I2cStartRequest { busno: busno as u32 });
-        recv!(&I2cBasicReply { succeeded } => if !succeeded {
-            raise!("I2CError", "I2C bus could not be accessed");
+        recv!(&I2cBasicReply { error } => if error != 0 {
+            raise!("I2CError", "I2C bus {2} could not be accessed (error {1})",
+                   -1, error as i64, busno as i64);
         });
     }
 
     pub extern fn restart(busno: i32) {
         send(&I2cRestartRequest { busno: busno as u32 });
-        recv!(&I2cBasicReply { succeeded } => if !succeeded {
-            raise!("I2CError", "I2C bus could not be accessed");
+        recv!(&I2cBasicReply { error } => if error != 0 {
+            raise!("I2CError", "I2C bus {2} could not be accessed (error {1})",
+                   -1, error as i64, busno as i64);
         });
     }
 
     pub extern fn stop(busno: i32) {
         send(&I2cStopRequest { busno: busno as u32 });
-        recv!(&I2cBasicReply { succeeded } => if !succeeded {
-            raise!("I2CError", "I2C bus could not be accessed");
+        recv!(&I2cBasicReply { error } => if error != 0 {
+            raise!("I2CError", "I2C bus {2} could not be accessed (error {1})",
+                   -1, error as i64, busno as i64);
         });
     }
 
     pub extern fn write(busno: i32, data: i32) -> bool {
         send(&I2cWriteRequest { busno: busno as u32, data: data as u8 });
-        recv!(&I2cWriteReply { succeeded, ack } => {
-            if !succeeded {
-                raise!("I2CError", "I2C bus could not be accessed");
+        recv!(&I2cWriteReply { error, ack } => {
+            if error != 0 {
+                raise!("I2CError", "I2C bus {2} could not be accessed (error {1})",
+                       -1, error as i64, busno as i64);
             }
             ack
         })
@@ -36,27 +49,102 @@ pub mod i2c {
 
     pub extern fn read(busno: i32, ack: bool) -> i32 {
         send(&I2cReadRequest { busno: busno as u32, ack: ack });
-        recv!(&I2cReadReply { succeeded, data } => {
-            if !succeeded {
-                raise!("I2CError", "I2C bus could not be accessed");
+        recv!(&I2cReadReply { error, data } => {
+            if error != 0 {
+                raise!("I2CError", "I2C bus {2} could not be accessed (error {1})",
+                       -1, error as i64, busno as i64);
+            }
+            data
+        }) as i32
+    }
+
+    /// Starts reading `register` of the device at `address`, and returns a
+    /// token for `read_collect`. The kernel keeps running while the comms CPU
+    /// performs the transaction.
+    pub extern fn read_async(busno: i32, address: i32, register: i32) -> i32 {
+        let slot = unsafe { IN_FLIGHT.iter().position(|read| read.is_none()) }.unwrap_or_else(|| {
+            raise!("I2CError", "too many I2C reads in flight (max {0})",
+                   I2C_ASYNC_READ_SLOTS as i64, 0, 0)
+        });
+        unsafe { IN_FLIGHT[slot] = Some(address as u8) }
+        send(&I2cReadAsyncRequest {
+            slot: slot as u8,
+            busno: busno as u32,
+            address: address as u8,
+            register: register as u8 });
+        slot as i32
+    }
+
+    /// Waits for the read started with `read_async` that returned `token`,
+    /// and returns the value read.
+    pub extern fn read_collect(token: i32) -> i32 {
+        let slot = token as usize;
+        let address = unsafe {
+            ensure!(token >= 0 && slot < I2C_ASYNC_READ_SLOTS && IN_FLIGHT[slot].is_some(), "I2CError",
+                "no I2C read in flight for token {0}", token as i64, 0, 0);
+            IN_FLIGHT[slot].take().unwrap()
+        };
+        send(&I2cReadCollectRequest { slot: slot as u8 });
+        recv!(&I2cReadReply { error, data } => {
+            if error != 0 {
+                raise!("I2CError", "I2C read from device {0} failed (error {1})",
+                       address as i64, error as i64, 0);
             }
             data
         }) as i32
     }
 
     pub extern fn switch_select(busno: i32, address: i32, mask: i32) {
-        send(&I2cSwitchSelectRequest { 
-            busno: busno as u32, 
-            address: address as u8, 
+        send(&I2cSwitchSelectRequest {
+            busno: busno as u32,
+            address: address as u8,
             mask: mask as u8 });
-        recv!(&I2cBasicReply { succeeded } => { if !succeeded {
-                raise!("I2CError", "I2C bus could not be accessed");
+        recv!(&I2cBasicReply { error } => { if error != 0 {
+                raise!("I2CError", "I2C switch {0} on bus {2} could not be set (error {1})",
+                       address as i64, error as i64, busno as i64);
+            }
+        });
+    }
+
+    /// Writes each `(register, value)` pair to the device at `address`,
+    /// as one request to the comms CPU.
+    pub extern fn write_batch(busno: i32, address: i32, writes: &CSlice<(i32, i32)>) {
+        send(&I2cWriteBatchRequest {
+            busno: busno as u32,
+            address: address as u8,
+            writes: writes.as_ref() });
+        recv!(&I2cBatchReply { failed, .. } => if let Some((index, error)) = failed {
+            raise!("I2CError", "I2C batch write to device {0} failed at element {2} (error {1})",
+                   address as i64, error as i64, index as i64);
+        });
+    }
+
+    /// Reads each register in `registers` from the device at `address`
+    /// into the matching element of `values`.
+    pub extern fn read_batch(busno: i32, address: i32, registers: &CSlice<i32>,
+                             values: &mut CMutSlice<i32>) {
+        if values.len() < registers.len() {
+            raise!("ValueError", "I2C batch read of {0} registers into a list of {1}",
+                   registers.len() as i64, values.len() as i64, 0);
+        }
+        send(&I2cReadBatchRequest {
+            busno: busno as u32,
+            address: address as u8,
+            registers: registers.as_ref() });
+        recv!(&I2cBatchReply { failed, data } => {
+            for (value, &byte) in values.as_mut_slice().iter_mut().zip(data.iter()) {
+                *value = byte as i32;
+            }
+            if let Some((index, error)) = failed {
+                raise!("I2CError", "I2C batch read from device {0} failed at element {2} (error {1})",
+                       address as i64, error as i64, index as i64);
             }
         });
     }
 }
 
 pub mod spi {
+    use cslice::{CSlice, CMutSlice};
     use ::send;
     use ::recv;
     use kernel_proto::*;
@@ -64,25 +152,155 @@ pub mod spi {
     pub extern fn set_config(busno: i32, flags: i32, length: i32, div: i32, cs: i32) {
         send(&SpiSetConfigRequest { busno: busno as u32, flags: flags as u8,
                                     length: length as u8, div: div as u8, cs: cs as u8 });
-        recv!(&SpiBasicReply { succeeded } => if !succeeded {
-            raise!("SPIError", "SPI bus could not be accessed");
+        recv!(&SpiBasicReply { error } => if error != 0 {
+            raise!("SPIError", "SPI bus {2} could not be accessed (error {1})",
+                   -1, error as i64, busno as i64);
         });
     }
 
     pub extern fn write(busno: i32, data: i32) {
         send(&SpiWriteRequest { busno: busno as u32, data: data as u32 });
-        recv!(&SpiBasicReply { succeeded } => if !succeeded {
-            raise!("SPIError", "SPI bus could not be accessed");
+        recv!(&SpiBasicReply { error } => if error != 0 {
+            raise!("SPIError", "SPI bus {2} could not be accessed (error {1})",
+                   -1, error as i64, busno as i64);
         });
     }
 
     pub extern fn read(busno: i32) -> i32 {
         send(&SpiReadRequest { busno: busno as u32 });
-        recv!(&SpiReadReply { succeeded, data } => {
-            if !succeeded {
-                raise!("SPIError", "SPI bus could not be accessed");
+        recv!(&SpiReadReply { error, data } => {
+            if error != 0 {
+                raise!("SPIError", "SPI bus {2} could not be accessed (error {1})",
+                       -1, error as i64, busno as i64);
             }
             data
         }) as i32
     }
+
+    /// Writes each word of `words`, as one request to the comms CPU.
+    pub extern fn write_wide(busno: i32, words: &CSlice<i32>) {
+        ensure!(words.len() <= SPI_WIDE_MAX_WORDS, "ValueError",
+            "cannot write {0} SPI words at once, at most {1} are supported",
+            words.len() as i64, SPI_WIDE_MAX_WORDS as i64, 0);
+        send(&SpiWriteWideRequest { busno: busno as u32, data: words.as_ref() });
+        recv!(&SpiBasicReply { error } => if error != 0 {
+            raise!("SPIError", "SPI bus {2} could not be accessed (error {1})",
+                   -1, error as i64, busno as i64);
+        });
+    }
+
+    /// Writes each word of `words` and replaces it with the word read back,
+    /// as one request to the comms CPU.
+    pub extern fn read_wide(busno: i32, words: &mut CMutSlice<i32>) {
+        let words = words.as_mut_slice();
+        ensure!(words.len() <= SPI_WIDE_MAX_WORDS, "ValueError",
+            "cannot read {0} SPI words at once, at most {1} are supported",
+            words.len() as i64, SPI_WIDE_MAX_WORDS as i64, 0);
+        send(&SpiReadWideRequest { busno: busno as u32, data: words });
+        recv!(&SpiReadWideReply { error, data } => {
+            if error != 0 {
+                raise!("SPIError", "SPI bus {2} could not be accessed (error {1})",
+                       -1, error as i64, busno as i64);
+            }
+            words.copy_from_slice(data);
+        });
+    }
+}
+
+// Registers of I2C peripherals (e.g. ADC gain or DAC offset registers), each
+// named by a single address word:
+//
+//   bits 31-24: 0
+//   bits 23-16: I2C bus number
+//   bits 15-8:  8-bit device address (LSB=0)
+//   bits 7-0:   register
+//
+// Registers are 8 bits wide, and each access is its own I2C transaction.
+// Bus errors raise `I2CError` with the address word, the error code and the
+// bus number.
+pub mod reg {
+    use cslice::CSlice;
+    use ::send;
+    use ::recv;
+    use kernel_proto::*;
+
+    fn decode(address: i32) -> (u32, u8, i32) {
+        if address as u32 >> 24 != 0 || address & 0x100 != 0 {
+            raise!("I2CError", "invalid non-realtime bus address {0:#x} (error {1})",
+                   address as i64, NRT_BUS_INVALID_ADDRESS as i64, -1);
+        }
+        ((address >> 16 & 0xff) as u32, (address >> 8 & 0xff) as u8, address & 0xff)
+    }
+
+    pub extern fn write(address: i32, value: i32) {
+        ensure!(value >= 0 && value <= 0xff, "ValueError",
+            "register value {0} does not fit in 8 bits", value as i64, 0, 0);
+        let (busno, device, register) = decode(address);
+        send(&I2cWriteBatchRequest {
+            busno: busno,
+            address: device,
+            writes: &[(register, value)] });
+        recv!(&I2cBatchReply { failed, .. } => if let Some((_, error)) = failed {
+            raise!("I2CError", "write to register {0:#x} failed (error {1})",
+                   address as i64, error as i64, busno as i64);
+        });
+    }
+
+    const WRITE_BATCH_CHUNK: usize = 32;
+
+    /// Writes `values[i]` to `addresses[i]`, in order. Consecutive writes to
+    /// the same device are sent together. Every address and value is checked
+    /// before anything is written. The batch is not atomic: a failed write
+    /// stops it and is reported with its index, and the writes before it
+    /// remain done.
+    pub extern fn write_batch(addresses: &CSlice<i32>, values: &CSlice<i32>) {
+        let (addresses, values) = (addresses.as_ref(), values.as_ref());
+        ensure!(addresses.len() == values.len(), "ValueError",
+            "{0} register addresses given for {1} values",
+            addresses.len() as i64, values.len() as i64, 0);
+        for (&address, &value) in addresses.iter().zip(values.iter()) {
+            ensure!(value >= 0 && value <= 0xff, "ValueError",
+                "register value {0} does not fit in 8 bits", value as i64, 0, 0);
+            decode(address);
+        }
+
+        let mut writes = [(0, 0); WRITE_BATCH_CHUNK];
+        let mut start = 0;
+        while start < addresses.len() {
+            let (busno, device, _) = decode(addresses[start]);
+            let mut count = 0;
+            while start + count < addresses.len() && count < WRITE_BATCH_CHUNK {
+                let (next_busno, next_device, register) = decode(addresses[start + count]);
+                if (next_busno, next_device) != (busno, device) { break }
+                writes[count] = (register, values[start + count]);
+                count += 1;
+            }
+            send(&I2cWriteBatchRequest {
+                busno: busno,
+                address: device,
+                writes: &writes[..count] });
+            recv!(&I2cBatchReply { failed, .. } => if let Some((index, error)) = failed {
+                let index = start + index as usize;
+                raise!("I2CError",
+                       "batched write to register {0:#x} failed at element {2} (error {1})",
+                       addresses[index] as i64, error as i64, index as i64);
+            });
+            start += count;
+        }
+    }
+
+    pub extern fn read(address: i32) -> i32 {
+        let (busno, device, register) = decode(address);
+        send(&I2cReadBatchRequest {
+            busno: busno,
+            address: device,
+            registers: &[register] });
+        recv!(&I2cBatchReply { failed, data } => {
+            if let Some((_, error)) = failed {
+                raise!("I2CError", "read from register {0:#x} failed (error {1})",
+                       address as i64, error as i64, busno as i64);
+            }
+            data[0]
+        }) as i32
+    }
 }
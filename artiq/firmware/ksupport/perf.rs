@@ -0,0 +1,225 @@
+//! Cycle-accurate profiling of kernel sections, based on the `mcycle` CSR.
+//! Cycles are accumulated per section name and logged when the kernel finishes.
+
+use core::str;
+use cslice::CSlice;
+use riscv::register::mcycle;
+
+const MAX_SECTIONS: usize = 32;
+const MAX_DEPTH: usize = 16;
+const MAX_NAME_LEN: usize = 32;
+
+#[derive(Clone, Copy)]
+struct Section {
+    // Copied, as the name passed by the kernel may be a temporary string.
+    name:     [u8; MAX_NAME_LEN],
+    name_len: usize,
+    cycles:   u64,
+    count:    u32,
+}
+
+impl Section {
+    fn name(&self) -> &[u8] {
+        &self.name[..self.name_len]
+    }
+}
+
+const EMPTY_SECTION: Section = Section { name: [0; MAX_NAME_LEN], name_len: 0, cycles: 0, count: 0 };
+
+static mut SECTIONS: [Section; MAX_SECTIONS] = [EMPTY_SECTION; MAX_SECTIONS];
+static mut SECTION_COUNT: usize = 0;
+// Open sections, as (index into SECTIONS, start cycle).
+static mut STACK: [(usize, u64); MAX_DEPTH] = [(0, 0); MAX_DEPTH];
+static mut DEPTH: usize = 0;
+
+#[inline(always)]
+pub fn cycles() -> u64 {
+    mcycle::read64()
+}
+
+pub fn reset() {
+    unsafe {
+        SECTION_COUNT = 0;
+        DEPTH = 0;
+        #[cfg(feature = "timing_report")]
+        { PHASE_CYCLES = [0; 3]; }
+        #[cfg(feature = "mailbox_latency")]
+        { MAILBOX_SPINS = EMPTY_SPIN_STATS; }
+    }
+}
+
+unsafe fn lookup(name: &[u8]) -> usize {
+    for i in 0..SECTION_COUNT {
+        if SECTIONS[i].name() == name {
+            return i
+        }
+    }
+    if name.len() > MAX_NAME_LEN {
+        raise!("ValueError", "profiled section name too long ({0} bytes, max {1})",
+               name.len() as i64, MAX_NAME_LEN as i64, 0)
+    }
+    if SECTION_COUNT == MAX_SECTIONS {
+        raise!("RuntimeError", "too many profiled sections (max {0})",
+               MAX_SECTIONS as i64, 0, 0)
+    }
+    let section = &mut SECTIONS[SECTION_COUNT];
+    *section = EMPTY_SECTION;
+    section.name[..name.len()].copy_from_slice(name);
+    section.name_len = name.len();
+    SECTION_COUNT += 1;
+    SECTION_COUNT - 1
+}
+
+fn begin(name: &[u8]) {
+    unsafe {
+        if DEPTH == MAX_DEPTH {
+            raise!("RuntimeError", "profiled sections nested too deeply (max {0})",
+                   MAX_DEPTH as i64, 0, 0)
+        }
+        let index = lookup(name);
+        STACK[DEPTH] = (index, cycles());
+        DEPTH += 1;
+    }
+}
+
+fn end() {
+    let now = cycles();
+    unsafe {
+        if DEPTH == 0 {
+            raise!("RuntimeError", "no profiled section to end")
+        }
+        DEPTH -= 1;
+        let (index, start) = STACK[DEPTH];
+        SECTIONS[index].cycles += now.wrapping_sub(start);
+        SECTIONS[index].count += 1;
+    }
+}
+
+pub extern fn cycles_extern() -> i64 {
+    cycles() as i64
+}
+
+/// Busy-waits for `n` cycles of the kernel CPU, plus the few cycles of the
+/// loop, for short delays such as settling times when bit-banging. Unlike
+/// `delay_mu`, this blocks the CPU for the whole delay and does not advance
+/// the timeline cursor, so RTIO events keep their timestamps.
+pub extern fn delay_cycles(n: i64) {
+    let start = cycles();
+    if n > 0 {
+        while cycles().wrapping_sub(start) < n as u64 {}
+    }
+}
+
+pub extern fn section_begin(name: CSlice<u8>) {
+    begin(name.as_ref())
+}
+
+pub extern fn section_end() {
+    end()
+}
+
+/// Logs the accumulated table, if any section was entered.
+pub fn dump() {
+    unsafe {
+        if SECTION_COUNT == 0 {
+            return
+        }
+        if DEPTH != 0 {
+            println!("perf: {} section(s) left open", DEPTH);
+        }
+        println!("perf: {:>16} {:>10} {:>20}", "section", "count", "cycles");
+        for section in SECTIONS[..SECTION_COUNT].iter() {
+            let name = str::from_utf8(section.name()).unwrap_or("<invalid utf-8>");
+            println!("perf: {:>16} {:>10} {:>20}", name, section.count, section.cycles);
+        }
+    }
+}
+
+/// Phases of a run accounted for in the end-of-run timing report.
+#[derive(Clone, Copy)]
+pub enum Phase {
+    ModInit,
+    Recv,
+    RpcFlush
+}
+
+#[cfg(feature = "timing_report")]
+static mut PHASE_CYCLES: [u64; 3] = [0; 3];
+
+#[cfg(feature = "timing_report")]
+#[inline(always)]
+pub fn timed<R, F: FnOnce() -> R>(phase: Phase, f: F) -> R {
+    let start = cycles();
+    let result = f();
+    unsafe { PHASE_CYCLES[phase as usize] += cycles().wrapping_sub(start) }
+    result
+}
+
+#[cfg(not(feature = "timing_report"))]
+#[inline(always)]
+pub fn timed<R, F: FnOnce() -> R>(_phase: Phase, f: F) -> R {
+    f()
+}
+
+#[cfg(feature = "timing_report")]
+pub fn send_timing_report() {
+    let cycles = unsafe { PHASE_CYCLES };
+    ::send(&::kernel_proto::TimingReport {
+        modinit_cycles:   cycles[Phase::ModInit as usize],
+        recv_cycles:      cycles[Phase::Recv as usize],
+        rpc_flush_cycles: cycles[Phase::RpcFlush as usize]
+    });
+}
+
+#[cfg(not(feature = "timing_report"))]
+#[inline(always)]
+pub fn send_timing_report() {}
+
+/// Distribution of the number of polling iterations spent waiting on the
+/// mailbox, as (count, min, max, sum). The kernel waits for the comms CPU to
+/// acknowledge each message it sends and to post each reply, so long waits
+/// point at contention on the comms CPU.
+#[repr(C)]
+pub struct MailboxLatencyStats {
+    count: i64,
+    min:   i64,
+    max:   i64,
+    sum:   i64
+}
+
+#[cfg(feature = "mailbox_latency")]
+const EMPTY_SPIN_STATS: (u64, u64, u64, u64) = (0, u64::max_value(), 0, 0);
+
+#[cfg(feature = "mailbox_latency")]
+static mut MAILBOX_SPINS: (u64, u64, u64, u64) = EMPTY_SPIN_STATS;
+
+#[cfg(feature = "mailbox_latency")]
+#[inline(always)]
+pub fn record_mailbox_spins(spins: u64) {
+    unsafe {
+        let (ref mut count, ref mut min, ref mut max, ref mut sum) = MAILBOX_SPINS;
+        *count += 1;
+        *min = core::cmp::min(*min, spins);
+        *max = core::cmp::max(*max, spins);
+        *sum += spins;
+    }
+}
+
+#[cfg(not(feature = "mailbox_latency"))]
+#[inline(always)]
+pub fn record_mailbox_spins(_spins: u64) {}
+
+/// Returns the mailbox wait statistics since the kernel started. They are
+/// only sampled with the `mailbox_latency` feature; otherwise the count is 0.
+pub extern fn mailbox_latency_stats() -> MailboxLatencyStats {
+    #[cfg(feature = "mailbox_latency")]
+    let (count, min, max, sum) = unsafe { MAILBOX_SPINS };
+    #[cfg(not(feature = "mailbox_latency"))]
+    let (count, min, max, sum) = (0u64, 0u64, 0u64, 0u64);
+    MailboxLatencyStats {
+        count: count as i64,
+        min:   if count == 0 { 0 } else { min as i64 },
+        max:   max as i64,
+        sum:   sum as i64
+    }
+}
@@ -4,11 +4,173 @@ pub struct TimestampedData {
     data: i32,
 }
 
+#[repr(C)]
+pub struct Resolution {
+    coarse_period_ps: i64,
+    fine_bits: i64,
+}
+
+/// Late output events raise `RTIOUnderflow`.
+pub const LATE_POLICY_RAISE: i32 = 0;
+/// Late output events are dropped and counted.
+pub const LATE_POLICY_DROP: i32 = 1;
+
+static mut LATE_POLICY: i32 = LATE_POLICY_RAISE;
+static mut DROPPED_COUNT: i32 = 0;
+
+pub fn reset_late_policy() {
+    unsafe {
+        LATE_POLICY = LATE_POLICY_RAISE;
+        DROPPED_COUNT = 0;
+    }
+}
+
+/// Sets what happens to the output events, direct or from DMA playback, that
+/// are submitted too late: `LATE_POLICY_RAISE` or `LATE_POLICY_DROP`.
+pub extern "C-unwind" fn set_late_policy(policy: i32) {
+    ensure!(policy == LATE_POLICY_RAISE || policy == LATE_POLICY_DROP, "ValueError",
+        "unknown RTIO late policy {0}", policy as i64, 0, 0);
+    unsafe { LATE_POLICY = policy }
+}
+
+/// Returns the number of late output events dropped since the kernel started.
+/// A DMA playback that underflows counts as one.
+pub extern fn dropped_count() -> i32 {
+    unsafe { DROPPED_COUNT }
+}
+
+/// Returns whether a late output event is to be dropped rather than raised
+/// under the current policy, counting it if so.
+pub fn drop_late_event() -> bool {
+    unsafe {
+        if LATE_POLICY == LATE_POLICY_DROP {
+            DROPPED_COUNT = DROPPED_COUNT.saturating_add(1);
+            true
+        } else {
+            false
+        }
+    }
+}
+
+// The smallest slack, `now_mu` minus the RTIO counter, seen by an output.
+static mut MIN_SLACK: i64 = i64::max_value();
+
+pub fn reset_min_slack() {
+    unsafe { MIN_SLACK = i64::max_value() }
+}
+
+/// Returns the smallest slack, in machine units, with which an output event
+/// was submitted since the kernel started, or the largest `i64` if none was.
+/// A value close to 0 means the kernel came close to an underflow.
+pub extern fn min_slack() -> i64 {
+    unsafe { MIN_SLACK }
+}
+
+const MAX_STICKY_OVERFLOW_CHANNELS: usize = 16;
+
+// The input channels whose overflows are recorded instead of raised, with
+// whether one has happened since the flag was last cleared.
+static mut STICKY_OVERFLOWS: [Option<(i32, bool)>; MAX_STICKY_OVERFLOW_CHANNELS] =
+    [None; MAX_STICKY_OVERFLOW_CHANNELS];
+
+pub fn reset_input_overflows() {
+    unsafe { STICKY_OVERFLOWS = [None; MAX_STICKY_OVERFLOW_CHANNELS] }
+}
+
+fn sticky_overflow(channel: i32) -> Option<&'static mut bool> {
+    unsafe {
+        STICKY_OVERFLOWS.iter_mut()
+            .filter_map(|entry| entry.as_mut())
+            .find(|entry| entry.0 == channel)
+            .map(|entry| &mut entry.1)
+    }
+}
+
+/// Makes the input overflows of `channel` set a flag, read with
+/// `input_overflowed`, instead of raising `RTIOOverflow` (`sticky`), or raise
+/// again. The events lost to an overflow are skipped. At most 16 channels can
+/// have sticky overflows at once.
+pub extern "C-unwind" fn set_input_overflow_sticky(channel: i32, sticky: bool) {
+    unsafe {
+        let index = STICKY_OVERFLOWS.iter().position(|entry|
+            entry.map_or(false, |(sticky_channel, _)| sticky_channel == channel));
+        match (index, sticky) {
+            (Some(_), true) => (),
+            (Some(index), false) => STICKY_OVERFLOWS[index] = None,
+            (None, true) => {
+                match STICKY_OVERFLOWS.iter_mut().find(|entry| entry.is_none()) {
+                    Some(entry) => *entry = Some((channel, false)),
+                    None => raise!("RuntimeError",
+                        "too many channels with sticky input overflows, at most {0}",
+                        MAX_STICKY_OVERFLOW_CHANNELS as i64, 0, 0)
+                }
+            }
+            (None, false) => ()
+        }
+    }
+}
+
+/// Returns whether an input overflow happened on `channel`, with sticky
+/// overflows, since its flag was last cleared.
+pub extern fn input_overflowed(channel: i32) -> bool {
+    sticky_overflow(channel).map_or(false, |overflowed| *overflowed)
+}
+
+/// Clears the input overflow flag of `channel`.
+pub extern fn clear_input_overflow(channel: i32) {
+    if let Some(overflowed) = sticky_overflow(channel) {
+        *overflowed = false;
+    }
+}
+
+/// Records an input overflow of `channel`, and returns whether the channel
+/// has sticky overflows, in which case it must not be raised.
+fn note_input_overflow(channel: i32) -> bool {
+    match sticky_overflow(channel) {
+        Some(overflowed) => { *overflowed = true; true }
+        None => false
+    }
+}
+
+const NOW_STACK_DEPTH: usize = 16;
+
+// Timeline cursors saved by `now_push`, for nested sequences.
+static mut NOW_STACK: [i64; NOW_STACK_DEPTH] = [0; NOW_STACK_DEPTH];
+static mut NOW_STACK_LEN: usize = 0;
+
+pub fn reset_now_stack() {
+    unsafe { NOW_STACK_LEN = 0 }
+}
+
+/// Saves the timeline cursor, to be restored by the matching `now_pop`.
+/// Raises `RTIOError` if 16 cursors are already saved.
+pub extern "C-unwind" fn now_push() {
+    unsafe {
+        ensure!(NOW_STACK_LEN < NOW_STACK_DEPTH, "RTIOError",
+            "timeline cursor stack overflow, at most {0} cursors can be saved",
+            NOW_STACK_DEPTH as i64, 0, 0);
+        NOW_STACK[NOW_STACK_LEN] = save_cursor();
+        NOW_STACK_LEN += 1;
+    }
+}
+
+/// Restores the timeline cursor saved by the last `now_push`.
+/// Raises `RTIOError` if there is none.
+pub extern "C-unwind" fn now_pop() {
+    unsafe {
+        ensure!(NOW_STACK_LEN > 0, "RTIOError",
+            "timeline cursor stack underflow, no cursor was saved");
+        NOW_STACK_LEN -= 1;
+        restore_cursor(NOW_STACK[NOW_STACK_LEN]);
+    }
+}
+
 #[cfg(has_rtio)]
 mod imp {
     use core::ptr::{read_volatile, write_volatile};
-    use cslice::CSlice;
-    use rtio::TimestampedData;
+    use cslice::{CSlice, CMutSlice};
+    use rtio::{TimestampedData, Resolution, drop_late_event, note_input_overflow};
+    use rtio::MIN_SLACK;
 
     use board_misoc::csr;
     use ::send;
@@ -38,6 +200,18 @@ mod imp {
         }
     }
 
+    /// Returns whether `destination` can be reached over DRTIO, for kernels
+    /// to avoid dispatching work to a satellite that is down. An invalid
+    /// destination is reported as down.
+    pub extern fn drtio_link_up(destination: i32) -> bool {
+        if 0 <= destination && destination <= 255 {
+            send(&DrtioLinkStatusRequest { destination: destination as u8 });
+            recv!(&DrtioLinkStatusReply { up } => up)
+        } else {
+            false
+        }
+    }
+
     pub extern fn get_counter() -> i64 {
         unsafe {
             csr::rtio::counter_update_write(1);
@@ -45,6 +219,89 @@ mod imp {
         }
     }
 
+    /// Busy-waits until the RTIO counter reaches `target_mu`, and returns the
+    /// counter value at release.
+    pub extern fn wait_until(target_mu: i64) -> i64 {
+        let mut counter = get_counter();
+        if counter > target_mu {
+            raise!("RTIOUnderflow",
+                "wait target {0} mu is already in the past (counter at {1} mu)",
+                target_mu, counter, 0);
+        }
+        while counter < target_mu {
+            ::watchdog::check();
+            ::keepalive::poll();
+            counter = get_counter();
+        }
+        counter
+    }
+
+    /// Returns the timeline cursor (`now_mu`).
+    pub extern fn save_cursor() -> i64 {
+        unsafe {
+            ((csr::rtio::now_hi_read() as i64) << 32) | (csr::rtio::now_lo_read() as i64)
+        }
+    }
+
+    /// Sets the timeline cursor to a value returned by `save_cursor`.
+    pub extern fn restore_cursor(timestamp: i64) {
+        unsafe {
+            csr::rtio::now_hi_write((timestamp >> 32) as u32);
+            csr::rtio::now_lo_write(timestamp as u32);
+        }
+    }
+
+    /// Moves the timeline cursor to `timestamp` if it is later, e.g. to join a
+    /// sequence that ran from a saved cursor, and returns the new cursor.
+    pub extern fn max_cursor(timestamp: i64) -> i64 {
+        let now = save_cursor();
+        if timestamp > now {
+            restore_cursor(timestamp);
+            timestamp
+        } else {
+            now
+        }
+    }
+
+    /// Returns the period of the RTIO coarse clock, which is the system
+    /// clock, and the number of fine timestamp bits within it.
+    pub extern fn resolution() -> Resolution {
+        let frequency = csr::CONFIG_CLOCK_FREQUENCY as i64;
+        Resolution {
+            coarse_period_ps: (1_000_000_000_000 + frequency / 2) / frequency,
+            fine_bits: unsafe { csr::rtio::fine_ts_width_read() as i64 },
+        }
+    }
+
+    fn fine_bits() -> u32 {
+        unsafe { csr::rtio::fine_ts_width_read() as u32 }
+    }
+
+    /// Returns the number of machine units in one second.
+    fn mu_per_second() -> f64 {
+        (csr::CONFIG_CLOCK_FREQUENCY as f64) * ((1u64 << fine_bits()) as f64)
+    }
+
+    /// Converts machine units to coarse RTIO clock cycles, rounding down.
+    pub extern fn mu_to_coarse(mu: i64) -> i64 {
+        mu >> fine_bits()
+    }
+
+    /// Converts coarse RTIO clock cycles to machine units.
+    pub extern fn coarse_to_mu(coarse: i64) -> i64 {
+        coarse << fine_bits()
+    }
+
+    /// Converts seconds to machine units, rounding to the nearest one.
+    pub extern fn seconds_to_mu(seconds: f64) -> i64 {
+        let mu = seconds * mu_per_second();
+        if mu < 0.0 { (mu - 0.5) as i64 } else { (mu + 0.5) as i64 }
+    }
+
+    pub extern fn mu_to_seconds(mu: i64) -> f64 {
+        mu as f64 / mu_per_second()
+    }
+
     // writing the LSB of o_data (offset=0) triggers the RTIO write
     #[inline(always)]
     pub unsafe fn rtio_o_data_write(offset: usize, data: u32) {
@@ -65,7 +322,7 @@ mod imp {
         if status & RTIO_O_STATUS_WAIT != 0 {
             while csr::rtio::o_status_read() & RTIO_O_STATUS_WAIT != 0 {}
         }
-        if status & RTIO_O_STATUS_UNDERFLOW != 0 {
+        if status & RTIO_O_STATUS_UNDERFLOW != 0 && !drop_late_event() {
             raise!("RTIOUnderflow",
                 "RTIO underflow at channel {rtio_channel_info:0}, {1} mu, slack {2} mu",
                 channel as i64, timestamp, timestamp - get_counter());
@@ -77,8 +334,18 @@ mod imp {
         }
     }
 
+    #[inline(always)]
+    unsafe fn track_slack() {
+        let now = ((csr::rtio::now_hi_read() as i64) << 32) | (csr::rtio::now_lo_read() as i64);
+        let slack = now - get_counter();
+        if slack < MIN_SLACK {
+            MIN_SLACK = slack;
+        }
+    }
+
     pub extern fn output(target: i32, data: i32) {
         unsafe {
+            track_slack();
             csr::rtio::target_write(target as u32);
             // writing target clears o_data
             rtio_o_data_write(0, data as _);
@@ -91,6 +358,7 @@ mod imp {
 
     pub extern fn output_wide(target: i32, data: &CSlice<i32>) {
         unsafe {
+            track_slack();
             csr::rtio::target_write(target as u32);
             // writing target clears o_data
             for i in (0..data.len()).rev() {
@@ -103,21 +371,49 @@ mod imp {
         }
     }
 
-    pub extern fn input_timestamp(timeout: i64, channel: i32) -> i64 {
-        unsafe {
+    /// Writes the number of events waiting in each SED lane of the local RTIO
+    /// core into `out`, as many lanes as fit, and returns the number of lanes.
+    /// Events spread unevenly over the lanes cause underflows before the
+    /// FIFOs are full. Returns 0 on a satellite, whose lanes are not exposed.
+    pub extern fn sed_lane_levels(out: &mut CMutSlice<i32>) -> i32 {
+        send(&RtioSedLaneLevelsRequest);
+        recv!(&RtioSedLaneLevelsReply { levels } => {
+            for (level, &lane_level) in out.as_mut_slice().iter_mut().zip(levels.iter()) {
+                *level = lane_level as i32;
+            }
+            levels.len() as i32
+        })
+    }
+
+    /// Reads the next input event of `channel`, waiting until `timeout`, and
+    /// returns the status of the read. An overflow raises `RTIOOverflow`,
+    /// unless the channel has sticky overflows, in which case the read is
+    /// retried with the events that were kept.
+    unsafe fn input_status(channel: i32, timeout: u64) -> u8 {
+        loop {
             csr::rtio::target_write((channel as u32) << 8);
-            csr::rtio::i_timeout_write(timeout as u64);
+            csr::rtio::i_timeout_write(timeout);
 
             let mut status = RTIO_I_STATUS_WAIT_STATUS;
             while status & RTIO_I_STATUS_WAIT_STATUS != 0 {
                 status = csr::rtio::i_status_read();
             }
 
-            if status & RTIO_I_STATUS_OVERFLOW != 0 {
+            if status & RTIO_I_STATUS_OVERFLOW == 0 {
+                return status
+            }
+            // reading the overflow status clears it
+            if !note_input_overflow(channel) {
                 raise!("RTIOOverflow",
                     "RTIO input overflow on channel {rtio_channel_info:0}",
                     channel as i64, 0, 0);
             }
+        }
+    }
+
+    pub extern fn input_timestamp(timeout: i64, channel: i32) -> i64 {
+        unsafe {
+            let status = input_status(channel, timeout as u64);
             if status & RTIO_I_STATUS_WAIT_EVENT != 0 {
                 return -1
             }
@@ -133,19 +429,7 @@ mod imp {
 
     pub extern fn input_data(channel: i32) -> i32 {
         unsafe {
-            csr::rtio::target_write((channel as u32) << 8);
-            csr::rtio::i_timeout_write(0xffffffff_ffffffff);
-
-            let mut status = RTIO_I_STATUS_WAIT_STATUS;
-            while status & RTIO_I_STATUS_WAIT_STATUS != 0 {
-                status = csr::rtio::i_status_read();
-            }
-
-            if status & RTIO_I_STATUS_OVERFLOW != 0 {
-                raise!("RTIOOverflow",
-                    "RTIO input overflow on channel {rtio_channel_info:0}",
-                    channel as i64, 0, 0);
-            }
+            let status = input_status(channel, 0xffffffff_ffffffff);
             if status & RTIO_I_STATUS_DESTINATION_UNREACHABLE != 0 {
                 raise!("RTIODestinationUnreachable",
                     "RTIO destination unreachable, input, on channel {rtio_channel_info:0}",
@@ -158,19 +442,7 @@ mod imp {
 
     pub extern fn input_timestamped_data(timeout: i64, channel: i32) -> TimestampedData {
         unsafe {
-            csr::rtio::target_write((channel as u32) << 8);
-            csr::rtio::i_timeout_write(timeout as u64);
-
-            let mut status = RTIO_I_STATUS_WAIT_STATUS;
-            while status & RTIO_I_STATUS_WAIT_STATUS != 0 {
-                status = csr::rtio::i_status_read();
-            }
-
-            if status & RTIO_I_STATUS_OVERFLOW != 0 {
-                raise!("RTIOOverflow",
-                    "RTIO input overflow on channel {rtio_channel_info:0}",
-                    channel as i64, 0, 0);
-            }
+            let status = input_status(channel, timeout as u64);
             if status & RTIO_I_STATUS_WAIT_EVENT != 0 {
                 return TimestampedData { timestamp: -1, data: 0 }
             }
@@ -216,8 +488,8 @@ mod imp {
 
 #[cfg(not(has_rtio))]
 mod imp {
-    use cslice::CSlice;
-    use rtio::TimestampedData;
+    use cslice::{CSlice, CMutSlice};
+    use rtio::{TimestampedData, Resolution};
 
     pub extern fn init() {
         unimplemented!("not(has_rtio)")
@@ -227,10 +499,50 @@ mod imp {
         unimplemented!("not(has_rtio)")
     }
 
+    pub extern fn drtio_link_up(_destination: i32) -> bool {
+        unimplemented!("not(has_rtio)")
+    }
+
     pub extern fn get_counter() -> i64 {
         unimplemented!("not(has_rtio)")
     }
 
+    pub extern fn wait_until(_target_mu: i64) -> i64 {
+        unimplemented!("not(has_rtio)")
+    }
+
+    pub extern fn save_cursor() -> i64 {
+        unimplemented!("not(has_rtio)")
+    }
+
+    pub extern fn restore_cursor(_timestamp: i64) {
+        unimplemented!("not(has_rtio)")
+    }
+
+    pub extern fn max_cursor(_timestamp: i64) -> i64 {
+        unimplemented!("not(has_rtio)")
+    }
+
+    pub extern fn resolution() -> Resolution {
+        unimplemented!("not(has_rtio)")
+    }
+
+    pub extern fn mu_to_coarse(_mu: i64) -> i64 {
+        unimplemented!("not(has_rtio)")
+    }
+
+    pub extern fn coarse_to_mu(_coarse: i64) -> i64 {
+        unimplemented!("not(has_rtio)")
+    }
+
+    pub extern fn seconds_to_mu(_seconds: f64) -> i64 {
+        unimplemented!("not(has_rtio)")
+    }
+
+    pub extern fn mu_to_seconds(_mu: i64) -> f64 {
+        unimplemented!("not(has_rtio)")
+    }
+
     pub extern fn output(_target: i32, _data: i32) {
         unimplemented!("not(has_rtio)")
     }
@@ -239,6 +551,10 @@ mod imp {
         unimplemented!("not(has_rtio)")
     }
 
+    pub extern fn sed_lane_levels(_out: &mut CMutSlice<i32>) -> i32 {
+        unimplemented!("not(has_rtio)")
+    }
+
     pub extern fn input_timestamp(_timeout: i64, _channel: i32) -> i64 {
         unimplemented!("not(has_rtio)")
     }
@@ -0,0 +1,39 @@
+//! Optional soft limit on the stack depth of a kernel.
+//!
+//! `check` compares the stack pointer with the limit and raises a catchable
+//! `RecursionError` if the stack has grown past it, so that deep recursion
+//! can be recovered from before it reaches the stack guard, which aborts the
+//! whole run. The kernel calls it at its recursion points.
+
+extern {
+    static _fstack: u8;
+}
+
+// Lowest stack pointer allowed; 0 disables the limit.
+static mut STACK_LIMIT: usize = 0;
+
+pub fn reset() {
+    unsafe { STACK_LIMIT = 0 }
+}
+
+/// Limits the stack to `bytes` below its top. 0 disables the limit.
+pub extern fn set(bytes: usize) {
+    unsafe {
+        if bytes > 0 {
+            STACK_LIMIT = (&_fstack as *const u8 as usize).saturating_sub(bytes);
+        } else {
+            reset();
+        }
+    }
+}
+
+pub extern "C-unwind" fn check() {
+    let sp: usize;
+    unsafe {
+        asm!("mv {0}, sp", out(reg) sp, options(nomem, nostack, preserves_flags));
+        if sp < STACK_LIMIT {
+            raise!("RecursionError", "stack depth exceeds the limit of {0} bytes",
+                   (&_fstack as *const u8 as usize - STACK_LIMIT) as i64, 0, 0)
+        }
+    }
+}
@@ -0,0 +1,66 @@
+//! Software watchdog for the blocking loops of the kernel CPU.
+//!
+//! The deadline is expressed on the RTIO counter. It is checked by the loops
+//! that wait on the comms CPU or on the DMA engine, so a wedged kernel raises
+//! `WatchdogTimeout` and unwinds instead of spinning forever. The mailbox
+//! handshake in `send` is intentionally not checked, as abandoning it midway
+//! would leave the comms CPU with a half-acknowledged message.
+
+use rtio;
+
+static mut TIMEOUT: i64 = 0;
+static mut DEADLINE: i64 = 0;
+
+pub fn reset() {
+    unsafe {
+        TIMEOUT = 0;
+        DEADLINE = 0;
+    }
+}
+
+/// Arms the watchdog to expire `timeout_mu` from now. A non-positive
+/// timeout disarms it.
+pub extern fn set(timeout_mu: i64) {
+    unsafe {
+        if timeout_mu > 0 {
+            TIMEOUT = timeout_mu;
+            DEADLINE = rtio::get_counter() + timeout_mu;
+        } else {
+            reset();
+        }
+    }
+}
+
+/// Pushes the deadline back by the timeout given to `set`.
+pub extern fn pet() {
+    unsafe {
+        if TIMEOUT > 0 {
+            DEADLINE = rtio::get_counter() + TIMEOUT;
+        }
+    }
+}
+
+pub fn armed() -> bool {
+    unsafe { DEADLINE != 0 }
+}
+
+#[inline(always)]
+pub fn expired() -> bool {
+    unsafe { DEADLINE != 0 && rtio::get_counter() > DEADLINE }
+}
+
+pub fn expire() -> ! {
+    let timeout = unsafe { TIMEOUT };
+    // disarm first, so that the unwinding path does not trip it again
+    reset();
+    raise!("WatchdogTimeout",
+        "kernel watchdog expired after {0} mu",
+        timeout, 0, 0)
+}
+
+#[inline(always)]
+pub fn check() {
+    if expired() {
+        expire()
+    }
+}
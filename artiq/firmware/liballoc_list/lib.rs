@@ -34,6 +34,34 @@ impl ListAlloc {
     pub unsafe fn add_range(&mut self, begin: *mut u8, end: *mut u8) {
         self.add(begin, end as usize - begin as usize)
     }
+
+    /// Returns the size of the largest chunk that can be allocated, before
+    /// alignment padding. Runs of free chunks count as one, as `alloc` joins
+    /// them; the heap is not modified.
+    pub fn largest_free_block(&self) -> usize {
+        let header_size = mem::size_of::<Header>();
+        let mut largest = 0;
+        unsafe {
+            let mut curr = self.root;
+            while !curr.is_null() {
+                match (*curr).magic {
+                    MAGIC_BUSY => curr = (*curr).next,
+                    MAGIC_FREE => {
+                        let mut size = (*curr).size;
+                        let mut next = (*curr).next;
+                        while !next.is_null() && (*next).magic == MAGIC_FREE {
+                            size += (*next).size + header_size;
+                            next = (*next).next;
+                        }
+                        if size > largest { largest = size }
+                        curr = next;
+                    },
+                    _ => panic!("heap corruption detected at {:p}", curr)
+                }
+            }
+        }
+        largest
+    }
 }
 
 unsafe impl GlobalAlloc for ListAlloc {
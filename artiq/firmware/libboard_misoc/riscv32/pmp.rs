@@ -8,12 +8,23 @@ const PMP_X    : usize = 0b00000100;
 const PMP_W    : usize = 0b00000010;
 const PMP_R    : usize = 0b00000001;
 const PMP_OFF  : usize = 0b00000000;
+const PMP_A    : usize = 0b00011000;
+const PMP_TOR  : usize = 0b00001000;
+const PMP_NA4  : usize = 0b00010000;
+
+/// Number of PMP entries driven by the firmware.
+pub const ENTRY_COUNT: usize = 4;
 
 pub const STACK_GUARD_SIZE: usize = 0x1000;
 
+/// NAPOT regions are powers of two of at least 8 bytes, aligned to their size.
+pub fn is_valid_stack_guard(guard_base: usize, guard_size: usize) -> bool {
+    guard_size >= 8 && guard_size.is_power_of_two() && guard_base % guard_size == 0
+}
+
 #[inline(always)]
-pub unsafe fn init_stack_guard(guard_base: usize) {
-    pmpaddr2::write((guard_base >> 2) | ((0x1000 - 1) >> 3));
+pub unsafe fn init_stack_guard(guard_base: usize, guard_size: usize) {
+    pmpaddr2::write((guard_base >> 2) | ((guard_size - 1) >> 3));
     pmpcfg0::write((PMP_L | PMP_NAPOT) << 16);
 }
 
@@ -55,3 +66,43 @@ pub unsafe fn pop_pmp_region() {
         _ => unreachable!()
     }
 }
+
+/// An active PMP entry, as read back from the CSRs.
+#[derive(Debug, Clone, Copy)]
+pub struct Entry {
+    pub base: usize,
+    /// Zero if the region extends to the end of the address space.
+    pub size: usize,
+    /// Raw configuration byte (L, A, X, W, R bits).
+    pub cfg:  u8,
+}
+
+fn read_addr(index: usize) -> usize {
+    match index {
+        0 => pmpaddr0::read(),
+        1 => pmpaddr1::read(),
+        2 => pmpaddr2::read(),
+        3 => pmpaddr3::read(),
+        _ => unreachable!()
+    }
+}
+
+/// Decodes entry `index`, or returns `None` if it is off.
+pub fn read_entry(index: usize) -> Option<Entry> {
+    let cfg = (pmpcfg0::read() >> (8 * index)) & 0xff;
+    let addr = read_addr(index);
+    let (base, size) = match cfg & PMP_A {
+        PMP_TOR => {
+            let base = if index == 0 { 0 } else { read_addr(index - 1) << 2 };
+            (base, (addr << 2).wrapping_sub(base))
+        }
+        PMP_NA4 => (addr << 2, 4),
+        PMP_NAPOT => {
+            let trailing_ones = (!addr).trailing_zeros();
+            let mask = 1usize.checked_shl(trailing_ones).unwrap_or(0).wrapping_sub(1);
+            ((addr & !mask) << 2, 1usize.checked_shl(trailing_ones + 3).unwrap_or(0))
+        }
+        _ => return None
+    };
+    Some(Entry { base: base, size: size, cfg: cfg as u8 })
+}
@@ -111,6 +111,11 @@ pub struct Library<'a> {
 }
 
 impl<'a> Library<'a> {
+    /// Address the library is loaded at.
+    pub fn image_start(&self) -> Elf32_Addr {
+        self.image_off
+    }
+
     pub fn lookup(&self, name: &[u8]) -> Option<Elf32_Word> {
         let hash = elf_hash(name);
         let mut index = self.hash_bucket[hash as usize % self.hash_bucket.len()] as usize;
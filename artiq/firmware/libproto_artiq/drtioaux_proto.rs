@@ -163,6 +163,16 @@ pub enum Packet {
     CXPROIViewerDataRequest { destination: u8 },
     CXPROIViewerPixelDataReply { length: u16, data: [u64; CXP_PAYLOAD_MAX_SIZE_U64] },
     CXPROIViewerFrameDataReply { width: u16, height: u16, pixel_code: u16 },
+    CXPLinkStatusRequest { destination: u8 },
+    CXPLinkStatusReply { up: bool, lanes: u8, bitrate_code: u8 },
+    CXPCameraCountRequest { destination: u8 },
+    CXPCameraCountReply { count: u8 },
+    // Same as the requests above, for camera `cam` of the grabber; those address camera 0
+    CXPCamReadRequest { destination: u8, cam: u8, address: u32, length: u16 },
+    CXPCamWrite32Request { destination: u8, cam: u8, address: u32, value: u32 },
+    CXPCamROIViewerSetupRequest { destination: u8, cam: u8, x0: u16, y0: u16, x1: u16, y1: u16 },
+    CXPCamROIViewerDataRequest { destination: u8, cam: u8 },
+    CXPCamLinkStatusRequest { destination: u8, cam: u8 },
 }
 
 impl Packet {
@@ -597,6 +607,48 @@ impl Packet {
                 height: reader.read_u16()?,
                 pixel_code: reader.read_u16()?,
             },
+            0xed => Packet::CXPLinkStatusRequest {
+                destination: reader.read_u8()?,
+            },
+            0xee => Packet::CXPLinkStatusReply {
+                up: reader.read_bool()?,
+                lanes: reader.read_u8()?,
+                bitrate_code: reader.read_u8()?,
+            },
+            0xef => Packet::CXPCameraCountRequest {
+                destination: reader.read_u8()?,
+            },
+            0xf0 => Packet::CXPCameraCountReply {
+                count: reader.read_u8()?,
+            },
+            0xf1 => Packet::CXPCamReadRequest {
+                destination: reader.read_u8()?,
+                cam: reader.read_u8()?,
+                address: reader.read_u32()?,
+                length: reader.read_u16()?,
+            },
+            0xf2 => Packet::CXPCamWrite32Request {
+                destination: reader.read_u8()?,
+                cam: reader.read_u8()?,
+                address: reader.read_u32()?,
+                value: reader.read_u32()?,
+            },
+            0xf3 => Packet::CXPCamROIViewerSetupRequest {
+                destination: reader.read_u8()?,
+                cam: reader.read_u8()?,
+                x0: reader.read_u16()?,
+                y0: reader.read_u16()?,
+                x1: reader.read_u16()?,
+                y1: reader.read_u16()?,
+            },
+            0xf4 => Packet::CXPCamROIViewerDataRequest {
+                destination: reader.read_u8()?,
+                cam: reader.read_u8()?,
+            },
+            0xf5 => Packet::CXPCamLinkStatusRequest {
+                destination: reader.read_u8()?,
+                cam: reader.read_u8()?,
+            },
 
             ty => return Err(Error::UnknownPacket(ty))
         })
@@ -1062,6 +1114,78 @@ impl Packet {
                 writer.write_u16(height)?;
                 writer.write_u16(pixel_code)?;
             }
+            Packet::CXPLinkStatusRequest { destination } => {
+                writer.write_u8(0xed)?;
+                writer.write_u8(destination)?;
+            }
+            Packet::CXPLinkStatusReply {
+                up,
+                lanes,
+                bitrate_code,
+            } => {
+                writer.write_u8(0xee)?;
+                writer.write_bool(up)?;
+                writer.write_u8(lanes)?;
+                writer.write_u8(bitrate_code)?;
+            }
+            Packet::CXPCameraCountRequest { destination } => {
+                writer.write_u8(0xef)?;
+                writer.write_u8(destination)?;
+            }
+            Packet::CXPCameraCountReply { count } => {
+                writer.write_u8(0xf0)?;
+                writer.write_u8(count)?;
+            }
+            Packet::CXPCamReadRequest {
+                destination,
+                cam,
+                address,
+                length,
+            } => {
+                writer.write_u8(0xf1)?;
+                writer.write_u8(destination)?;
+                writer.write_u8(cam)?;
+                writer.write_u32(address)?;
+                writer.write_u16(length)?;
+            }
+            Packet::CXPCamWrite32Request {
+                destination,
+                cam,
+                address,
+                value,
+            } => {
+                writer.write_u8(0xf2)?;
+                writer.write_u8(destination)?;
+                writer.write_u8(cam)?;
+                writer.write_u32(address)?;
+                writer.write_u32(value)?;
+            }
+            Packet::CXPCamROIViewerSetupRequest {
+                destination,
+                cam,
+                x0,
+                y0,
+                x1,
+                y1,
+            } => {
+                writer.write_u8(0xf3)?;
+                writer.write_u8(destination)?;
+                writer.write_u8(cam)?;
+                writer.write_u16(x0)?;
+                writer.write_u16(y0)?;
+                writer.write_u16(x1)?;
+                writer.write_u16(y1)?;
+            }
+            Packet::CXPCamROIViewerDataRequest { destination, cam } => {
+                writer.write_u8(0xf4)?;
+                writer.write_u8(destination)?;
+                writer.write_u8(cam)?;
+            }
+            Packet::CXPCamLinkStatusRequest { destination, cam } => {
+                writer.write_u8(0xf5)?;
+                writer.write_u8(destination)?;
+                writer.write_u8(cam)?;
+            }
         }
         Ok(())
     }
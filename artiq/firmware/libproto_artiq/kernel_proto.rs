@@ -11,6 +11,22 @@ pub const KERNELCPU_LAST_ADDRESS:    usize = 0x4fffffff;
 // section in ksupport.elf.
 pub const KSUPPORT_HEADER_SIZE: usize = 0x74;
 
+// Number of asynchronous I2C reads that can be in flight at once.
+pub const I2C_ASYNC_READ_SLOTS: usize = 8;
+
+// Longest SPI transfer of a single wide request, as for wide RTIO outputs.
+pub const SPI_WIDE_MAX_WORDS: usize = 16;
+
+// Error codes of the I2C and SPI replies, reported to kernels in `I2CError` and `SPIError`.
+// Replies carry 0 if the transaction succeeded.
+pub const NRT_BUS_NO_BUS: u8 = 1;
+pub const NRT_BUS_NACK: u8 = 2;
+pub const NRT_BUS_STUCK: u8 = 3;
+pub const NRT_BUS_ARBITRATION_LOST: u8 = 4;
+pub const NRT_BUS_OTHER: u8 = 5;
+// Only raised by the kernel CPU, for addresses it cannot decode.
+pub const NRT_BUS_INVALID_ADDRESS: u8 = 6;
+
 #[derive(Debug)]
 pub enum SubkernelStatus<'a> {
     Timeout,
@@ -22,13 +38,30 @@ pub enum SubkernelStatus<'a> {
 
 #[derive(Debug)]
 pub enum Message<'a> {
-    LoadRequest(&'a [u8]),
+    LoadRequest {
+        library: &'a [u8],
+        destination: u8,
+        // maximum level of core log messages, encoded as `log::LevelFilter`
+        log_level: u8,
+        // CRC-32 the library must have, if known
+        expected_crc: Option<u32>
+    },
     LoadReply(Result<(), dyld::Error<'a>>),
 
+    MailboxSelftestRequest(u32),
+    MailboxSelftestReply(u32),
+
+    KeepaliveRequest,
+    KeepaliveReply { alive: bool },
+
     RtioInitRequest,
 
     RtioDestinationStatusRequest { destination: u8 },
     RtioDestinationStatusReply { up: bool },
+    DrtioLinkStatusRequest { destination: u8 },
+    DrtioLinkStatusReply { up: bool },
+    RtioSedLaneLevelsRequest,
+    RtioSedLaneLevelsReply { levels: &'a [u16] },
 
     DmaRecordStart(&'a str),
     DmaRecordAppend(&'a [u8]),
@@ -50,6 +83,11 @@ pub enum Message<'a> {
         uses_ddma: bool,
     },
 
+    DmaDump {
+        name:  &'a str,
+        trace: &'a [u8]
+    },
+
     DmaStartRemoteRequest {
         id: i32,
         timestamp: i64,
@@ -66,15 +104,28 @@ pub enum Message<'a> {
 
 
     RunFinished,
+    PartialResult(&'a [u8]),
     RunException {
         exceptions: &'a [Option<eh::eh_artiq::Exception<'a>>],
         stack_pointers: &'a [eh::eh_artiq::StackPointerBacktrace],
-        backtrace: &'a [(usize, usize)]
+        backtrace: &'a [(usize, usize, usize)]
     },
     RunAborted,
+    RunPanic {
+        file: &'a str,
+        line: u32,
+        column: u32,
+        message: Option<fmt::Arguments<'a>>
+    },
+    TimingReport {
+        modinit_cycles: u64,
+        recv_cycles: u64,
+        rpc_flush_cycles: u64
+    },
 
     RpcSend {
         async: bool,
+        format: u8,
         service: u32,
         tag: &'a [u8],
         data: *const *const ()
@@ -82,40 +133,73 @@ pub enum Message<'a> {
     RpcRecvRequest(*mut ()),
     RpcRecvReply(Result<usize, eh::eh_artiq::Exception<'a>>),
     RpcFlush,
+    PublishResult {
+        channel: u32,
+        tag: &'a [u8],
+        data: *const *const ()
+    },
 
     CacheGetRequest { key: &'a str },
     CacheGetReply   { value: *const CSlice<'static, i32> },
     CachePutRequest { key: &'a str, value: &'a [i32] },
     CachePutReply   { succeeded: bool },
+    CacheGetBytesRequest { key: &'a str },
+    CacheGetBytesReply   { value: *const CSlice<'static, u8> },
+    CachePutBytesRequest { key: &'a str, value: &'a [u8] },
+    CacheCasRequest { key: &'a str, expected: i32, new: i32 },
+    CacheCasReply   { succeeded: bool, swapped: bool },
 
     I2cStartRequest { busno: u32 },
     I2cRestartRequest { busno: u32 },
     I2cStopRequest { busno: u32 },
     I2cWriteRequest { busno: u32, data: u8 },
-    I2cWriteReply { succeeded: bool, ack: bool },
+    I2cWriteReply { error: u8, ack: bool },
     I2cReadRequest { busno: u32, ack: bool },
-    I2cReadReply { succeeded: bool, data: u8 },
-    I2cBasicReply { succeeded: bool },
+    I2cReadReply { error: u8, data: u8 },
+    I2cBasicReply { error: u8 },
     I2cSwitchSelectRequest { busno: u32, address: u8, mask: u8 },
+    // `address` is the 8-bit device address (LSB=0); registers and values are
+    // truncated to 8 bits. `failed` is the index of the element that failed,
+    // and its error code.
+    I2cWriteBatchRequest { busno: u32, address: u8, writes: &'a [(i32, i32)] },
+    I2cReadBatchRequest { busno: u32, address: u8, registers: &'a [i32] },
+    I2cBatchReply { failed: Option<(u32, u8)>, data: &'a [u8] },
+    // Acknowledged before the read is performed; the result is kept in `slot`
+    // until the next read into it, and fetched with `I2cReadCollectRequest`
+    // (answered with `I2cReadReply`).
+    I2cReadAsyncRequest { slot: u8, busno: u32, address: u8, register: u8 },
+    I2cReadCollectRequest { slot: u8 },
 
     SpiSetConfigRequest { busno: u32, flags: u8, length: u8, div: u8, cs: u8 },
     SpiWriteRequest { busno: u32, data: u32 },
     SpiReadRequest { busno: u32 },
-    SpiReadReply { succeeded: bool, data: u32 },
-    SpiBasicReply { succeeded: bool },
+    SpiReadReply { error: u8, data: u32 },
+    SpiBasicReply { error: u8 },
+    // At most `SPI_WIDE_MAX_WORDS` words. A wide read writes each word of
+    // `data` and replies with the words read back.
+    SpiWriteWideRequest { busno: u32, data: &'a [i32] },
+    SpiReadWideRequest { busno: u32, data: &'a [i32] },
+    SpiReadWideReply { error: u8, data: &'a [i32] },
 
     SubkernelLoadRunRequest { id: u32, destination: u8, run: bool, timestamp: u64 },
     SubkernelLoadRunReply { succeeded: bool },
+    SubkernelLoadGroupRequest { ids: &'a [u32], destination: u8 },
+    SubkernelLoadGroupReply { failed_id: Option<u32> },
+    SubkernelAvailableRequest { id: u32, destination: u8 },
+    SubkernelAvailableReply { available: bool },
     SubkernelAwaitFinishRequest { id: u32, timeout: i64 },
     SubkernelAwaitFinishReply,
     SubkernelMsgSend { id: u32, destination: Option<u8>, count: u8, tag: &'a [u8], data: *const *const () },
     SubkernelMsgRecvRequest { id: i32, timeout: i64, tags: &'a [u8] },
     SubkernelMsgRecvReply { count: u8 },
     SubkernelError(SubkernelStatus<'a>),
+    SubkernelCacheResultsRequest { id: u32, enable: bool },
+    SubkernelCacheClearRequest { id: u32 },
 
     CXPError(&'a str),
     CXPReadRequest {
         destination: u8,
+        cam: u8,
         address: u32,
         length: u16,
     },
@@ -125,12 +209,14 @@ pub enum Message<'a> {
     },
     CXPWrite32Request {
         destination: u8,
+        cam: u8,
         address: u32,
         value: u32,
     },
     CXPWrite32Reply,
     CXPROIViewerSetupRequest {
         destination: u8,
+        cam: u8,
         x0: u16,
         y0: u16,
         x1: u16,
@@ -139,7 +225,15 @@ pub enum Message<'a> {
     CXPROIViewerSetupReply,
     CXPROIViewerDataRequest {
         destination: u8,
+        cam: u8,
     },
+    // Like CXPROIViewerDataRequest, but answered with CXPROIViewerWaitReply
+    // instead of blocking when the grabber has no data yet
+    CXPROIViewerPollRequest {
+        destination: u8,
+        cam: u8,
+    },
+    CXPROIViewerWaitReply,
     CXPROIVIewerPixelDataReply {
         length: u16,
         data: [u64; CXP_PAYLOAD_MAX_SIZE_U64],
@@ -149,10 +243,30 @@ pub enum Message<'a> {
         height: u16,
         pixel_code: u16,
     },
+    CXPLinkStatusRequest {
+        destination: u8,
+        cam: u8,
+    },
+    CXPLinkStatusReply {
+        up: bool,
+        lanes: u8,
+        bitrate_code: u8,
+    },
+    CXPCameraCountRequest {
+        destination: u8,
+    },
+    CXPCameraCountReply {
+        count: u8,
+    },
     UpdateNow(u64),
 
     Log(fmt::Arguments<'a>),
-    LogSlice(&'a str)
+    LogSlice(&'a str),
+    LogBinary(&'a [u8]),
+
+    // Requests that only need to be acknowledged (logs and DMA record
+    // appends), handled in order with a single acknowledgement.
+    Batch(&'a [Message<'a>])
 }
 
 pub use self::Message::*;
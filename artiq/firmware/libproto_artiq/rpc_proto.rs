@@ -3,7 +3,7 @@ use core::slice;
 use cslice::{CSlice, CMutSlice};
 use byteorder::{NativeEndian, ByteOrder};
 use io::{ProtoRead, Read, Write, ProtoWrite, Error};
-use self::tag::{Tag, TagIterator, split_tag};
+use self::tag::{Tag, TagIterator, split_tag, skip_tag};
 
 #[inline]
 fn round_up(val: usize, power_of_two: usize) -> usize {
@@ -340,6 +340,11 @@ unsafe fn send_value<W>(writer: &mut W, tag: Tag, data: &mut *const (), write_ta
     }
 }
 
+/// RPC arguments are sent by `send_args`, each preceded by its tag only.
+pub const FORMAT_COMPACT: u8 = 0;
+/// RPC arguments are sent by `send_args_debug`, each also preceded by its size.
+pub const FORMAT_DEBUG: u8 = 1;
+
 pub fn send_args<W>(writer: &mut W, service: u32, tag_bytes: &[u8], data: *const *const (), write_tags: bool)
                    -> Result<(), Error<W::WriteError>>
     where W: Write + ?Sized
@@ -368,6 +373,81 @@ pub fn send_args<W>(writer: &mut W, service: u32, tag_bytes: &[u8], data: *const
     Ok(())
 }
 
+/// Same as `send_args` with the tags written, but each argument is preceded
+/// by the number of bytes it takes, and the arguments end with a size of 0
+/// instead of a zero tag. With the sizes, the host can report which argument
+/// it fails to decode, instead of misreading the arguments that follow.
+pub fn send_args_debug<W>(writer: &mut W, service: u32, tag_bytes: &[u8], data: *const *const ())
+                         -> Result<(), Error<W::WriteError>>
+    where W: Write + ?Sized
+{
+    let (arg_tags_bytes, return_tag_bytes) = split_tag(tag_bytes);
+
+    let mut args_it = TagIterator::new(arg_tags_bytes);
+    writer.write_u32(service)?;
+    for index in 0.. {
+        if let Some(arg_tag) = args_it.next() {
+            let data = unsafe { *data.offset(index) };
+            let size = count_bytes(|counter| unsafe {
+                send_value(counter, arg_tag, &mut data.clone(), true)
+            });
+            writer.write_u32(size as u32)?;
+            unsafe { send_value(writer, arg_tag, &mut data.clone(), true)? };
+        } else {
+            break
+        }
+    }
+    writer.write_u32(0)?;
+    writer.write_bytes(return_tag_bytes)?;
+
+    Ok(())
+}
+
+struct Counter(usize);
+
+impl Write for Counter {
+    type WriteError = ();
+    type FlushError = ();
+
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::WriteError> {
+        self.0 += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<(), Self::FlushError> {
+        Ok(())
+    }
+}
+
+fn count_bytes<F>(f: F) -> usize
+    where F: FnOnce(&mut Counter) -> Result<(), Error<()>>
+{
+    let mut counter = Counter(0);
+    match f(&mut counter) {
+        Ok(()) => counter.0,
+        Err(_) => unreachable!()
+    }
+}
+
+/// Checks that `tag_bytes` is a well-formed RPC tag, i.e. a sequence of
+/// argument tags followed by `:` and a single return tag, without panicking
+/// on truncated or unknown tags as `send_args` would.
+pub fn validate_tag(tag_bytes: &[u8]) -> bool {
+    let tag_separator = match tag_bytes.iter().position(|&b| b == b':') {
+        Some(position) => position,
+        None => return false
+    };
+    let (mut arg_tags_bytes, rest) = tag_bytes.split_at(tag_separator);
+    let mut return_tag_bytes = &rest[1..];
+
+    while !arg_tags_bytes.is_empty() {
+        if !skip_tag(&mut arg_tags_bytes) {
+            return false
+        }
+    }
+    skip_tag(&mut return_tag_bytes) && return_tag_bytes.is_empty()
+}
+
 mod tag {
     use core::fmt;
     use super::round_up;
@@ -382,6 +462,31 @@ mod tag {
         (arg_tags_bytes, return_tag_bytes)
     }
 
+    /// Advances `data` past one tag, returning false if it is truncated or
+    /// contains an unknown tag byte.
+    pub fn skip_tag(data: &mut &[u8]) -> bool {
+        let tag_byte = match data.split_first() {
+            Some((&tag_byte, rest)) => { *data = rest; tag_byte }
+            None => return false
+        };
+        match tag_byte {
+            b'n' | b'b' | b'i' | b'I' | b'f' | b's' | b'B' | b'A' | b'O' => true,
+            b't' => match data.split_first() {
+                Some((&count, rest)) => {
+                    *data = rest;
+                    (0..count).all(|_| skip_tag(data))
+                }
+                None => false
+            },
+            b'a' => match data.split_first() {
+                Some((_, rest)) => { *data = rest; skip_tag(data) }
+                None => false
+            },
+            b'l' | b'r' | b'k' => skip_tag(data),
+            _ => false
+        }
+    }
+
     #[derive(Debug, Clone, Copy)]
     pub enum Tag<'a> {
         None,
@@ -71,7 +71,8 @@ fn write_sync<W>(writer: &mut W) -> Result<(), IoError<W::WriteError>>
 pub enum Request {
     SystemInfo,
 
-    LoadKernel(Vec<u8>),
+    // The CRC-32 of the kernel is checked before it is linked, if given.
+    LoadKernel { kernel: Vec<u8>, crc: Option<u32> },
     RunKernel,
 
     RpcReply { tag: Vec<u8> },
@@ -105,11 +106,30 @@ pub enum Reply<'a> {
     KernelException {
         exceptions: &'a [Option<Exception<'a>>],
         stack_pointers: &'a [StackPointerBacktrace],
-        backtrace: &'a [(usize, usize)],
+        // only the IP and SP of each entry are sent
+        backtrace: &'a [(usize, usize, usize)],
         async_errors: u8
     },
+    // sent before a `KernelException` with the same backtrace
+    BacktraceFrameSizes {
+        backtrace: &'a [(usize, usize, usize)]
+    },
+    DmaDump {
+        name: &'a str,
+        trace: &'a [u8]
+    },
+    PartialResult {
+        data: &'a [u8]
+    },
+    KernelPanic {
+        file: &'a str,
+        line: u32,
+        column: u32,
+        message: &'a str
+    },
 
-    RpcRequest { async: bool },
+    RpcRequest { async: bool, debug: bool },
+    PublishResult,
 
     ClockFailure,
 }
@@ -122,7 +142,10 @@ impl Request {
         Ok(match reader.read_u8()? {
             3  => Request::SystemInfo,
 
-            5  => Request::LoadKernel(reader.read_bytes()?),
+            5  => Request::LoadKernel {
+                kernel: reader.read_bytes()?,
+                crc: None
+            },
             6  => Request::RunKernel,
 
             7  => Request::RpcReply {
@@ -144,6 +167,10 @@ impl Request {
                 destination: reader.read_u8()?,
                 kernel: reader.read_bytes()?
             },
+            10 => Request::LoadKernel {
+                kernel: reader.read_bytes()?,
+                crc: Some(reader.read_u32()?)
+            },
 
             ty  => return Err(Error::UnknownPacket(ty))
         })
@@ -225,17 +252,45 @@ impl<'a> Reply<'a> {
                 }
 
                 writer.write_u32(backtrace.len() as u32)?;
-                for &(addr, sp) in backtrace {
+                for &(addr, sp, _) in backtrace {
                     writer.write_u32(addr as u32)?;
                     writer.write_u32(sp as u32)?;
                 }
                 writer.write_u8(async_errors)?;
             },
+            Reply::BacktraceFrameSizes { backtrace } => {
+                writer.write_u8(21)?;
+                writer.write_u32(backtrace.len() as u32)?;
+                for &(_, _, frame_size) in backtrace {
+                    writer.write_u32(frame_size as u32)?;
+                }
+            },
+
+            Reply::DmaDump { name, trace } => {
+                writer.write_u8(17)?;
+                writer.write_string(name)?;
+                writer.write_bytes(trace)?;
+            },
+            Reply::PartialResult { data } => {
+                writer.write_u8(18)?;
+                writer.write_bytes(data)?;
+            },
 
-            Reply::RpcRequest { async } => {
-                writer.write_u8(10)?;
+            Reply::KernelPanic { file, line, column, message } => {
+                writer.write_u8(16)?;
+                writer.write_string(file)?;
+                writer.write_u32(line)?;
+                writer.write_u32(column)?;
+                writer.write_string(message)?;
+            },
+
+            Reply::RpcRequest { async, debug } => {
+                writer.write_u8(if debug { 19 } else { 10 })?;
                 writer.write_u8(async as u8)?;
             },
+            Reply::PublishResult => {
+                writer.write_u8(20)?;
+            },
 
             Reply::ClockFailure => {
                 writer.write_u8(15)?;
@@ -2,13 +2,13 @@ use alloc::{vec::Vec, string::String, collections::btree_map::BTreeMap};
 use cslice::{CSlice, AsCSlice};
 use core::mem::transmute;
 
-struct Entry {
-    data: Vec<i32>,
-    slice: CSlice<'static, i32>,
+struct Entry<T> {
+    data: Vec<T>,
+    slice: CSlice<'static, T>,
     borrowed: bool
 }
 
-impl core::fmt::Debug for Entry {
+impl<T: core::fmt::Debug> core::fmt::Debug for Entry<T> {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         f.debug_struct("Entry")
          .field("data", &self.data)
@@ -17,12 +17,14 @@ impl core::fmt::Debug for Entry {
     }
 }
 
-pub struct Cache {
-    entries: BTreeMap<String, Entry>,
-    empty: CSlice<'static, i32>,
+/// Kernel-accessible store of lists of `T`, shared by all the kernels run
+/// until reboot.
+pub struct Cache<T> {
+    entries: BTreeMap<String, Entry<T>>,
+    empty: CSlice<'static, T>,
 }
 
-impl core::fmt::Debug for Cache {
+impl<T: core::fmt::Debug> core::fmt::Debug for Cache<T> {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         f.debug_struct("Cache")
          .field("entries", &self.entries)
@@ -30,16 +32,16 @@ impl core::fmt::Debug for Cache {
     }
 }
 
-impl Cache {
-    pub fn new() -> Cache {
+impl<T: Copy> Cache<T> {
+    pub fn new() -> Cache<T> {
         let empty_vec = vec![];
         let empty = unsafe {
-            transmute::<CSlice<'_, i32>, CSlice<'static, i32>>(empty_vec.as_c_slice())
+            transmute::<CSlice<'_, T>, CSlice<'static, T>>(empty_vec.as_c_slice())
         };
         Cache { entries: BTreeMap::new(), empty }
     }
 
-    pub fn get(&mut self, key: &str) -> *const CSlice<'static, i32> {
+    pub fn get(&mut self, key: &str) -> *const CSlice<'static, T> {
         match self.entries.get_mut(key) {
             None => &self.empty,
             Some(ref mut entry) => {
@@ -49,14 +51,19 @@ impl Cache {
         }
     }
 
-    pub fn put(&mut self, key: &str, data: &[i32]) -> Result<(), ()> {
+    /// Returns the list stored under `key`, without lending it to the kernel.
+    pub fn peek(&self, key: &str) -> Option<&[T]> {
+        self.entries.get(key).map(|entry| &entry.data[..])
+    }
+
+    pub fn put(&mut self, key: &str, data: &[T]) -> Result<(), ()> {
         match self.entries.get_mut(key) {
             None => (),
             Some(ref mut entry) => {
                 if entry.borrowed { return Err(()) }
                 entry.data = Vec::from(data);
                 unsafe {
-                    entry.slice = transmute::<CSlice<'_, i32>, CSlice<'static, i32>>(
+                    entry.slice = transmute::<CSlice<'_, T>, CSlice<'static, T>>(
                         entry.data.as_c_slice());
                 }
                 return Ok(())
@@ -65,7 +72,7 @@ impl Cache {
 
         let data = Vec::from(data);
         let slice = unsafe {
-            transmute::<CSlice<'_, i32>, CSlice<'static, i32>>(data.as_c_slice())
+            transmute::<CSlice<'_, T>, CSlice<'static, T>>(data.as_c_slice())
         };
         self.entries.insert(String::from(key), Entry {
             data,
@@ -75,6 +82,39 @@ impl Cache {
         Ok(())
     }
 
+    /// Removes the lists stored under keys starting with `prefix`, except
+    /// those lent to the running kernel.
+    pub fn remove_prefix(&mut self, prefix: &str) {
+        let keys: Vec<String> = self.entries.iter()
+            .filter(|&(key, entry)| key.starts_with(prefix) && !entry.borrowed)
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in keys {
+            self.entries.remove(&key);
+        }
+    }
+
+    /// Replaces the first element of the list stored under `key` with `new`
+    /// if it equals `expected`, returning whether it did. A missing or empty
+    /// list counts as a first element of zero, and is replaced with `[new]`.
+    /// The rest of the list is left untouched.
+    pub fn compare_and_swap(&mut self, key: &str, expected: T, new: T) -> Result<bool, ()>
+            where T: PartialEq + Default {
+        match self.entries.get_mut(key) {
+            Some(ref entry) if entry.borrowed => Err(()),
+            Some(ref mut entry) if !entry.data.is_empty() => {
+                if entry.data[0] != expected { return Ok(false) }
+                entry.data[0] = new;
+                Ok(true)
+            }
+            _ => {
+                if expected != T::default() { return Ok(false) }
+                self.put(key, &[new])?;
+                Ok(true)
+            }
+        }
+    }
+
     pub unsafe fn unborrow(&mut self) {
         for (_key, entry) in self.entries.iter_mut() {
             entry.borrowed = false;
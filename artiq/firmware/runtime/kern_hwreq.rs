@@ -1,4 +1,5 @@
 use core::cell::RefCell;
+use alloc::vec::Vec;
 use kernel_proto as kern;
 use sched::{Io, Mutex, Error as SchedError};
 use session::{kern_acknowledge, kern_send, Error};
@@ -278,6 +279,54 @@ macro_rules! dispatch {
     }}
 }
 
+// Results of asynchronous reads, as the data or the error code.
+static mut ASYNC_READS: [Result<u8, u8>; kern::I2C_ASYNC_READ_SLOTS] =
+    [Err(kern::NRT_BUS_OTHER); kern::I2C_ASYNC_READ_SLOTS];
+
+fn i2c_error_code(error: local_i2c::Error) -> u8 {
+    match error {
+        local_i2c::Error::NoI2C | local_i2c::Error::InvalidBus => kern::NRT_BUS_NO_BUS,
+        local_i2c::Error::Nack => kern::NRT_BUS_NACK,
+        local_i2c::Error::SCLLow | local_i2c::Error::SDALow => kern::NRT_BUS_STUCK,
+        local_i2c::Error::ArbitrationLost => kern::NRT_BUS_ARBITRATION_LOST,
+        local_i2c::Error::IOExpanderError | local_i2c::Error::OtherError => kern::NRT_BUS_OTHER,
+    }
+}
+
+fn spi_error_code(error: local_spi::Error) -> u8 {
+    match error {
+        local_spi::Error::NoSPI | local_spi::Error::InvalidBus => kern::NRT_BUS_NO_BUS,
+        local_spi::Error::OtherError => kern::NRT_BUS_OTHER,
+    }
+}
+
+fn i2c_write_register(io: &Io, aux_mutex: &Mutex, ddma_mutex: &Mutex, subkernel_mutex: &Mutex,
+        routing_table: &drtio_routing::RoutingTable,
+        busno: u32, address: u8, register: u8, value: u8
+) -> Result<(), local_i2c::Error> {
+    dispatch!(io, aux_mutex, ddma_mutex, subkernel_mutex, local_i2c, remote_i2c, routing_table, busno, start)?;
+    let result = dispatch!(io, aux_mutex, ddma_mutex, subkernel_mutex, local_i2c, remote_i2c, routing_table, busno, write, address)
+        .and_then(|_| dispatch!(io, aux_mutex, ddma_mutex, subkernel_mutex, local_i2c, remote_i2c, routing_table, busno, write, register))
+        .and_then(|_| dispatch!(io, aux_mutex, ddma_mutex, subkernel_mutex, local_i2c, remote_i2c, routing_table, busno, write, value));
+    // release the bus even if the device did not acknowledge
+    let stopped = dispatch!(io, aux_mutex, ddma_mutex, subkernel_mutex, local_i2c, remote_i2c, routing_table, busno, stop);
+    result.and(stopped).map(|_| ())
+}
+
+fn i2c_read_register(io: &Io, aux_mutex: &Mutex, ddma_mutex: &Mutex, subkernel_mutex: &Mutex,
+        routing_table: &drtio_routing::RoutingTable,
+        busno: u32, address: u8, register: u8
+) -> Result<u8, local_i2c::Error> {
+    dispatch!(io, aux_mutex, ddma_mutex, subkernel_mutex, local_i2c, remote_i2c, routing_table, busno, start)?;
+    let result = dispatch!(io, aux_mutex, ddma_mutex, subkernel_mutex, local_i2c, remote_i2c, routing_table, busno, write, address)
+        .and_then(|_| dispatch!(io, aux_mutex, ddma_mutex, subkernel_mutex, local_i2c, remote_i2c, routing_table, busno, write, register))
+        .and_then(|_| dispatch!(io, aux_mutex, ddma_mutex, subkernel_mutex, local_i2c, remote_i2c, routing_table, busno, restart))
+        .and_then(|_| dispatch!(io, aux_mutex, ddma_mutex, subkernel_mutex, local_i2c, remote_i2c, routing_table, busno, write, address | 1))
+        .and_then(|_| dispatch!(io, aux_mutex, ddma_mutex, subkernel_mutex, local_i2c, remote_i2c, routing_table, busno, read, false));
+    let stopped = dispatch!(io, aux_mutex, ddma_mutex, subkernel_mutex, local_i2c, remote_i2c, routing_table, busno, stop);
+    result.and_then(|data| stopped.map(|_| data))
+}
+
 pub fn process_kern_hwreq(io: &Io, aux_mutex: &Mutex, ddma_mutex: &Mutex, subkernel_mutex: &Mutex,
         routing_table: &drtio_routing::RoutingTable,
         _up_destinations: &Urc<RefCell<[bool; drtio_routing::DEST_COUNT]>>,
@@ -300,52 +349,138 @@ pub fn process_kern_hwreq(io: &Io, aux_mutex: &Mutex, ddma_mutex: &Mutex, subker
             kern_send(io, &kern::RtioDestinationStatusReply { up: up })
         }
 
+        &kern::DrtioLinkStatusRequest { destination } => {
+            // a destination without a route is never up
+            let routed = routing_table.0.get(destination as usize)
+                .map_or(false, |hops| hops[0] != drtio_routing::INVALID_HOP);
+            #[cfg(has_drtio)]
+            let up = routed && _up_destinations.borrow()[destination as usize];
+            #[cfg(not(has_drtio))]
+            let up = routed || destination == 0;
+            kern_send(io, &kern::DrtioLinkStatusReply { up: up })
+        }
+
+        &kern::RtioSedLaneLevelsRequest => {
+            let levels = rtio_mgt::sed_lane_levels();
+            kern_send(io, &kern::RtioSedLaneLevelsReply { levels: &levels })
+        }
+
         &kern::I2cStartRequest { busno } => {
-            let succeeded = dispatch!(io, aux_mutex, ddma_mutex, subkernel_mutex, local_i2c, remote_i2c, routing_table, busno, start).is_ok();
-            kern_send(io, &kern::I2cBasicReply { succeeded: succeeded })
+            let error = dispatch!(io, aux_mutex, ddma_mutex, subkernel_mutex, local_i2c, remote_i2c, routing_table, busno, start).err().map_or(0, i2c_error_code);
+            kern_send(io, &kern::I2cBasicReply { error: error })
         }
         &kern::I2cRestartRequest { busno } => {
-            let succeeded = dispatch!(io, aux_mutex, ddma_mutex, subkernel_mutex, local_i2c, remote_i2c, routing_table, busno, restart).is_ok();
-            kern_send(io, &kern::I2cBasicReply { succeeded: succeeded })
+            let error = dispatch!(io, aux_mutex, ddma_mutex, subkernel_mutex, local_i2c, remote_i2c, routing_table, busno, restart).err().map_or(0, i2c_error_code);
+            kern_send(io, &kern::I2cBasicReply { error: error })
         }
         &kern::I2cStopRequest { busno } => {
-            let succeeded = dispatch!(io, aux_mutex, ddma_mutex, subkernel_mutex, local_i2c, remote_i2c, routing_table, busno, stop).is_ok();
-            kern_send(io, &kern::I2cBasicReply { succeeded: succeeded })
+            let error = dispatch!(io, aux_mutex, ddma_mutex, subkernel_mutex, local_i2c, remote_i2c, routing_table, busno, stop).err().map_or(0, i2c_error_code);
+            kern_send(io, &kern::I2cBasicReply { error: error })
         }
         &kern::I2cWriteRequest { busno, data } => {
             match dispatch!(io, aux_mutex, ddma_mutex, subkernel_mutex, local_i2c, remote_i2c, routing_table, busno, write, data) {
-                Ok(()) => kern_send(io, &kern::I2cWriteReply { succeeded: true, ack: true }),
-                Err(local_i2c::Error::Nack) => kern_send(io, &kern::I2cWriteReply { succeeded: true, ack: false }),
-                Err(_) => kern_send(io, &kern::I2cWriteReply { succeeded: false, ack: false })
+                Ok(()) => kern_send(io, &kern::I2cWriteReply { error: 0, ack: true }),
+                Err(local_i2c::Error::Nack) => kern_send(io, &kern::I2cWriteReply { error: 0, ack: false }),
+                Err(e) => kern_send(io, &kern::I2cWriteReply { error: i2c_error_code(e), ack: false })
             }
         }
         &kern::I2cReadRequest { busno, ack } => {
             match dispatch!(io, aux_mutex, ddma_mutex, subkernel_mutex, local_i2c, remote_i2c, routing_table, busno, read, ack) {
-                Ok(data) => kern_send(io, &kern::I2cReadReply { succeeded: true, data: data }),
-                Err(_) => kern_send(io, &kern::I2cReadReply { succeeded: false, data: 0xff })
+                Ok(data) => kern_send(io, &kern::I2cReadReply { error: 0, data: data }),
+                Err(e) => kern_send(io, &kern::I2cReadReply { error: i2c_error_code(e), data: 0xff })
             }
         }
         &kern::I2cSwitchSelectRequest { busno, address, mask } => {
-            let succeeded = dispatch!(io, aux_mutex, ddma_mutex, subkernel_mutex, local_i2c, remote_i2c, routing_table, busno,
-                switch_select, address, mask).is_ok();
-            kern_send(io, &kern::I2cBasicReply { succeeded: succeeded })
+            let error = dispatch!(io, aux_mutex, ddma_mutex, subkernel_mutex, local_i2c, remote_i2c, routing_table, busno,
+                switch_select, address, mask).err().map_or(0, i2c_error_code);
+            kern_send(io, &kern::I2cBasicReply { error: error })
+        }
+        &kern::I2cWriteBatchRequest { busno, address, writes } => {
+            let mut failed = None;
+            for (index, &(register, value)) in writes.iter().enumerate() {
+                if let Err(e) = i2c_write_register(io, aux_mutex, ddma_mutex, subkernel_mutex, routing_table,
+                        busno, address, register as u8, value as u8) {
+                    failed = Some((index as u32, i2c_error_code(e)));
+                    break
+                }
+            }
+            kern_send(io, &kern::I2cBatchReply { failed: failed, data: &[] })
+        }
+        &kern::I2cReadBatchRequest { busno, address, registers } => {
+            let mut data = Vec::with_capacity(registers.len());
+            let mut failed = None;
+            for (index, &register) in registers.iter().enumerate() {
+                match i2c_read_register(io, aux_mutex, ddma_mutex, subkernel_mutex, routing_table,
+                        busno, address, register as u8) {
+                    Ok(value) => data.push(value),
+                    Err(e) => {
+                        failed = Some((index as u32, i2c_error_code(e)));
+                        break
+                    }
+                }
+            }
+            kern_send(io, &kern::I2cBatchReply { failed: failed, data: &data })
+        }
+        &kern::I2cReadAsyncRequest { slot, busno, address, register } => {
+            // let the kernel run on while the bus is busy
+            kern_acknowledge()?;
+            let data = i2c_read_register(io, aux_mutex, ddma_mutex, subkernel_mutex, routing_table,
+                busno, address, register).map_err(i2c_error_code);
+            unsafe { ASYNC_READS[slot as usize] = data }
+            Ok(())
+        }
+        &kern::I2cReadCollectRequest { slot } => {
+            match unsafe { ASYNC_READS[slot as usize] } {
+                Ok(data) => kern_send(io, &kern::I2cReadReply { error: 0, data: data }),
+                Err(error) => kern_send(io, &kern::I2cReadReply { error: error, data: 0xff })
+            }
         }
 
         &kern::SpiSetConfigRequest { busno, flags, length, div, cs } => {
-            let succeeded = dispatch!(io, aux_mutex, ddma_mutex, subkernel_mutex, local_spi, remote_spi, routing_table, busno,
-                set_config, flags, length, div, cs).is_ok();
-            kern_send(io, &kern::SpiBasicReply { succeeded: succeeded })
+            let error = dispatch!(io, aux_mutex, ddma_mutex, subkernel_mutex, local_spi, remote_spi, routing_table, busno,
+                set_config, flags, length, div, cs).err().map_or(0, spi_error_code);
+            kern_send(io, &kern::SpiBasicReply { error: error })
         },
         &kern::SpiWriteRequest { busno, data } => {
-            let succeeded = dispatch!(io, aux_mutex, ddma_mutex, subkernel_mutex, local_spi, remote_spi, routing_table, busno,
-                write, data).is_ok();
-            kern_send(io, &kern::SpiBasicReply { succeeded: succeeded })
+            let error = dispatch!(io, aux_mutex, ddma_mutex, subkernel_mutex, local_spi, remote_spi, routing_table, busno,
+                write, data).err().map_or(0, spi_error_code);
+            kern_send(io, &kern::SpiBasicReply { error: error })
         }
         &kern::SpiReadRequest { busno } => {
             match dispatch!(io, aux_mutex, ddma_mutex, subkernel_mutex, local_spi, remote_spi, routing_table, busno, read) {
-                Ok(data) => kern_send(io, &kern::SpiReadReply { succeeded: true, data: data }),
-                Err(_) => kern_send(io, &kern::SpiReadReply { succeeded: false, data: 0 })
+                Ok(data) => kern_send(io, &kern::SpiReadReply { error: 0, data: data }),
+                Err(e) => kern_send(io, &kern::SpiReadReply { error: spi_error_code(e), data: 0 })
+            }
+        }
+        // The SPI cores have no burst mode, so wide transfers go word by word.
+        &kern::SpiWriteWideRequest { busno, data } => {
+            let mut error = 0;
+            for &word in data.iter() {
+                if let Err(e) = dispatch!(io, aux_mutex, ddma_mutex, subkernel_mutex, local_spi, remote_spi, routing_table, busno,
+                        write, word as u32) {
+                    error = spi_error_code(e);
+                    break
+                }
+            }
+            kern_send(io, &kern::SpiBasicReply { error: error })
+        }
+        &kern::SpiReadWideRequest { busno, data } => {
+            let mut words = Vec::with_capacity(data.len());
+            let mut error = 0;
+            for &word in data.iter() {
+                let result = dispatch!(io, aux_mutex, ddma_mutex, subkernel_mutex, local_spi, remote_spi, routing_table, busno,
+                        write, word as u32)
+                    .and_then(|_| dispatch!(io, aux_mutex, ddma_mutex, subkernel_mutex, local_spi, remote_spi, routing_table, busno,
+                        read));
+                match result {
+                    Ok(read) => words.push(read as i32),
+                    Err(e) => {
+                        error = spi_error_code(e);
+                        break
+                    }
+                }
             }
+            kern_send(io, &kern::SpiReadWideReply { error: error, data: &words })
         }
 
         _ => return Ok(false)
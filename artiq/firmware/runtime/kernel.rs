@@ -91,7 +91,7 @@ pub fn validate(ptr: usize) -> bool {
 
 #[cfg(has_drtio)]
 pub mod subkernel {
-    use alloc::{vec::Vec, collections::btree_map::BTreeMap};
+    use alloc::{vec::Vec, string::String, collections::btree_map::BTreeMap};
     use board_artiq::drtio_routing::RoutingTable;
     use board_misoc::clock;
     use proto_artiq::{drtioaux_proto::{PayloadStatus, MASTER_PAYLOAD_MAX_SIZE}, rpc_proto as rpc};
@@ -162,7 +162,10 @@ pub mod subkernel {
     struct Subkernel {
         pub destination: u8,
         pub data: Vec<u8>,
-        pub state: SubkernelState
+        pub state: SubkernelState,
+        pub cache_results: bool,
+        // cache key and arguments of the run whose result is awaited
+        pub result_key: Option<(String, Vec<u8>)>
     }
 
     impl Subkernel {
@@ -170,12 +173,17 @@ pub mod subkernel {
             Subkernel {
                 destination: destination,
                 data: data,
-                state: SubkernelState::NotLoaded
+                state: SubkernelState::NotLoaded,
+                cache_results: false,
+                result_key: None
             }
         }
     }
 
     static mut SUBKERNELS: BTreeMap<u32, Subkernel> = BTreeMap::new();
+    // id and timestamp of a run held back until its arguments are known;
+    // they are sent right after the run, so there is at most one
+    static mut DEFERRED_RUN: Option<(u32, u64)> = None;
 
     pub fn add_subkernel(io: &Io, subkernel_mutex: &Mutex, id: u32, destination: u8, kernel: Vec<u8>) -> Result<(), Error> {
         let _lock = subkernel_mutex.lock(io)?;
@@ -201,6 +209,11 @@ pub mod subkernel {
             error!("for id: {} expected Uploaded, got: {:?}", id, subkernel.state);
             return Err(Error::IncorrectState);
         }
+        if run && subkernel.cache_results {
+            // the result may be cached, which is only known with the arguments
+            unsafe { DEFERRED_RUN = Some((id, timestamp)) }
+            return Ok(())
+        }
         drtio::subkernel_load(io, aux_mutex, ddma_mutex, subkernel_mutex, 
             routing_table, id, subkernel.destination, run, timestamp)?;
         if run {
@@ -209,10 +222,133 @@ pub mod subkernel {
         Ok(())
     }
 
+    /// Checks that every subkernel of `ids`, a subkernel and the subkernels
+    /// it calls, has been uploaded, then preloads the first one to
+    /// `destination`. Nothing is loaded unless all of them pass; returns the
+    /// id of the first one that did not.
+    pub fn load_group(io: &Io, aux_mutex: &Mutex, ddma_mutex: &Mutex, subkernel_mutex: &Mutex,
+            routing_table: &RoutingTable, ids: &[u32], destination: u8) -> Result<Option<u32>, Error> {
+        let _lock = subkernel_mutex.lock(io)?;
+        let entry = match ids.first() {
+            Some(&id) => id,
+            None => return Ok(None)
+        };
+        for &id in ids {
+            match unsafe { SUBKERNELS.get(&id) } {
+                Some(subkernel) if subkernel.state == SubkernelState::Uploaded &&
+                        (id != entry || subkernel.destination == destination) => (),
+                Some(subkernel) => {
+                    error!("for id: {} in group of {} expected Uploaded to {}, got: {:?} to {}",
+                        id, entry, destination, subkernel.state, subkernel.destination);
+                    return Ok(Some(id))
+                }
+                None => {
+                    error!("unknown subkernel id: {} in group of {}", id, entry);
+                    return Ok(Some(id))
+                }
+            }
+        }
+        match drtio::subkernel_load(io, aux_mutex, ddma_mutex, subkernel_mutex,
+                routing_table, entry, destination, false, 0) {
+            Ok(()) => Ok(None),
+            Err(e) => {
+                error!("error preloading subkernel {}: {}", entry, e);
+                Ok(Some(entry))
+            }
+        }
+    }
+
+    /// Returns whether subkernel `id` has been uploaded to `destination`.
+    pub fn is_uploaded(io: &Io, subkernel_mutex: &Mutex, id: u32, destination: u8) -> Result<bool, Error> {
+        let _lock = subkernel_mutex.lock(io)?;
+        Ok(unsafe { SUBKERNELS.get(&id) }.map_or(false, |subkernel|
+            subkernel.destination == destination && subkernel.state != SubkernelState::NotLoaded))
+    }
+
+    pub fn set_result_caching(io: &Io, subkernel_mutex: &Mutex, id: u32, enable: bool) -> Result<(), Error> {
+        let _lock = subkernel_mutex.lock(io)?;
+        match unsafe { SUBKERNELS.get_mut(&id) } {
+            Some(subkernel) => subkernel.cache_results = enable,
+            None => warn!("cannot cache the results of unknown subkernel #{}", id)
+        }
+        Ok(())
+    }
+
+    /// Prefix of the keys of the cached results of subkernel `id`.
+    pub fn result_cache_prefix(id: u32) -> String {
+        format!("subkernel:{}:", id)
+    }
+
+    fn fnv1a(data: &[u8]) -> u32 {
+        data.iter().fold(0x811c9dc5, |hash, &byte| (hash ^ byte as u32).wrapping_mul(0x01000193))
+    }
+
+    /// Returns the id of the subkernel whose run `load` is holding back, if any.
+    pub fn deferred_run_id() -> Option<u32> {
+        unsafe { DEFERRED_RUN.map(|(id, _)| id) }
+    }
+
+    /// Returns the timestamp of the run of subkernel `id` held back by `load`,
+    /// if any, along with the key its result is cached under for `args` (the
+    /// serialized argument message, empty without arguments). The run must
+    /// then be started with `run_deferred` or `finish_from_cache`.
+    pub fn take_deferred_run(io: &Io, subkernel_mutex: &Mutex, id: u32, args: &[u8]
+    ) -> Result<Option<(u64, String)>, Error> {
+        let _lock = subkernel_mutex.lock(io)?;
+        let timestamp = match unsafe { DEFERRED_RUN } {
+            Some((deferred_id, timestamp)) if deferred_id == id => timestamp,
+            _ => return Ok(None)
+        };
+        unsafe { DEFERRED_RUN = None }
+        Ok(unsafe { SUBKERNELS.get(&id) }.map(|subkernel| {
+            // the subkernel code is part of the key, as IDs are only unique to a kernel
+            (timestamp, format!("{}{:08x}:{:08x}", result_cache_prefix(id),
+                                fnv1a(&subkernel.data), fnv1a(args)))
+        }))
+    }
+
+    /// Starts a run held back by `load`, recording the cache key and
+    /// arguments its result is to be stored with.
+    pub fn run_deferred(io: &Io, aux_mutex: &Mutex, ddma_mutex: &Mutex, subkernel_mutex: &Mutex,
+            routing_table: &RoutingTable, id: u32, timestamp: u64, key: String, args: &[u8]
+    ) -> Result<(), Error> {
+        let _lock = subkernel_mutex.lock(io)?;
+        let subkernel = unsafe { SUBKERNELS.get_mut(&id).unwrap() };
+        drtio::subkernel_load(io, aux_mutex, ddma_mutex, subkernel_mutex,
+            routing_table, id, subkernel.destination, true, timestamp)?;
+        subkernel.state = SubkernelState::Running;
+        subkernel.result_key = Some((key, args.to_vec()));
+        Ok(())
+    }
+
+    /// Completes a run held back by `load` without running the subkernel,
+    /// queuing its cached return message, if it has one.
+    pub fn finish_from_cache(io: &Io, subkernel_mutex: &Mutex, id: u32, result: Option<(u8, &[u8])>
+    ) -> Result<(), Error> {
+        let _lock = subkernel_mutex.lock(io)?;
+        if let Some((count, data)) = result {
+            unsafe {
+                MESSAGE_QUEUE.push(Message { from_id: id, count: count, data: data.to_vec() });
+            }
+        }
+        let subkernel = unsafe { SUBKERNELS.get_mut(&id).unwrap() };
+        subkernel.state = SubkernelState::Finished { status: FinishStatus::Ok };
+        Ok(())
+    }
+
+    /// Returns the cache key and arguments of the result of subkernel `id`,
+    /// if it is to be cached and has not been yet.
+    pub fn take_result_key(io: &Io, subkernel_mutex: &Mutex, id: u32
+    ) -> Result<Option<(String, Vec<u8>)>, Error> {
+        let _lock = subkernel_mutex.lock(io)?;
+        Ok(unsafe { SUBKERNELS.get_mut(&id) }.and_then(|subkernel| subkernel.result_key.take()))
+    }
+
     pub fn clear_subkernels(io: &Io, subkernel_mutex: &Mutex) -> Result<(), Error> {
         let _lock = subkernel_mutex.lock(io)?;
         unsafe {
             SUBKERNELS = BTreeMap::new();
+            DEFERRED_RUN = None;
             MESSAGE_QUEUE = Vec::new();
             CURRENT_MESSAGES = BTreeMap::new();
         }
@@ -464,20 +600,28 @@ pub mod subkernel {
         }
     }
 
-    pub fn message_send<'a>(io: &Io, aux_mutex: &Mutex, ddma_mutex: &Mutex, subkernel_mutex: &Mutex,
-        routing_table: &RoutingTable, id: u32, destination: Option<u8>, count: u8, tag: &'a [u8], message: *const *const ()
-    ) -> Result<(), Error> {
+    /// Serializes a message as it is sent to a satellite: its argument count
+    /// followed by the arguments.
+    pub fn message_serialize<'a>(count: u8, tag: &'a [u8], message: *const *const ()) -> Result<Vec<u8>, Error> {
         let mut writer = Cursor::new(Vec::new());
         // reuse rpc code for sending arbitrary data
         rpc::send_args(&mut writer, 0, tag, message, false)?;
         // skip service tag, but overwrite first byte with tag count
+        let mut data = writer.into_inner().split_off(3);
+        data[0] = count;
+        Ok(data)
+    }
+
+    /// Sends a message serialized by `message_serialize`, to the destination of
+    /// subkernel `id` if `destination` is `None`.
+    pub fn message_send_serialized(io: &Io, aux_mutex: &Mutex, ddma_mutex: &Mutex, subkernel_mutex: &Mutex,
+        routing_table: &RoutingTable, id: u32, destination: Option<u8>, data: &[u8]
+    ) -> Result<(), Error> {
         let destination = destination.unwrap_or_else(|| {
                 let _lock = subkernel_mutex.lock(io).unwrap();
                 unsafe { SUBKERNELS.get(&id).unwrap().destination }
             }
         );
-        let data = &mut writer.into_inner()[3..];
-        data[0] = count;
         Ok(drtio::subkernel_send_message(
             io, aux_mutex, ddma_mutex, subkernel_mutex, routing_table, id, destination, data
         )?)
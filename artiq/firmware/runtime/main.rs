@@ -277,7 +277,7 @@ pub extern fn main() -> i32 {
         }
         ALLOC.add_range(&mut _fheap, &mut _eheap);
 
-        pmp::init_stack_guard(&_sstack_guard as *const u8 as usize);
+        pmp::init_stack_guard(&_sstack_guard as *const u8 as usize, pmp::STACK_GUARD_SIZE);
 
         #[cfg(soc_platform = "kasli")]
         irq::enable_interrupts();
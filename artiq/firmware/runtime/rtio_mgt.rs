@@ -1,4 +1,5 @@
 use core::cell::RefCell;
+use alloc::vec::Vec;
 use urc::Urc;
 use board_misoc::{csr, config};
 #[cfg(has_drtio)]
@@ -753,6 +754,16 @@ fn read_device_map() -> DeviceMap {
     device_map
 }
 
+/// Returns the number of events waiting in each SED lane of the RTIO core.
+pub fn sed_lane_levels() -> Vec<u16> {
+    unsafe {
+        (0..csr::rtio_core::sed_lane_count_read()).map(|lane| {
+            csr::rtio_core::sed_lane_select_write(lane);
+            csr::rtio_core::sed_lane_level_read()
+        }).collect()
+    }
+}
+
 fn toggle_sed_spread(val: u8) {
     unsafe { csr::rtio_core::sed_spread_enable_write(val); }
 }
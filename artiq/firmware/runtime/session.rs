@@ -23,6 +23,8 @@ use rtio_dma::remote_dma;
 use kernel::{subkernel, subkernel::Error as SubkernelError};
 #[cfg(has_drtio)]
 use rtio_mgt::drtio;
+#[cfg(has_drtio)]
+use board_misoc::clock;
 use rtio_mgt::get_async_errors;
 use cache::Cache;
 use kern_hwreq;
@@ -32,6 +34,10 @@ use rpc_proto as rpc;
 use session_proto as host;
 use kernel_proto as kern;
 
+// How long the satellite may keep answering CXPWaitReply before the access fails
+#[cfg(has_drtio)]
+const CXP_WAIT_TIMEOUT_MS: u64 = 5_000;
+
 #[derive(Fail, Debug)]
 pub enum Error<T> {
     #[fail(display = "cannot load kernel: {}", _0)]
@@ -131,7 +137,8 @@ macro_rules! unexpected {
 // Persistent state
 #[derive(Debug)]
 struct Congress {
-    cache: Cache,
+    cache: Cache<i32>,
+    bytes_cache: Cache<u8>,
     dma_manager: DmaManager,
     finished_cleanly: Cell<bool>
 }
@@ -140,6 +147,7 @@ impl Congress {
     fn new() -> Congress {
         Congress {
             cache: Cache::new(),
+            bytes_cache: Cache::new(),
             dma_manager: DmaManager::new(),
             finished_cleanly: Cell::new(true)
         }
@@ -199,7 +207,8 @@ fn host_read<R>(reader: &mut R) -> Result<host::Request, Error<R::ReadError>>
 {
     let request = host::Request::read_from(reader)?;
     match &request {
-        &host::Request::LoadKernel(_) => debug!("comm<-host LoadLibrary(...)"),
+        &host::Request::LoadKernel { kernel: _, crc } => debug!(
+            "comm<-host LoadLibrary(crc: {:?}, ...)", crc),
         &host::Request::UploadSubkernel { id, destination, kernel: _} => debug!(
             "comm<-host UploadSubkernel(id: {}, destination: {}, ...)", id, destination),
         _ => debug!("comm<-host {:?}", request)
@@ -214,9 +223,24 @@ fn host_write<W>(writer: &mut W, reply: host::Reply) -> Result<(), IoError<W::Wr
     reply.write_to(writer)
 }
 
+/// Sends an RPC request to the host, with the arguments serialized in
+/// `format` (see `rpc_proto::FORMAT_COMPACT`).
+fn host_send_rpc<W>(writer: &mut W, async: bool, format: u8, service: u32, tag: &[u8],
+                    data: *const *const ()) -> Result<(), IoError<W::WriteError>>
+    where W: Write + ?Sized
+{
+    let debug = format == rpc::FORMAT_DEBUG;
+    host_write(writer, host::Reply::RpcRequest { async: async, debug: debug })?;
+    if debug {
+        rpc::send_args_debug(writer, service, tag, data)
+    } else {
+        rpc::send_args(writer, service, tag, data, true)
+    }
+}
+
 pub fn kern_send(io: &Io, request: &kern::Message) -> Result<(), Error<SchedError>> {
     match request {
-        &kern::LoadRequest(_) => debug!("comm->kern LoadRequest(...)"),
+        &kern::LoadRequest { .. } => debug!("comm->kern LoadRequest(...)"),
         &kern::DmaRetrieveReply { trace, duration, uses_ddma } => {
             if trace.map(|data| data.len() > 100).unwrap_or(false) {
                 debug!("comm->kern DmaRetrieveReply {{ trace: ..., duration: {:?}, uses_ddma: {} }}", duration, uses_ddma)
@@ -245,6 +269,10 @@ fn kern_recv_dotrace(reply: &kern::Message) {
     match reply {
         &kern::Log(_) => debug!("comm<-kern Log(...)"),
         &kern::LogSlice(_) => debug!("comm<-kern LogSlice(...)"),
+        &kern::LogBinary(data) => debug!("comm<-kern LogBinary([_; {:#x}])", data.len()),
+        &kern::Batch(requests) => debug!("comm<-kern Batch([_; {}])", requests.len()),
+        &kern::DmaDump { name, trace } =>
+            debug!("comm<-kern DmaDump {{ name: {:?}, trace: [_; {:#x}] }}", name, trace.len()),
         &kern::DmaRecordAppend(data) => {
             if data.len() > 100 {
                 debug!("comm<-kern DmaRecordAppend([_; {:#x}])", data.len())
@@ -270,7 +298,7 @@ pub fn kern_acknowledge() -> Result<(), Error<SchedError>> {
     Ok(())
 }
 
-unsafe fn kern_load(io: &Io, session: &mut Session, library: &[u8])
+unsafe fn kern_load(io: &Io, session: &mut Session, library: &[u8], expected_crc: Option<u32>)
                    -> Result<(), Error<SchedError>> {
     if session.running() {
         unexpected!("attempted to load a new kernel while a kernel was running")
@@ -278,7 +306,12 @@ unsafe fn kern_load(io: &Io, session: &mut Session, library: &[u8])
 
     kernel::start();
 
-    kern_send(io, &kern::LoadRequest(&library))?;
+    kern_send(io, &kern::LoadRequest {
+        library: &library,
+        destination: 0,
+        log_level: ::log::max_level() as u8,
+        expected_crc: expected_crc
+    })?;
     kern_recv(io, |reply| {
         match reply {
             kern::LoadReply(Ok(())) => {
@@ -317,7 +350,7 @@ fn process_flash_kernel(io: &Io, _aux_mutex: &Mutex, _subkernel_mutex: &Mutex, _
         // assume ELF file, proceed as before
         unsafe {
             // make a copy as kernel CPU cannot read SPI directly
-            kern_load(io, session, Vec::from(kernel).as_ref())
+            kern_load(io, session, Vec::from(kernel).as_ref(), None)
         }
     } else {
         #[cfg(has_drtio)]
@@ -352,7 +385,7 @@ fn process_flash_kernel(io: &Io, _aux_mutex: &Mutex, _subkernel_mutex: &Mutex, _
                 }
             }
             unsafe {
-                kern_load(io, session, Vec::from(main_lib.unwrap()).as_ref())
+                kern_load(io, session, Vec::from(main_lib.unwrap()).as_ref(), None)
             }
         }
         #[cfg(not(has_drtio))]
@@ -374,8 +407,8 @@ fn process_host_message(io: &Io, _aux_mutex: &Mutex, _ddma_mutex: &Mutex, _subke
             session.congress.finished_cleanly.set(true)
         }
 
-        host::Request::LoadKernel(kernel) => {
-            match unsafe { kern_load(io, session, &kernel) } {
+        host::Request::LoadKernel { kernel, crc } => {
+            match unsafe { kern_load(io, session, &kernel, crc) } {
                 Ok(()) => host_write(stream, host::Reply::LoadCompleted)?,
                 Err(error) => {
                     let mut description = String::new();
@@ -476,6 +509,157 @@ fn process_host_message(io: &Io, _aux_mutex: &Mutex, _ddma_mutex: &Mutex, _subke
     Ok(())
 }
 
+// A cached subkernel result is stored as the length of the argument message
+// (u32), the argument message, whether there is a return message (u8), then
+// the argument count and data of the return message.
+#[cfg(has_drtio)]
+fn subkernel_cached_result<'a>(value: &'a [u8], args: &[u8]) -> Option<Option<(u8, &'a [u8])>> {
+    if value.len() < 4 {
+        return None
+    }
+    let (args_len, value) = value.split_at(4);
+    let args_len = NativeEndian::read_u32(args_len) as usize;
+    if value.len() < args_len + 1 || &value[..args_len] != args {
+        return None
+    }
+    match &value[args_len..] {
+        [0] => Some(None),
+        [1, count, data @ ..] => Some(Some((*count, data))),
+        _ => None
+    }
+}
+
+/// Starts a subkernel run that `subkernel::load` held back to look up its
+/// result, unless it is found in the cache, with `args` as the serialized
+/// argument message. Returns whether the arguments must still be sent.
+#[cfg(has_drtio)]
+fn subkernel_start_deferred(io: &Io, aux_mutex: &Mutex, ddma_mutex: &Mutex, subkernel_mutex: &Mutex,
+        routing_table: &drtio_routing::RoutingTable, cache: &Cache<u8>, id: u32, args: &[u8]
+) -> Result<bool, Error<SchedError>> {
+    let (timestamp, key) = match subkernel::take_deferred_run(io, subkernel_mutex, id, args)? {
+        Some(run) => run,
+        None => return Ok(true)
+    };
+    match cache.peek(&key).and_then(|value| subkernel_cached_result(value, args)) {
+        Some(result) => {
+            debug!("subkernel #{} result found in cache", id);
+            subkernel::finish_from_cache(io, subkernel_mutex, id, result)?;
+            Ok(false)
+        }
+        None => {
+            subkernel::run_deferred(io, aux_mutex, ddma_mutex, subkernel_mutex, routing_table,
+                id, timestamp, key, args)?;
+            Ok(true)
+        }
+    }
+}
+
+/// Caches the result of a subkernel run started by `subkernel_start_deferred`.
+#[cfg(has_drtio)]
+fn subkernel_cache_result(io: &Io, subkernel_mutex: &Mutex, cache: &mut Cache<u8>, id: u32,
+        result: Option<(u8, &[u8])>) -> Result<(), Error<SchedError>> {
+    if let Some((key, args)) = subkernel::take_result_key(io, subkernel_mutex, id)? {
+        let mut value = vec![0; 4];
+        NativeEndian::write_u32(&mut value, args.len() as u32);
+        value.extend_from_slice(&args);
+        match result {
+            Some((count, data)) => {
+                value.extend_from_slice(&[1, count]);
+                value.extend_from_slice(data);
+            }
+            None => value.push(0)
+        }
+        if cache.put(&key, &value).is_err() {
+            warn!("cannot cache the result of subkernel #{}, as a kernel holds it", id)
+        }
+    }
+    Ok(())
+}
+
+/// Handles the requests from the kernel CPU that only need to be acknowledged,
+/// which may also come in a `Batch`. Returns false for any other request.
+// Camera 0 is addressed with the packets that predate the camera index,
+// so that satellites running older gateware keep answering.
+#[cfg(has_drtio)]
+fn cxp_read_packet(destination: u8, cam: u8, address: u32, length: u16) -> Packet {
+    if cam == 0 {
+        Packet::CXPReadRequest { destination, address, length }
+    } else {
+        Packet::CXPCamReadRequest { destination, cam, address, length }
+    }
+}
+
+#[cfg(has_drtio)]
+fn cxp_write32_packet(destination: u8, cam: u8, address: u32, value: u32) -> Packet {
+    if cam == 0 {
+        Packet::CXPWrite32Request { destination, address, value }
+    } else {
+        Packet::CXPCamWrite32Request { destination, cam, address, value }
+    }
+}
+
+#[cfg(has_drtio)]
+fn cxp_roi_viewer_setup_packet(destination: u8, cam: u8, x0: u16, y0: u16, x1: u16, y1: u16) -> Packet {
+    if cam == 0 {
+        Packet::CXPROIViewerSetupRequest { destination, x0, y0, x1, y1 }
+    } else {
+        Packet::CXPCamROIViewerSetupRequest { destination, cam, x0, y0, x1, y1 }
+    }
+}
+
+#[cfg(has_drtio)]
+fn cxp_roi_viewer_data_packet(destination: u8, cam: u8) -> Packet {
+    if cam == 0 {
+        Packet::CXPROIViewerDataRequest { destination }
+    } else {
+        Packet::CXPCamROIViewerDataRequest { destination, cam }
+    }
+}
+
+#[cfg(has_drtio)]
+fn cxp_link_status_packet(destination: u8, cam: u8) -> Packet {
+    if cam == 0 {
+        Packet::CXPLinkStatusRequest { destination }
+    } else {
+        Packet::CXPCamLinkStatusRequest { destination, cam }
+    }
+}
+
+fn process_kern_notice(session: &mut Session, request: &kern::Message) -> bool {
+    match request {
+        &kern::Log(args) => {
+            use core::fmt::Write;
+            session.log_buffer
+                   .write_fmt(args)
+                   .unwrap_or_else(|_| warn!("cannot append to session log buffer"));
+            session.flush_log_buffer();
+        }
+
+        &kern::LogSlice(arg) => {
+            session.log_buffer += arg;
+            session.flush_log_buffer();
+        }
+
+        &kern::LogBinary(data) => {
+            // The core log is text, so binary data is logged in hex,
+            // as a record of its own.
+            use core::fmt::Write;
+            let mut hex = String::with_capacity(data.len() * 2);
+            for byte in data {
+                let _ = write!(hex, "{:02x}", byte);
+            }
+            info!(target: "kernel::binary", "{}", hex);
+        }
+
+        &kern::DmaRecordAppend(data) => {
+            session.congress.dma_manager.record_append(data);
+        }
+
+        _ => return false
+    }
+    true
+}
+
 fn process_kern_message(io: &Io, aux_mutex: &Mutex,
                         routing_table: &drtio_routing::RoutingTable,
                         up_destinations: &Urc<RefCell<[bool; drtio_routing::DEST_COUNT]>>,
@@ -497,26 +681,57 @@ fn process_kern_message(io: &Io, aux_mutex: &Mutex,
 
         kern_recv_dotrace(request);
 
+        // the arguments of a subkernel run are sent right after it, so any other
+        // message means a run held back for them has none, and it can start
+        #[cfg(has_drtio)]
+        match (subkernel::deferred_run_id(), request) {
+            (Some(id), &kern::SubkernelMsgSend { id: msg_id, .. })
+                if msg_id == id => (),
+            (Some(id), _) => {
+                subkernel_start_deferred(io, aux_mutex, ddma_mutex, subkernel_mutex, routing_table,
+                    &session.congress.bytes_cache, id, &[])?;
+            }
+            (None, _) => ()
+        }
+
         if kern_hwreq::process_kern_hwreq(io, aux_mutex, ddma_mutex, subkernel_mutex, routing_table, up_destinations, request)? {
             return Ok(false)
         }
 
+        if process_kern_notice(session, request) {
+            return kern_acknowledge().and(Ok(false))
+        }
+
         match request {
-            &kern::Log(args) => {
-                use core::fmt::Write;
-                session.log_buffer
-                       .write_fmt(args)
-                       .unwrap_or_else(|_| warn!("cannot append to session log buffer"));
-                session.flush_log_buffer();
+            &kern::Batch(requests) => {
+                for request in requests {
+                    kern_recv_dotrace(request);
+                    if !process_kern_notice(session, request) {
+                        unexpected!("request {:?} from kernel CPU cannot be batched", request)
+                    }
+                }
                 kern_acknowledge()
             }
 
-            &kern::LogSlice(arg) => {
-                session.log_buffer += arg;
-                session.flush_log_buffer();
+            &kern::TimingReport { modinit_cycles, recv_cycles, rpc_flush_cycles } => {
+                info!("kernel timing: {} cycles in kernel ({} blocked in recv), {} in RPC flush",
+                      modinit_cycles, recv_cycles, rpc_flush_cycles);
                 kern_acknowledge()
             }
 
+            &kern::MailboxSelftestRequest(nonce) => {
+                kern_send(io, &kern::MailboxSelftestReply(nonce))
+            }
+
+            &kern::KeepaliveRequest => {
+                // flash kernels have no host connection to lose
+                let alive = match stream {
+                    None => true,
+                    Some(ref stream) => stream.may_send()
+                };
+                kern_send(io, &kern::KeepaliveReply { alive: alive })
+            }
+
             &kern::DmaRecordStart(name) => {
                 if let Some(_id) = session.congress.dma_manager.record_start(name) {
                     // replace the record
@@ -525,10 +740,6 @@ fn process_kern_message(io: &Io, aux_mutex: &Mutex,
                 }
                 kern_acknowledge()
             }
-            &kern::DmaRecordAppend(data) => {
-                session.congress.dma_manager.record_append(data);
-                kern_acknowledge()
-            }
             &kern::DmaRecordStop { duration, enable_ddma } => {
                 let _id = session.congress.dma_manager.record_stop(duration, enable_ddma, io, ddma_mutex)?;
                 #[cfg(has_drtio)]
@@ -546,6 +757,14 @@ fn process_kern_message(io: &Io, aux_mutex: &Mutex,
                 session.congress.dma_manager.erase(name);
                 kern_acknowledge()
             }
+            &kern::DmaDump { name, trace } => {
+                match stream {
+                    None => warn!("DMA trace {:?} cannot be dumped without a host connection", name),
+                    Some(ref mut stream) =>
+                        host_write(stream, host::Reply::DmaDump { name: name, trace: trace })?
+                }
+                kern_acknowledge()
+            }
             &kern::DmaRetrieveRequest { name } => {
                 session.congress.dma_manager.with_trace(name, |trace, duration| {
                     #[cfg(has_drtio)]
@@ -584,12 +803,11 @@ fn process_kern_message(io: &Io, aux_mutex: &Mutex,
                 kern_send(io, &reply)
             }
 
-            &kern::RpcSend { async, service, tag, data } => {
+            &kern::RpcSend { async, format, service, tag, data } => {
                 match stream {
                     None => unexpected!("unexpected RPC in flash kernel"),
                     Some(ref mut stream) => {
-                        host_write(stream, host::Reply::RpcRequest { async: async })?;
-                        rpc::send_args(stream, service, tag, data, true)?;
+                        host_send_rpc(stream, async, format, service, tag, data)?;
                         if !async {
                             session.kernel_state = KernelState::RpcWait
                         }
@@ -603,6 +821,17 @@ fn process_kern_message(io: &Io, aux_mutex: &Mutex,
                 // structured.
                 kern_acknowledge()
             },
+            &kern::PublishResult { channel, tag, data } => {
+                match stream {
+                    None => warn!("result published on channel {} dropped without a host connection",
+                                  channel),
+                    Some(ref mut stream) => {
+                        host_write(stream, host::Reply::PublishResult)?;
+                        rpc::send_args(stream, channel, tag, data, true)?;
+                    }
+                }
+                kern_acknowledge()
+            },
 
             &kern::CacheGetRequest { key } => {
                 let value = session.congress.cache.get(key);
@@ -618,10 +847,31 @@ fn process_kern_message(io: &Io, aux_mutex: &Mutex,
                 kern_send(io, &kern::CachePutReply { succeeded: succeeded })
             }
 
+            &kern::CacheGetBytesRequest { key } => {
+                let value = session.congress.bytes_cache.get(key);
+                kern_send(io, &kern::CacheGetBytesReply {
+                    value: unsafe { mem::transmute(value) }
+                })
+            }
+
+            &kern::CachePutBytesRequest { key, value } => {
+                let succeeded = session.congress.bytes_cache.put(key, value).is_ok();
+                kern_send(io, &kern::CachePutReply { succeeded: succeeded })
+            }
+
+            &kern::CacheCasRequest { key, expected, new } => {
+                let result = session.congress.cache.compare_and_swap(key, expected, new);
+                kern_send(io, &kern::CacheCasReply {
+                    succeeded: result.is_ok(),
+                    swapped: result.unwrap_or(false)
+                })
+            }
+
             &kern::RunFinished => {
                 unsafe { kernel::stop() }
                 session.kernel_state = KernelState::Absent;
                 unsafe { session.congress.cache.unborrow() }
+                unsafe { session.congress.bytes_cache.unborrow() }
                 #[cfg(has_drtio)]
                 subkernel::clear_subkernels(io, subkernel_mutex)?;
 
@@ -633,6 +883,16 @@ fn process_kern_message(io: &Io, aux_mutex: &Mutex,
                         }).map_err(|e| e.into())
                 }
             }
+            &kern::PartialResult(data) => {
+                // best-effort, as the exception that follows matters more
+                match stream {
+                    None => warn!("partial result cannot be sent without a host connection"),
+                    Some(ref mut stream) =>
+                        host_write(stream, host::Reply::PartialResult { data: data })
+                            .unwrap_or_else(|_| warn!("cannot send partial result to host"))
+                }
+                kern_acknowledge()
+            }
             &kern::RunException {
                 exceptions,
                 stack_pointers,
@@ -641,6 +901,7 @@ fn process_kern_message(io: &Io, aux_mutex: &Mutex,
                 unsafe { kernel::stop() }
                 session.kernel_state = KernelState::Absent;
                 unsafe { session.congress.cache.unborrow() }
+                unsafe { session.congress.bytes_cache.unborrow() }
                 #[cfg(has_drtio)]
                 subkernel::clear_subkernels(io, subkernel_mutex)?;
 
@@ -653,6 +914,9 @@ fn process_kern_message(io: &Io, aux_mutex: &Mutex,
                         return Ok(true)
                     },
                     Some(ref mut stream) => {
+                        host_write(stream, host::Reply::BacktraceFrameSizes {
+                            backtrace: backtrace
+                        })?;
                         host_write(stream, host::Reply::KernelException {
                             exceptions: exceptions,
                             stack_pointers: stack_pointers,
@@ -662,6 +926,31 @@ fn process_kern_message(io: &Io, aux_mutex: &Mutex,
                     }
                 }
             }
+            &kern::RunPanic { file, line, column, message } => {
+                let mut description = String::new();
+                if let Some(message) = message {
+                    write!(&mut description, "{}", message).unwrap();
+                }
+                error!("panic in kernel at {}:{}:{}: {}", file, line, column, description);
+
+                unsafe { kernel::stop() }
+                session.kernel_state = KernelState::Absent;
+                unsafe { session.congress.cache.unborrow() }
+                unsafe { session.congress.bytes_cache.unborrow() }
+                #[cfg(has_drtio)]
+                subkernel::clear_subkernels(io, subkernel_mutex)?;
+
+                match stream {
+                    None => return Ok(true),
+                    Some(ref mut stream) =>
+                        host_write(stream, host::Reply::KernelPanic {
+                            file: file,
+                            line: line,
+                            column: column,
+                            message: &description
+                        }).map_err(|e| e.into())
+                }
+            }
             #[cfg(has_drtio)]
             &kern::SubkernelLoadRunRequest { id, destination: _, run, timestamp } => {
                 let succeeded = match subkernel::load(
@@ -672,6 +961,32 @@ fn process_kern_message(io: &Io, aux_mutex: &Mutex,
                 kern_send(io, &kern::SubkernelLoadRunReply { succeeded: succeeded })
             }
             #[cfg(has_drtio)]
+            &kern::SubkernelLoadGroupRequest { ids, destination } => {
+                let failed_id = subkernel::load_group(
+                    io, aux_mutex, ddma_mutex, subkernel_mutex, routing_table, ids, destination)?;
+                kern_send(io, &kern::SubkernelLoadGroupReply { failed_id: failed_id })
+            }
+            #[cfg(has_drtio)]
+            &kern::SubkernelAvailableRequest { id, destination } => {
+                let up = up_destinations.borrow()[destination as usize];
+                let available = up && subkernel::is_uploaded(io, subkernel_mutex, id, destination)?;
+                kern_send(io, &kern::SubkernelAvailableReply { available: available })
+            }
+            #[cfg(not(has_drtio))]
+            &kern::SubkernelAvailableRequest { .. } => {
+                kern_send(io, &kern::SubkernelAvailableReply { available: false })
+            }
+            #[cfg(has_drtio)]
+            &kern::SubkernelCacheResultsRequest { id, enable } => {
+                subkernel::set_result_caching(io, subkernel_mutex, id, enable)?;
+                kern_acknowledge()
+            }
+            #[cfg(has_drtio)]
+            &kern::SubkernelCacheClearRequest { id } => {
+                session.congress.bytes_cache.remove_prefix(&subkernel::result_cache_prefix(id));
+                kern_acknowledge()
+            }
+            #[cfg(has_drtio)]
             &kern::SubkernelAwaitFinishRequest{ id, timeout } => {
                 let res = subkernel::await_finish(io, aux_mutex, ddma_mutex, subkernel_mutex, routing_table,
                     id, timeout);
@@ -687,6 +1002,8 @@ fn process_kern_message(io: &Io, aux_mutex: &Mutex,
                                     kern::SubkernelError(kern::SubkernelStatus::OtherError)
                                 }
                             } else {
+                                subkernel_cache_result(io, subkernel_mutex, &mut session.congress.bytes_cache,
+                                    id, None)?;
                                 kern::SubkernelAwaitFinishReply
                             }
                         },
@@ -698,12 +1015,21 @@ fn process_kern_message(io: &Io, aux_mutex: &Mutex,
             }
             #[cfg(has_drtio)]
             &kern::SubkernelMsgSend { id, destination, count, tag, data } => {
-                subkernel::message_send(io, aux_mutex, ddma_mutex, subkernel_mutex, routing_table, id, destination, count, tag, data)?;
+                let args = subkernel::message_serialize(count, tag, data)?;
+                if subkernel_start_deferred(io, aux_mutex, ddma_mutex, subkernel_mutex, routing_table,
+                        &session.congress.bytes_cache, id, &args)? {
+                    subkernel::message_send_serialized(io, aux_mutex, ddma_mutex, subkernel_mutex, routing_table,
+                        id, destination, &args)?;
+                }
                 kern_acknowledge()
             }
             #[cfg(has_drtio)]
             &kern::SubkernelMsgRecvRequest { id, timeout, tags } => {
                 let message_received = subkernel::message_await(io, subkernel_mutex, id as u32, timeout);
+                if let Ok(ref message) = message_received {
+                    subkernel_cache_result(io, subkernel_mutex, &mut session.congress.bytes_cache,
+                        id as u32, Some((message.count, &message.data)))?;
+                }
                 if let Err(SubkernelError::SubkernelFinished) = message_received {
                     let res = subkernel::retrieve_finish_status(io, aux_mutex, ddma_mutex, subkernel_mutex,
                         routing_table, id as u32)?;
@@ -782,20 +1108,21 @@ fn process_kern_message(io: &Io, aux_mutex: &Mutex,
             #[cfg(has_drtio)]
             kern::CXPReadRequest {
                 destination,
+                cam,
                 address,
                 length,
             } => {
                 let linkno = routing_table.0[*destination as usize][0] - 1;
+                let max_time = clock::get_ms() + CXP_WAIT_TIMEOUT_MS;
                 loop {
                     let drtioaux_packet = drtio::aux_transact(io, aux_mutex, ddma_mutex, subkernel_mutex, routing_table, linkno,
-                        &Packet::CXPReadRequest {
-                            destination: *destination,
-                            address: *address,
-                            length: *length,
-                        },
+                        &cxp_read_packet(*destination, *cam, *address, *length),
                     );
 
                     match drtioaux_packet {
+                        Ok(Packet::CXPWaitReply) if clock::get_ms() > max_time => {
+                            break kern_send(io, &kern::CXPError("Timeout - camera did not acknowledge the register read"));
+                        }
                         Ok(Packet::CXPWaitReply) => {}
                         Ok(Packet::CXPReadReply { length, data }) => {
                             break kern_send(io, &kern::CXPReadReply { length, data });
@@ -817,21 +1144,22 @@ fn process_kern_message(io: &Io, aux_mutex: &Mutex,
             #[cfg(has_drtio)]
             kern::CXPWrite32Request {
                 destination,
+                cam,
                 address,
                 value,
             } => {
                 let linkno = routing_table.0[*destination as usize][0] - 1;
+                let max_time = clock::get_ms() + CXP_WAIT_TIMEOUT_MS;
                 loop {
                     let drtioaux_packet = drtio::aux_transact(io, aux_mutex, ddma_mutex, subkernel_mutex, routing_table, linkno,
-                        &Packet::CXPWrite32Request {
-                            destination: *destination,
-                            address: *address,
-                            value: *value,
-                        },
+                        &cxp_write32_packet(*destination, *cam, *address, *value),
                     );
                     
 
                     match drtioaux_packet {
+                        Ok(Packet::CXPWaitReply) if clock::get_ms() > max_time => {
+                            break kern_send(io, &kern::CXPError("Timeout - camera did not acknowledge the register write"));
+                        }
                         Ok(Packet::CXPWaitReply) => {}
                         Ok(Packet::CXPWrite32Reply) => break kern_send(io, &kern::CXPWrite32Reply),
                         Ok(Packet::CXPError { length, message }) => {
@@ -851,6 +1179,7 @@ fn process_kern_message(io: &Io, aux_mutex: &Mutex,
             #[cfg(has_drtio)]
             kern::CXPROIViewerSetupRequest {
                 destination,
+                cam,
                 x0,
                 y0,
                 x1,
@@ -858,13 +1187,7 @@ fn process_kern_message(io: &Io, aux_mutex: &Mutex,
             } => {
                 let linkno = routing_table.0[*destination as usize][0] - 1;
                 let drtioaux_packet = drtio::aux_transact(io, aux_mutex, ddma_mutex, subkernel_mutex, routing_table, linkno,
-                    &Packet::CXPROIViewerSetupRequest {
-                        destination: *destination,
-                        x0: *x0,
-                        y0: *y0,
-                        x1: *x1,
-                        y1: *y1,
-                    },
+                    &cxp_roi_viewer_setup_packet(*destination, *cam, *x0, *y0, *x1, *y1),
                 );
 
                 let reply = match drtioaux_packet {
@@ -881,11 +1204,11 @@ fn process_kern_message(io: &Io, aux_mutex: &Mutex,
                 kern_send(io,&reply)
             }
             #[cfg(has_drtio)]
-            kern::CXPROIViewerDataRequest { destination } => {
+            kern::CXPROIViewerDataRequest { destination, cam } => {
                 let linkno = routing_table.0[*destination as usize][0] - 1;
                 let reply = loop {
                     let drtioaux_packet = drtio::aux_transact(io, aux_mutex, ddma_mutex, subkernel_mutex, routing_table, linkno,
-                        &Packet::CXPROIViewerDataRequest { destination: *destination },
+                        &cxp_roi_viewer_data_packet(*destination, *cam),
                     );
 
                     match drtioaux_packet {
@@ -916,6 +1239,83 @@ fn process_kern_message(io: &Io, aux_mutex: &Mutex,
                 };
                 kern_send(io,&reply)
                 }
+            #[cfg(has_drtio)]
+            kern::CXPROIViewerPollRequest { destination, cam } => {
+                let linkno = routing_table.0[*destination as usize][0] - 1;
+                let drtioaux_packet = drtio::aux_transact(io, aux_mutex, ddma_mutex, subkernel_mutex, routing_table, linkno,
+                    &cxp_roi_viewer_data_packet(*destination, *cam),
+                );
+
+                let reply = match drtioaux_packet {
+                    Ok(Packet::CXPWaitReply) => kern::CXPROIViewerWaitReply,
+                    Ok(Packet::CXPROIViewerPixelDataReply { length, data }) => {
+                        kern::CXPROIVIewerPixelDataReply { length, data }
+                    }
+                    Ok(Packet::CXPROIViewerFrameDataReply { width, height, pixel_code }) => {
+                        kern::CXPROIVIewerFrameDataReply { width, height, pixel_code }
+                    }
+                    Ok(Packet::CXPError { length, message }) => {
+                        kern::CXPError(str::from_utf8(&message[..length as usize]).unwrap())
+                    }
+                    Ok(packet) => {
+                        error!("received unexpected aux packet {:?}", packet);
+                        kern::CXPError("recevied unexpected drtio aux reply")
+                    }
+                    Err(e) => {
+                        error!("aux packet error ({})", e);
+                        kern::CXPError("drtio aux error")
+                    }
+                };
+                kern_send(io,&reply)
+            }
+            #[cfg(has_drtio)]
+            kern::CXPLinkStatusRequest { destination, cam } => {
+                let linkno = routing_table.0[*destination as usize][0] - 1;
+                let drtioaux_packet = drtio::aux_transact(io, aux_mutex, ddma_mutex, subkernel_mutex, routing_table, linkno,
+                    &cxp_link_status_packet(*destination, *cam),
+                );
+
+                let reply = match drtioaux_packet {
+                    Ok(Packet::CXPLinkStatusReply { up, lanes, bitrate_code }) => {
+                        kern::CXPLinkStatusReply { up, lanes, bitrate_code }
+                    }
+                    Ok(Packet::CXPError { length, message }) => {
+                        kern::CXPError(str::from_utf8(&message[..length as usize]).unwrap())
+                    }
+                    Ok(packet) => {
+                        error!("received unexpected aux packet {:?}", packet);
+                        kern::CXPError("recevied unexpected drtio aux reply")
+                    }
+                    Err(e) => {
+                        error!("aux packet error ({})", e);
+                        kern::CXPError("drtio aux error")
+                    }
+                };
+                kern_send(io,&reply)
+            }
+            #[cfg(has_drtio)]
+            kern::CXPCameraCountRequest { destination } => {
+                let linkno = routing_table.0[*destination as usize][0] - 1;
+                let drtioaux_packet = drtio::aux_transact(io, aux_mutex, ddma_mutex, subkernel_mutex, routing_table, linkno,
+                    &Packet::CXPCameraCountRequest { destination: *destination },
+                );
+
+                let reply = match drtioaux_packet {
+                    Ok(Packet::CXPCameraCountReply { count }) => kern::CXPCameraCountReply { count },
+                    Ok(Packet::CXPError { length, message }) => {
+                        kern::CXPError(str::from_utf8(&message[..length as usize]).unwrap())
+                    }
+                    Ok(packet) => {
+                        error!("received unexpected aux packet {:?}", packet);
+                        kern::CXPError("recevied unexpected drtio aux reply")
+                    }
+                    Err(e) => {
+                        error!("aux packet error ({})", e);
+                        kern::CXPError("drtio aux error")
+                    }
+                };
+                kern_send(io,&reply)
+            }
             request => unexpected!("unexpected request {:?} from kernel CPU", request)
         }.and(Ok(false))
     })
@@ -926,9 +1326,10 @@ fn process_kern_queued_rpc(stream: &mut TcpStream,
     rpc_queue::dequeue(|slice| {
         debug!("comm<-kern (async RPC)");
         let length = NativeEndian::read_u32(slice) as usize;
-        host_write(stream, host::Reply::RpcRequest { async: true })?;
-        debug!("{:?}", &slice[4..][..length]);
-        stream.write_all(&slice[4..][..length])?;
+        let debug = slice[4] == rpc::FORMAT_DEBUG;
+        host_write(stream, host::Reply::RpcRequest { async: true, debug: debug })?;
+        debug!("{:?}", &slice[5..][..length]);
+        stream.write_all(&slice[5..][..length])?;
         Ok(())
     })
 }
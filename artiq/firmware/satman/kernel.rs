@@ -2,7 +2,7 @@ use core::mem;
 use alloc::{string::String, format, vec::Vec, collections::btree_map::BTreeMap};
 use cslice::{CSlice, AsCSlice};
 
-use board_artiq::{drtioaux, drtio_routing::RoutingTable, mailbox, spi};
+use board_artiq::{drtioaux, drtio_routing::{self, RoutingTable}, mailbox, spi};
 use board_misoc::{csr, clock, i2c};
 use proto_artiq::{
     drtioaux_proto::PayloadStatus,
@@ -65,6 +65,7 @@ enum KernelState {
     MsgAwait { id: u32, max_time: i64, tags: Vec<u8> },
     MsgSending,
     SubkernelAwaitLoad,
+    SubkernelAwaitLoadGroup { entry: u32 },
     SubkernelAwaitFinish { max_time: i64, id: u32 },
     DmaUploading { max_time: u64 },
     DmaAwait { max_time: u64 },
@@ -153,7 +154,8 @@ pub struct Manager {
     kernels: BTreeMap<u32, KernelLibrary>,
     current_id: u32,
     session: Session,
-    cache: Cache,
+    cache: Cache<i32>,
+    bytes_cache: Cache<u8>,
     last_finished: Option<SubkernelFinished>
 }
 
@@ -302,6 +304,44 @@ impl Session {
             self.log_buffer.clear()
         }
     }
+
+    /// Handles the requests from the kernel CPU that only need to be
+    /// acknowledged, which may also come in a `Batch`. Returns false for
+    /// any other request.
+    fn process_kern_notice(&mut self, dma_manager: &mut DmaManager, request: &kern::Message) -> bool {
+        match request {
+            &kern::Log(args) => {
+                use core::fmt::Write;
+                self.log_buffer
+                    .write_fmt(args)
+                    .unwrap_or_else(|_| warn!("cannot append to session log buffer"));
+                self.flush_log_buffer();
+            }
+
+            &kern::LogSlice(arg) => {
+                self.log_buffer += arg;
+                self.flush_log_buffer();
+            }
+
+            &kern::LogBinary(data) => {
+                // The core log is text, so binary data is logged in hex,
+                // as a record of its own.
+                use core::fmt::Write;
+                let mut hex = String::with_capacity(data.len() * 2);
+                for byte in data {
+                    let _ = write!(hex, "{:02x}", byte);
+                }
+                info!(target: "kernel::binary", "{}", hex);
+            }
+
+            &kern::DmaRecordAppend(data) => {
+                dma_manager.record_append(data);
+            }
+
+            _ => return false
+        }
+        true
+    }
 }
 
 impl Manager {
@@ -311,6 +351,7 @@ impl Manager {
             current_id: 0,
             session: Session::new(),
             cache: Cache::new(),
+            bytes_cache: Cache::new(),
             last_finished: None,
         }
     }
@@ -361,13 +402,14 @@ impl Manager {
         unsafe { kernel_cpu::stop() }
         self.session.kernel_state = KernelState::Absent;
         unsafe { self.cache.unborrow() }
+        unsafe { self.bytes_cache.unborrow() }
     }
 
-    pub fn run(&mut self, source: u8, id: u32, timestamp: u64) -> Result<(), Error> {
+    pub fn run(&mut self, source: u8, id: u32, timestamp: u64, self_destination: u8) -> Result<(), Error> {
         info!("starting subkernel #{}", id);
         if self.session.kernel_state != KernelState::Loaded
             || self.current_id != id {
-            self.load(id)?;
+            self.load(id, self_destination)?;
         } 
         self.session.source = source;
         self.session.kernel_state = KernelState::Running;
@@ -398,7 +440,7 @@ impl Manager {
         self.session.messages.ack_slice()
     }
 
-    pub fn load(&mut self, id: u32) -> Result<(), Error> {
+    pub fn load(&mut self, id: u32, self_destination: u8) -> Result<(), Error> {
         if self.current_id == id && self.session.kernel_state == KernelState::Loaded {
             return Ok(())
         }
@@ -412,7 +454,12 @@ impl Manager {
         unsafe { 
             kernel_cpu::start();
 
-            kern_send(&kern::LoadRequest(&self.kernels.get(&id).unwrap().library)).unwrap();
+            kern_send(&kern::LoadRequest {
+                library: &self.kernels.get(&id).unwrap().library,
+                destination: self_destination,
+                log_level: ::log::max_level() as u8,
+                expected_crc: None
+            }).unwrap();
             kern_recv(|reply| {
                 match reply {
                     kern::LoadReply(Ok(())) => {
@@ -528,6 +575,7 @@ impl Manager {
                 unsafe { kernel_cpu::stop() }
                 self.session.kernel_state = KernelState::Absent;
                 unsafe { self.cache.unborrow() }
+                unsafe { self.bytes_cache.unborrow() }
                 self.session.last_exception = Some(exception);
                 self.last_finished = finished!(true);
             },
@@ -632,8 +680,15 @@ impl Manager {
     }
 
     pub fn subkernel_load_run_reply(&mut self, succeeded: bool, self_destination: u8) {
-        if self.session.kernel_state == KernelState::SubkernelAwaitLoad {
-            if let Err(e) = kern_send(&kern::SubkernelLoadRunReply { succeeded: succeeded }) {
+        let reply = match self.session.kernel_state {
+            KernelState::SubkernelAwaitLoad => Some(kern::SubkernelLoadRunReply { succeeded: succeeded }),
+            KernelState::SubkernelAwaitLoadGroup { entry } => Some(kern::SubkernelLoadGroupReply {
+                failed_id: if succeeded { None } else { Some(entry) }
+            }),
+            _ => None
+        };
+        if let Some(reply) = reply {
+            if let Err(e) = kern_send(&reply) {
                 self.stop(); 
                 self.runtime_exception(e);
                 self.last_finished = Some(SubkernelFinished { 
@@ -690,6 +745,7 @@ impl Manager {
                     (_, KernelState::DmaAwait { .. }) |
                     (_, KernelState::MsgSending) |
                     (_, KernelState::SubkernelAwaitLoad) | 
+                    (_, KernelState::SubkernelAwaitLoadGroup { .. }) |
                     (_, KernelState::SubkernelRetrievingException { .. }) |
                     (_, KernelState::SubkernelAwaitFinish { .. }) => {
                     // We're standing by; ignore the message.
@@ -706,28 +762,47 @@ impl Manager {
                 return Ok(None)
             }
 
+            if self.session.process_kern_notice(dma_manager, request) {
+                return kern_acknowledge().and(Ok(None))
+            }
+
             match request {
-                &kern::Log(args) => {
-                    use core::fmt::Write;
-                    self.session.log_buffer
-                        .write_fmt(args)
-                        .unwrap_or_else(|_| warn!("cannot append to session log buffer"));
-                    self.session.flush_log_buffer();
+                &kern::Batch(requests) => {
+                    for request in requests {
+                        if !self.session.process_kern_notice(dma_manager, request) {
+                            unexpected!("request {:?} from kernel CPU cannot be batched", request)
+                        }
+                    }
                     kern_acknowledge()
                 }
 
-                &kern::LogSlice(arg) => {
-                    self.session.log_buffer += arg;
-                    self.session.flush_log_buffer();
+                &kern::TimingReport { modinit_cycles, recv_cycles, rpc_flush_cycles } => {
+                    info!("subkernel timing: {} cycles in kernel ({} blocked in recv), {} in RPC flush",
+                          modinit_cycles, recv_cycles, rpc_flush_cycles);
                     kern_acknowledge()
                 }
 
+                &kern::MailboxSelftestRequest(nonce) => {
+                    kern_send(&kern::MailboxSelftestReply(nonce))
+                }
+
+                &kern::KeepaliveRequest => {
+                    // subkernels have no host connection of their own
+                    kern_send(&kern::KeepaliveReply { alive: true })
+                }
+
                 &kern::RpcFlush => {
                     // we do not have to do anything about this request,
                     // it is sent by the kernel firmware regardless of RPC being used
                     kern_acknowledge()
                 }
 
+                &kern::PublishResult { channel, .. } => {
+                    warn!("result published on channel {} dropped, as subkernels have no host connection",
+                          channel);
+                    kern_acknowledge()
+                }
+
                 &kern::CacheGetRequest { key } => {
                     let value = self.cache.get(key);
                     kern_send(&kern::CacheGetReply {
@@ -740,30 +815,56 @@ impl Manager {
                     kern_send(&kern::CachePutReply { succeeded: succeeded })
                 }
 
+                &kern::CacheGetBytesRequest { key } => {
+                    let value = self.bytes_cache.get(key);
+                    kern_send(&kern::CacheGetBytesReply {
+                        value: unsafe { mem::transmute(value) }
+                    })
+                }
+
+                &kern::CachePutBytesRequest { key, value } => {
+                    let succeeded = self.bytes_cache.put(key, value).is_ok();
+                    kern_send(&kern::CachePutReply { succeeded: succeeded })
+                }
+
+                &kern::CacheCasRequest { key, expected, new } => {
+                    let result = self.cache.compare_and_swap(key, expected, new);
+                    kern_send(&kern::CacheCasReply {
+                        succeeded: result.is_ok(),
+                        swapped: result.unwrap_or(false)
+                    })
+                }
+
                 &kern::RunFinished => {
                     unsafe { kernel_cpu::stop() }
                     self.session.kernel_state = KernelState::Absent;
                     unsafe { self.cache.unborrow() }
+                    unsafe { self.bytes_cache.unborrow() }
 
                     return Ok(Some(false))
                 }
+                // the partial results of subkernels are not passed on
+                &kern::PartialResult(_) => kern_acknowledge(),
                 &kern::RunException { exceptions, stack_pointers, backtrace } => {
                     unsafe { kernel_cpu::stop() }
                     self.session.kernel_state = KernelState::Absent;
                     unsafe { self.cache.unborrow() }    
+                    unsafe { self.bytes_cache.unborrow() }
                     let exception = slice_kernel_exception(&exceptions, &stack_pointers, &backtrace)?;
                     self.session.last_exception = Some(exception);
                     return Ok(Some(true))
                 }
 
+                &kern::RunPanic { file, line, column, message } => {
+                    // stops the subkernel and reports it as failed to the master
+                    let description = message.map(|message| format!("{}", message)).unwrap_or_default();
+                    unexpected!("panic in subkernel at {}:{}:{}: {}", file, line, column, description)
+                }
+
                 &kern::DmaRecordStart(name) => {
                     dma_manager.record_start(name);
                     kern_acknowledge()
                 }
-                &kern::DmaRecordAppend(data) => {
-                    dma_manager.record_append(data);
-                    kern_acknowledge()
-                }
                 &kern::DmaRecordStop { duration, enable_ddma: _ } => {
                     // ddma is always used on satellites
                     if let Ok(id) = dma_manager.record_stop(duration, destination) {
@@ -783,6 +884,10 @@ impl Manager {
                     dma_manager.erase_name(name, router, rank, destination, routing_table);
                     kern_acknowledge()
                 }
+                &kern::DmaDump { name, trace: _ } => {
+                    warn!("DMA trace {:?} cannot be dumped from a subkernel", name);
+                    kern_acknowledge()
+                }
                 &kern::DmaRetrieveRequest { name } => {
                     dma_manager.with_trace(destination, name, |trace, duration| {
                         kern_send(&kern::DmaRetrieveReply {
@@ -837,12 +942,49 @@ impl Manager {
                     Ok(())
                 }
 
+                // The master keeps track of uploads, so a satellite can only
+                // check that the first subkernel of the group loads.
+                &kern::SubkernelLoadGroupRequest { ids, destination: sk_destination } => {
+                    match ids.first() {
+                        Some(&entry) => {
+                            self.session.kernel_state = KernelState::SubkernelAwaitLoadGroup { entry };
+                            router.route(drtioaux::Packet::SubkernelLoadRunRequest {
+                                source: destination, destination: sk_destination, id: entry, run: false, timestamp: 0
+                            }, routing_table, rank, destination);
+                            Ok(())
+                        }
+                        None => kern_send(&kern::SubkernelLoadGroupReply { failed_id: None })
+                    }
+                }
+
+                // Only the subkernels uploaded to this satellite are known.
+                &kern::SubkernelAvailableRequest { id, destination: sk_destination } => {
+                    let available = sk_destination == destination &&
+                        self.kernels.get(&id).map_or(false, |kernel| kernel.complete);
+                    kern_send(&kern::SubkernelAvailableReply { available: available })
+                }
+
+                // Satellites do not track the state of other destinations, so
+                // those with a route are assumed to be up.
+                &kern::DrtioLinkStatusRequest { destination: link_destination } => {
+                    let up = link_destination == destination ||
+                        routing_table.0.get(link_destination as usize)
+                            .map_or(false, |hops| hops[0] != drtio_routing::INVALID_HOP);
+                    kern_send(&kern::DrtioLinkStatusReply { up: up })
+                }
+
                 &kern::SubkernelAwaitFinishRequest { id, timeout } => {
                     let max_time = if timeout > 0 { clock::get_ms() as i64 + timeout } else { timeout };
                     self.session.kernel_state = KernelState::SubkernelAwaitFinish { max_time, id };
                     Ok(())
                 }
 
+                // results of subkernels called from a satellite are not cached
+                &kern::SubkernelCacheResultsRequest { .. } |
+                &kern::SubkernelCacheClearRequest { .. } => {
+                    kern_acknowledge()
+                }
+
                 request => unexpected!("unexpected request {:?} from kernel CPU", request)
             }.and(Ok(None))
         })
@@ -940,7 +1082,7 @@ fn kern_send(request: &kern::Message) -> Result<(), Error> {
 
 fn slice_kernel_exception(exceptions: &[Option<eh_artiq::Exception>],
     stack_pointers: &[eh_artiq::StackPointerBacktrace],
-    backtrace: &[(usize, usize)]
+    backtrace: &[(usize, usize, usize)]
 ) -> Result<Sliceable, Error> {
     error!("exception in kernel");
     for exception in exceptions {
@@ -1018,6 +1160,48 @@ fn pass_message_to_kernel(message: &Message, tags: &[u8]) -> Result<(), Error> {
     Ok(())
 }
 
+// Results of asynchronous reads, as the data or the error code.
+static mut ASYNC_READS: [Result<u8, u8>; kern::I2C_ASYNC_READ_SLOTS] =
+    [Err(kern::NRT_BUS_OTHER); kern::I2C_ASYNC_READ_SLOTS];
+
+fn i2c_error_code(error: i2c::Error) -> u8 {
+    match error {
+        i2c::Error::NoI2C | i2c::Error::InvalidBus => kern::NRT_BUS_NO_BUS,
+        i2c::Error::Nack => kern::NRT_BUS_NACK,
+        i2c::Error::SCLLow | i2c::Error::SDALow => kern::NRT_BUS_STUCK,
+        i2c::Error::ArbitrationLost => kern::NRT_BUS_ARBITRATION_LOST,
+        i2c::Error::IOExpanderError | i2c::Error::OtherError => kern::NRT_BUS_OTHER,
+    }
+}
+
+fn spi_error_code(error: spi::Error) -> u8 {
+    match error {
+        spi::Error::NoSPI | spi::Error::InvalidBus => kern::NRT_BUS_NO_BUS,
+        spi::Error::OtherError => kern::NRT_BUS_OTHER,
+    }
+}
+
+fn i2c_write_register(busno: u8, address: u8, register: u8, value: u8) -> Result<(), i2c::Error> {
+    i2c::start(busno)?;
+    let result = i2c::write(busno, address)
+        .and_then(|_| i2c::write(busno, register))
+        .and_then(|_| i2c::write(busno, value));
+    // release the bus even if the device did not acknowledge
+    let stopped = i2c::stop(busno);
+    result.and(stopped).map(|_| ())
+}
+
+fn i2c_read_register(busno: u8, address: u8, register: u8) -> Result<u8, i2c::Error> {
+    i2c::start(busno)?;
+    let result = i2c::write(busno, address)
+        .and_then(|_| i2c::write(busno, register))
+        .and_then(|_| i2c::restart(busno))
+        .and_then(|_| i2c::write(busno, address | 1))
+        .and_then(|_| i2c::read(busno, false));
+    let stopped = i2c::stop(busno);
+    result.and_then(|data| stopped.map(|_| data))
+}
+
 fn process_kern_hwreq(request: &kern::Message, self_destination: u8) -> Result<bool, Error> {
     match request {
         &kern::RtioInitRequest => {
@@ -1036,56 +1220,123 @@ fn process_kern_hwreq(request: &kern::Message, self_destination: u8) -> Result<b
                 up: destination == self_destination })
         }
 
+        &kern::RtioSedLaneLevelsRequest => {
+            // the SED lanes of a satellite are not exposed through CSRs
+            kern_send(&kern::RtioSedLaneLevelsReply { levels: &[] })
+        }
+
         &kern::I2cStartRequest { busno } => {
-            let succeeded = i2c::start(busno as u8).is_ok();
-            kern_send(&kern::I2cBasicReply { succeeded: succeeded })
+            let error = i2c::start(busno as u8).err().map_or(0, i2c_error_code);
+            kern_send(&kern::I2cBasicReply { error: error })
         }
         &kern::I2cRestartRequest { busno } => {
-            let succeeded = i2c::restart(busno as u8).is_ok();
-            kern_send(&kern::I2cBasicReply { succeeded: succeeded })
+            let error = i2c::restart(busno as u8).err().map_or(0, i2c_error_code);
+            kern_send(&kern::I2cBasicReply { error: error })
         }
         &kern::I2cStopRequest { busno } => {
-            let succeeded = i2c::stop(busno as u8).is_ok();
-            kern_send(&kern::I2cBasicReply { succeeded: succeeded })
+            let error = i2c::stop(busno as u8).err().map_or(0, i2c_error_code);
+            kern_send(&kern::I2cBasicReply { error: error })
         }
         &kern::I2cWriteRequest { busno, data } => {
             match i2c::write(busno as u8, data) {
                 Ok(_) => kern_send(
-                    &kern::I2cWriteReply { succeeded: true, ack: true }),
+                    &kern::I2cWriteReply { error: 0, ack: true }),
                 Err(i2c::Error::Nack) => kern_send(
-                    &kern::I2cWriteReply { succeeded: true, ack: false }),
-                Err(_) => kern_send(
-                    &kern::I2cWriteReply { succeeded: false, ack: false })
+                    &kern::I2cWriteReply { error: 0, ack: false }),
+                Err(e) => kern_send(
+                    &kern::I2cWriteReply { error: i2c_error_code(e), ack: false })
             }
         }
         &kern::I2cReadRequest { busno, ack } => {
             match i2c::read(busno as u8, ack) {
                 Ok(data) => kern_send(
-                    &kern::I2cReadReply { succeeded: true, data: data }),
-                Err(_) => kern_send(
-                    &kern::I2cReadReply { succeeded: false, data: 0xff })
+                    &kern::I2cReadReply { error: 0, data: data }),
+                Err(e) => kern_send(
+                    &kern::I2cReadReply { error: i2c_error_code(e), data: 0xff })
             }
         }
         &kern::I2cSwitchSelectRequest { busno, address, mask } => {
-            let succeeded = i2c::switch_select(busno as u8, address, mask).is_ok();
-            kern_send(&kern::I2cBasicReply { succeeded: succeeded })
+            let error = i2c::switch_select(busno as u8, address, mask).err().map_or(0, i2c_error_code);
+            kern_send(&kern::I2cBasicReply { error: error })
+        }
+        &kern::I2cWriteBatchRequest { busno, address, writes } => {
+            let mut failed = None;
+            for (index, &(register, value)) in writes.iter().enumerate() {
+                if let Err(e) = i2c_write_register(busno as u8, address, register as u8, value as u8) {
+                    failed = Some((index as u32, i2c_error_code(e)));
+                    break
+                }
+            }
+            kern_send(&kern::I2cBatchReply { failed: failed, data: &[] })
+        }
+        &kern::I2cReadBatchRequest { busno, address, registers } => {
+            let mut data = Vec::with_capacity(registers.len());
+            let mut failed = None;
+            for (index, &register) in registers.iter().enumerate() {
+                match i2c_read_register(busno as u8, address, register as u8) {
+                    Ok(value) => data.push(value),
+                    Err(e) => {
+                        failed = Some((index as u32, i2c_error_code(e)));
+                        break
+                    }
+                }
+            }
+            kern_send(&kern::I2cBatchReply { failed: failed, data: &data })
+        }
+        &kern::I2cReadAsyncRequest { slot, busno, address, register } => {
+            // let the kernel run on while the bus is busy
+            kern_acknowledge()?;
+            let data = i2c_read_register(busno as u8, address, register).map_err(i2c_error_code);
+            unsafe { ASYNC_READS[slot as usize] = data }
+            Ok(())
+        }
+        &kern::I2cReadCollectRequest { slot } => {
+            match unsafe { ASYNC_READS[slot as usize] } {
+                Ok(data) => kern_send(&kern::I2cReadReply { error: 0, data: data }),
+                Err(error) => kern_send(&kern::I2cReadReply { error: error, data: 0xff })
+            }
         }
 
         &kern::SpiSetConfigRequest { busno, flags, length, div, cs } => {
-            let succeeded = spi::set_config(busno as u8, flags, length, div, cs).is_ok();
-            kern_send(&kern::SpiBasicReply { succeeded: succeeded })
+            let error = spi::set_config(busno as u8, flags, length, div, cs).err().map_or(0, spi_error_code);
+            kern_send(&kern::SpiBasicReply { error: error })
         },
         &kern::SpiWriteRequest { busno, data } => {
-            let succeeded = spi::write(busno as u8, data).is_ok();
-            kern_send(&kern::SpiBasicReply { succeeded: succeeded })
+            let error = spi::write(busno as u8, data).err().map_or(0, spi_error_code);
+            kern_send(&kern::SpiBasicReply { error: error })
         }
         &kern::SpiReadRequest { busno } => {
             match spi::read(busno as u8) {
                 Ok(data) => kern_send(
-                    &kern::SpiReadReply { succeeded: true, data: data }),
-                Err(_) => kern_send(
-                    &kern::SpiReadReply { succeeded: false, data: 0 })
+                    &kern::SpiReadReply { error: 0, data: data }),
+                Err(e) => kern_send(
+                    &kern::SpiReadReply { error: spi_error_code(e), data: 0 })
+            }
+        }
+        // The SPI cores have no burst mode, so wide transfers go word by word.
+        &kern::SpiWriteWideRequest { busno, data } => {
+            let mut error = 0;
+            for &word in data.iter() {
+                if let Err(e) = spi::write(busno as u8, word as u32) {
+                    error = spi_error_code(e);
+                    break
+                }
+            }
+            kern_send(&kern::SpiBasicReply { error: error })
+        }
+        &kern::SpiReadWideRequest { busno, data } => {
+            let mut words = Vec::with_capacity(data.len());
+            let mut error = 0;
+            for &word in data.iter() {
+                match spi::write(busno as u8, word as u32).and_then(|_| spi::read(busno as u8)) {
+                    Ok(read) => words.push(read as i32),
+                    Err(e) => {
+                        error = spi_error_code(e);
+                        break
+                    }
+                }
             }
+            kern_send(&kern::SpiReadWideReply { error: error, data: &words })
         }
 
         _ => return Ok(false)
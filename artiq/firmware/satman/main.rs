@@ -435,16 +435,16 @@ fn process_aux_packet(dmamgr: &mut DmaManager, analyzer: &mut Analyzer, kernelmg
             drtioaux::send(0,
                 &drtioaux::Packet::SubkernelAddDataReply { succeeded: succeeded })
         }
-        drtioaux::Packet::SubkernelLoadRunRequest { source, destination: _destination, id, run, timestamp } => {
-            forward!(router, _routing_table, _destination, *rank, *self_destination, _repeaters, &packet);
-            let mut succeeded = kernelmgr.load(id).is_ok();
+        drtioaux::Packet::SubkernelLoadRunRequest { source, destination, id, run, timestamp } => {
+            forward!(router, _routing_table, destination, *rank, *self_destination, _repeaters, &packet);
+            let mut succeeded = kernelmgr.load(id, destination).is_ok();
             // allow preloading a kernel with delayed run
             if run {
                 if dmamgr.running() {
                     // cannot run kernel while DDMA is running
                     succeeded = false;
                 } else {
-                    succeeded |= kernelmgr.run(source, id, timestamp).is_ok();
+                    succeeded |= kernelmgr.run(source, id, timestamp, destination).is_ok();
                 }
             }
             router.send(drtioaux::Packet::SubkernelLoadRunReply { 
@@ -666,7 +666,13 @@ fn process_aux_packet(dmamgr: &mut DmaManager, analyzer: &mut Analyzer, kernelmg
         drtioaux::Packet::CXPReadRequest { destination: _destination, .. }
         | drtioaux::Packet::CXPWrite32Request { destination: _destination, .. }
         | drtioaux::Packet::CXPROIViewerSetupRequest { destination: _destination, .. }
-        | drtioaux::Packet::CXPROIViewerDataRequest { destination: _destination } => {
+        | drtioaux::Packet::CXPROIViewerDataRequest { destination: _destination, .. }
+        | drtioaux::Packet::CXPLinkStatusRequest { destination: _destination, .. }
+        | drtioaux::Packet::CXPCamReadRequest { destination: _destination, .. }
+        | drtioaux::Packet::CXPCamWrite32Request { destination: _destination, .. }
+        | drtioaux::Packet::CXPCamROIViewerSetupRequest { destination: _destination, .. }
+        | drtioaux::Packet::CXPCamROIViewerDataRequest { destination: _destination, .. }
+        | drtioaux::Packet::CXPCamLinkStatusRequest { destination: _destination, .. } => {
             forward!(
                 router,
                 _routing_table,
@@ -690,6 +696,19 @@ fn process_aux_packet(dmamgr: &mut DmaManager, analyzer: &mut Analyzer, kernelmg
                 },
             )
         }
+        drtioaux::Packet::CXPCameraCountRequest { destination: _destination } => {
+            forward!(
+                router,
+                _routing_table,
+                _destination,
+                *rank,
+                *self_destination,
+                _repeaters,
+                &packet
+            );
+
+            drtioaux::send(0, &drtioaux::Packet::CXPCameraCountReply { count: 0 })
+        }
 
         _ => {
             warn!("received unexpected aux packet");
@@ -892,7 +911,7 @@ pub extern fn main() -> i32 {
 
     unsafe {
         ALLOC.add_range(&mut _fheap, &mut _eheap);
-        pmp::init_stack_guard(&_sstack_guard as *const u8 as usize);
+        pmp::init_stack_guard(&_sstack_guard as *const u8 as usize, pmp::STACK_GUARD_SIZE);
     }
     #[cfg(soc_platform = "kasli")]
     irq::enable_interrupts();
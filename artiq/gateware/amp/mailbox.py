@@ -6,6 +6,9 @@ class Mailbox(Module):
     def __init__(self, size=1, adr_width=30):
         self.i1 = wishbone.Interface(data_width=32, adr_width=adr_width)
         self.i2 = wishbone.Interface(data_width=32, adr_width=adr_width)
+        # Raised by a write on i1 until the next access on i2, to wake up the
+        # CPU on i2 when it idles in a wait for the other one.
+        self.irq = Signal()
 
         # # #
 
@@ -19,3 +22,11 @@ class Mailbox(Module):
                     If(i.we, values[i.adr[:bits_for(size-1)]].eq(i.dat_w))
                 )
             ]
+        self.sync += [
+            If(self.i2.cyc & self.i2.stb & ~self.i2.ack,
+                self.irq.eq(0)
+            ),
+            If(self.i1.cyc & self.i1.stb & ~self.i1.ack & self.i1.we,
+                self.irq.eq(1)
+            )
+        ]
@@ -27,6 +27,8 @@ class AMPSoC:
                           self.mailbox.i1)
         self.kernel_cpu.add_wb_slave(self.mem_map["mailbox"], self.csr_separation*mailbox_size,
                                      self.mailbox.i2)
+        self.comb += self.kernel_cpu.cpu.interrupt[0].eq(self.mailbox.irq)
+        self.config["kernel_has_mailbox_irq"] = None
         self.add_memory_region("mailbox",
                                self.mem_map["mailbox"] | 0x80000000,
                                self.csr_separation*mailbox_size)
@@ -23,6 +23,9 @@ class Core(Module, AutoCSR):
         self.collision_channel = CSRStatus(16)
         self.busy_channel = CSRStatus(16)
         self.sequence_error_channel = CSRStatus(16)
+        self.sed_lane_count = CSRStatus(8, reset=lane_count)
+        self.sed_lane_select = CSRStorage(bits_for(lane_count - 1))
+        self.sed_lane_level = CSRStatus(16)
 
         # Clocking/Reset
         # Create rio and rio_phy domains based on sys
@@ -69,6 +72,11 @@ class Core(Module, AutoCSR):
         self.comb += outputs.coarse_timestamp.eq(tsc.coarse_ts)
         self.sync += outputs.minimum_coarse_timestamp.eq(tsc.coarse_ts + 12)
         self.specials += MultiReg(self.sed_spread_enable.storage, outputs.enable_spread, "rio")
+        # rio is clocked by sys, so the levels can be read directly
+        self.comb += [
+            outputs.lane_select.eq(self.sed_lane_select.storage),
+            self.sed_lane_level.status.eq(outputs.lane_level)
+        ]
 
         inputs = ClockDomainsRenamer("rio")(InputCollector(tsc, channels,
             quash_channels=quash_channels,
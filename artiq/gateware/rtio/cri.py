@@ -83,6 +83,8 @@ class KernelInitiator(Module, AutoCSR):
         self.counter = CSRStatus(64)
         self.counter_update = CSR()
 
+        self.fine_ts_width = CSRStatus(8, reset=tsc.glbl_fine_ts_width)
+
         if cri is None:
             cri = Interface()
         self.cri = cri
@@ -26,7 +26,10 @@ class CXPGrabber(Module, AutoCSR):
         # Trigger rtio
         nbit_extra_linktrig = 1
         nbit_linktrig = 2
-        self.trigger = rtlink.Interface(rtlink.OInterface(nbit_extra_linktrig + nbit_linktrig))
+        # the address is the camera index, for grabbers with several cameras
+        nbit_camera = 2
+        self.trigger = rtlink.Interface(rtlink.OInterface(nbit_extra_linktrig + nbit_linktrig,
+                                                          address_width=nbit_camera))
 
         
         # ROI rtio
@@ -51,12 +54,14 @@ class CXPGrabber(Module, AutoCSR):
             self.submodules.phy = trx = HostTRXPHYs(refclk, gt_pads, sys_clk_freq)
             self.submodules.core = core = CXPHostCore(trx.phys[0], trx.phys[0], sys_clk_freq)
 
+        # this PHY drives a single camera, at index 0
+        trigger_stb = self.trigger.o.stb & (self.trigger.o.address == 0)
         self.sync.rio += [
-            If(self.trigger.o.stb,
+            If(trigger_stb,
                 core.tx.trig_extra_linktrig.eq(self.trigger.o.data[:nbit_extra_linktrig]),
                 core.tx.trig_linktrig_mode.eq(self.trigger.o.data[nbit_extra_linktrig:]),
             ),
-            core.tx.trig_stb.eq(self.trigger.o.stb),
+            core.tx.trig_stb.eq(trigger_stb),
         ]
 
         self.submodules.stream_decoder = stream_decoder = StreamDecoder(res_width)
@@ -40,6 +40,11 @@ class SED(Module):
         for o, i in zip(self.gates.output, self.output_driver.input):
             self.comb += i.eq(o)
 
+        # number of events waiting in the lane picked by lane_select
+        self.lane_select = Signal(max=lane_count)
+        self.lane_level = Signal(len(self.fifos.levels[0]))
+        self.comb += self.lane_level.eq(Array(self.fifos.levels)[self.lane_select])
+
         if report_buffer_space:
             self.comb += [
                 self.cri.o_buffer_space_valid.eq(1),
@@ -40,6 +40,9 @@ class FIFOs(Module):
                 fifo.re.eq(output.re)
             ]
 
+        # number of events waiting in each lane
+        self.levels = [fifo.level for fifo in fifos]
+
         if report_buffer_space:
             def compute_max(elts):
                 l = len(elts)
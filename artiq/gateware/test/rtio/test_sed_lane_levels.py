@@ -0,0 +1,63 @@
+import unittest
+
+from migen import *
+
+from artiq.gateware import rtio
+from artiq.gateware.rtio import cri
+from artiq.gateware.rtio.sed.core import *
+from artiq.gateware.rtio.phy import ttl_simple
+
+
+class DUT(Module):
+    def __init__(self, **kwargs):
+        self.ttl0 = Signal()
+        self.submodules.phy0 = ttl_simple.Output(self.ttl0)
+
+        rtio_channels = [rtio.Channel.from_phy(self.phy0)]
+
+        self.submodules.sed = SED(rtio_channels, 0, **kwargs)
+        self.sync += [
+            self.sed.coarse_timestamp.eq(self.sed.coarse_timestamp + 1),
+            self.sed.minimum_coarse_timestamp.eq(self.sed.coarse_timestamp + 16)
+        ]
+        self.comb += self.sed.enable_spread.eq(0)
+
+
+def simulate(timestamps, **kwargs):
+    dut = DUT(**kwargs)
+    lane_count = kwargs.get("lane_count", 8)
+    levels = []
+
+    def gen():
+        yield dut.sed.cri.chan_sel.eq(0)
+        for timestamp in timestamps:
+            yield dut.sed.cri.o_timestamp.eq(timestamp)
+            yield dut.sed.cri.o_data.eq(1)
+            yield
+            yield dut.sed.cri.cmd.eq(cri.commands["write"])
+            yield
+            yield dut.sed.cri.cmd.eq(cri.commands["nop"])
+            yield
+            while (yield dut.sed.cri.o_status) & 0x01:
+                yield
+        # let the events settle into the FIFOs
+        for _ in range(4):
+            yield
+
+        for lane in range(lane_count):
+            yield dut.sed.lane_select.eq(lane)
+            yield
+            levels.append((yield dut.sed.lane_level))
+
+    run_simulation(dut, {"sys": gen()}, {"sys": 5, "rio": 5, "rio_phy": 5})
+    return levels
+
+
+class TestSEDLaneLevels(unittest.TestCase):
+    def test_empty(self):
+        self.assertEqual(simulate([], lane_count=2), [0, 0])
+
+    def test_levels(self):
+        # increasing timestamps stay in the current lane, and the earlier
+        # last one moves on to the next lane
+        self.assertEqual(simulate([1000, 1010, 1020, 500], lane_count=2), [3, 1])
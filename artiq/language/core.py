@@ -89,6 +89,15 @@ def subkernel(arg=None, destination=0, flags={}):
     actually start running. To help with that, subkernels can be preloaded, with
     ``subkernel_preload(subkernel)`` function. A call to a preloaded subkernel 
     will take less time, but only one subkernel can be preloaded at a time.
+    ``subkernel_preload_group(subkernel, *dependencies)`` preloads a subkernel
+    only after checking that the subkernels it calls are in place as well, so
+    a call does not fail halfway through for lack of one of them.
+
+    Results of a subkernel that only depend on its arguments can be cached
+    with ``subkernel_cache_results(subkernel, True)``. A later call with the
+    same arguments then returns the cached result instead of running the
+    subkernel again. ``subkernel_cache_clear(subkernel)`` drops the cached
+    results. Caching only applies to subkernels called from the master.
     """
     if isinstance(arg, str):
         def inner_decorator(function):
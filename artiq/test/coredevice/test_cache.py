@@ -22,6 +22,28 @@ class _Cache(EnvExperiment):
         self.get(key)
         self.put(key, value)
 
+    @kernel
+    def try_put(self, key, value):
+        return self.core_cache.try_put(key, value)
+
+    @kernel
+    def get_try_put(self, key, value):
+        self.get(key)
+        return self.try_put(key, value)
+
+    @kernel
+    def get_bytes(self, key):
+        return self.core_cache.get_bytes(key)
+
+    @kernel
+    def put_bytes(self, key, value):
+        self.core_cache.put_bytes(key, value)
+
+    @kernel
+    def get_put_bytes(self, key, value):
+        self.get_bytes(key)
+        self.put_bytes(key, value)
+
 
 class CacheTest(ExperimentCase):
     def test_get_empty(self):
@@ -46,3 +68,31 @@ class CacheTest(ExperimentCase):
         exp.put("x4", [1, 2, 3])
         with self.assertRaises(CacheError):
             exp.get_put("x4", [])
+
+    def test_try_put(self):
+        exp = self.create(_Cache)
+        self.assertTrue(exp.try_put("x7", [1, 2, 3]))
+        self.assertEqual(exp.get("x7"), [1, 2, 3])
+
+    def test_try_put_borrowed(self):
+        exp = self.create(_Cache)
+        if exp.core.target_cls == CortexA9Target:
+            self.skipTest("Zynq port memory management does not need CacheError")
+        exp.put("x8", [1, 2, 3])
+        self.assertFalse(exp.get_try_put("x8", []))
+        self.assertEqual(exp.get("x8"), [1, 2, 3])
+
+    def test_put_get_bytes(self):
+        exp = self.create(_Cache)
+        exp.put_bytes("x5", b"\x00\x01\xff")
+        self.assertEqual(exp.get_bytes("x5"), b"\x00\x01\xff")
+        # byte strings do not share keys with lists of integers
+        self.assertEqual(exp.get("x5"), [])
+
+    def test_borrow_bytes(self):
+        exp = self.create(_Cache)
+        if exp.core.target_cls == CortexA9Target:
+            self.skipTest("Zynq port memory management does not need CacheError")
+        exp.put_bytes("x6", b"abc")
+        with self.assertRaises(CacheError):
+            exp.get_put_bytes("x6", b"")
@@ -425,6 +425,38 @@ class Rounding(EnvExperiment):
         self.set_dataset("delta", t2 - t1)
 
 
+class SaveRestoreCursor(EnvExperiment):
+    def build(self):
+        self.setattr_device("core")
+
+    @kernel
+    def run(self):
+        self.core.reset()
+        saved = self.core.save_cursor_mu()
+        # cross the boundary between the low and high words of the cursor
+        self.core.restore_cursor_mu(0x1fffffff0)
+        delay_mu(0x20)
+        self.set_dataset("crossed", self.core.save_cursor_mu())
+        self.core.restore_cursor_mu(saved)
+        self.set_dataset("saved", saved)
+        self.set_dataset("restored", now_mu())
+
+
+class MaxCursor(EnvExperiment):
+    def build(self):
+        self.setattr_device("core")
+
+    @kernel
+    def run(self):
+        self.core.reset()
+        start = self.core.save_cursor_mu()
+        delay_mu(1000)
+        later = self.core.save_cursor_mu()
+        self.core.restore_cursor_mu(start)
+        self.set_dataset("later", self.core.max_cursor_mu(later))
+        self.set_dataset("kept", self.core.max_cursor_mu(start))
+
+
 class DummyException(Exception):
     pass
 
@@ -563,6 +595,17 @@ class CoredeviceTest(ExperimentCase):
         dt = self.dataset_mgr.get("delta")
         self.assertEqual(dt, 8000)
 
+    def test_save_restore_cursor(self):
+        self.execute(SaveRestoreCursor)
+        self.assertEqual(self.dataset_mgr.get("crossed"), 0x200000010)
+        self.assertEqual(self.dataset_mgr.get("restored"),
+                         self.dataset_mgr.get("saved"))
+
+    def test_max_cursor(self):
+        self.execute(MaxCursor)
+        self.assertEqual(self.dataset_mgr.get("later"),
+                         self.dataset_mgr.get("kept"))
+
 
 class RPCTiming(EnvExperiment):
     def build(self, repeats=100):
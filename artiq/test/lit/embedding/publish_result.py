@@ -0,0 +1,12 @@
+# RUN: env ARTIQ_DUMP_LLVM=%t %python -m artiq.compiler.testbench.embedding +compile %s
+# RUN: OutputCheck %s --file-to-check=%t.ll
+
+from artiq.language.core import *
+from artiq.language.types import *
+
+@kernel
+def entrypoint():
+    # CHECK: call void @publish_result\(i32 3, .*\), !dbg !.
+    publish_result(3, 42)
+
+# CHECK-L: declare void @publish_result(i32, i8**, { i8*, i32 }*) local_unnamed_addr
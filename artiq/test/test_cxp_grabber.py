@@ -0,0 +1,32 @@
+"""Test the host-side helpers of the CoaXPress grabber driver"""
+
+import unittest
+
+from artiq.coredevice.cxp_grabber import (
+    decode_link_status, LINK_STATUS_UP, LINK_STATUS_ERRORS)
+
+
+def encode_link_status(up, lanes, bitrate_code, errors=0):
+    # Same layout as link_status_word in the kernel CPU firmware
+    return int(up) | lanes << 8 | bitrate_code << 16 | errors << 24
+
+
+class LinkStatusCase(unittest.TestCase):
+    def test_down(self):
+        self.assertEqual(decode_link_status(0), (False, 0, 0))
+
+    def test_up(self):
+        self.assertEqual(decode_link_status(LINK_STATUS_UP), (True, 0, 0))
+
+    def test_fields(self):
+        for up in (False, True):
+            for lanes in (1, 2, 4, 0xff):
+                for bitrate_code in (0x28, 0x30, 0x38, 0x40, 0x48, 0x50, 0x58):
+                    word = encode_link_status(up, lanes, bitrate_code)
+                    self.assertEqual(decode_link_status(word),
+                                     (up, lanes, bitrate_code))
+
+    def test_errors_ignored(self):
+        word = encode_link_status(True, 4, 0x58, errors=0xff)
+        self.assertEqual(word & LINK_STATUS_ERRORS, LINK_STATUS_ERRORS)
+        self.assertEqual(decode_link_status(word), (True, 4, 0x58))